@@ -22,12 +22,24 @@ use json::JsonValue;
 quick_error! {
   #[derive(Debug)]
   pub enum Error {
+    BadRequest(error: String) {
+      display("{}", error)
+    }
     NotAuthenticated(error: String) {
       display("{}", error)
     }
+    Forbidden(error: String) {
+      display("{}", error)
+    }
     NotFound(error: String) {
       display("{}", error)
     }
+    Conflict(error: String) {
+      display("{}", error)
+    }
+    TooManyRequests(error: String) {
+      display("{}", error)
+    }
     IOError(error: String) {
       display("{}", error)
     }
@@ -79,8 +91,12 @@ impl std::convert::From<uuid::Error> for Error {
 impl Error {
   fn to_code(&self) -> usize {
     match self {
+      Error::BadRequest(_) => 400,
       Error::NotAuthenticated(_) => 401,
+      Error::Forbidden(_) => 403,
       Error::NotFound(_) => 404,
+      Error::Conflict(_) => 409,
+      Error::TooManyRequests(_) => 429,
       Error::NotImplemented => 501,
       _ => 500,
     }
@@ -88,8 +104,12 @@ impl Error {
 
   fn to_class_name(&self) -> &str {
     match self {
+      Error::BadRequest(_) => "bad-request",
       Error::NotAuthenticated(_) => "not-authenticated",
+      Error::Forbidden(_) => "forbidden",
       Error::NotFound(_) => "not-found",
+      Error::Conflict(_) => "conflict",
+      Error::TooManyRequests(_) => "too-many-requests",
       Error::IOError(_) => "io-errors",
       Error::GeneralError(_) => "general-errors",
       Error::CameraError(_) => "general-errors",
@@ -99,8 +119,12 @@ impl Error {
 
   fn to_name(&self) -> &str {
     match self {
+      Error::BadRequest(_) => "BadRequest",
       Error::NotAuthenticated(_) => "NotAuthenticated",
+      Error::Forbidden(_) => "Forbidden",
       Error::NotFound(_) => "NotFound",
+      Error::Conflict(_) => "Conflict",
+      Error::TooManyRequests(_) => "TooManyRequests",
       Error::IOError(_) => "IOError",
       Error::GeneralError(_) => "GeneralError",
       Error::CameraError(_) => "GeneralError",
@@ -30,6 +30,8 @@ pub struct Account {
 pub struct Context {
   pub request: Option<actix_web::dev::RequestHead>,
   pub account: Arc<RwLock<Account>>,
+  // login session of account
+  pub session: Arc<RwLock<Option<String>>>,
   pub timestamp: Duration,
 }
 
@@ -38,16 +40,25 @@ impl Context {
     Arc::new(RwLock::new(Account { id: ID_MIN, email: "".to_string() }))
   }
 
+  fn new(request: Option<actix_web::dev::RequestHead>) -> Self {
+    Self {
+      request,
+      timestamp: Context::since_the_epoch(),
+      account: Self::guest(),
+      session: Arc::new(RwLock::new(None)),
+    }
+  }
+
   pub fn local() -> Self {
-    Self { request: None, timestamp: Context::since_the_epoch(), account: Self::guest() }
+    Self::new(None)
   }
 
   pub fn rest(request: actix_web::dev::RequestHead) -> Self {
-    Self { request: Some(request), timestamp: Context::since_the_epoch(), account: Self::guest() }
+    Self::new(Some(request))
   }
 
   pub fn websocket(request: actix_web::dev::RequestHead) -> Self {
-    Self { request: Some(request), timestamp: Context::since_the_epoch(), account: Self::guest() }
+    Self::new(Some(request))
   }
 
   fn since_the_epoch() -> Duration {
@@ -59,6 +70,26 @@ impl Context {
 pub trait Services: Send + Sync {
   fn register(&mut self, service: Arc<dyn Service>);
   fn service<S: AsRef<str> + ToString>(&self, name: S) -> Arc<dyn Service>;
+  // registered services ordered by path
+  fn registered(&self) -> Vec<Arc<dyn Service>>;
+}
+
+/// Commands of `Service`, each one is a method of it
+pub const COMMANDS: [&str; 6] = ["find", "get", "create", "update", "patch", "remove"];
+
+/// Contract of service as JSON Schema objects, `Null` if shape isn't declared.
+/// `response` describe single item, `find` respond with page of items.
+#[derive(Debug, Clone)]
+pub struct Schema {
+  pub params: JsonValue,
+  pub data: JsonValue,
+  pub response: JsonValue,
+}
+
+impl Schema {
+  pub fn new(params: JsonValue, data: JsonValue, response: JsonValue) -> Self {
+    Schema { params, data, response }
+  }
 }
 
 pub trait Service: Send + Sync {
@@ -71,6 +102,15 @@ pub trait Service: Send + Sync {
   fn patch(&self, ctx: Context, id: String, data: Data, params: Params) -> Result;
   fn remove(&self, ctx: Context, id: String, params: Params) -> Result;
 
+  fn schema(&self) -> Option<Schema> {
+    None
+  }
+
+  // commands service implements, others respond `NotImplemented`
+  fn commands(&self) -> &[&str] {
+    &COMMANDS
+  }
+
   fn enrich(&self, params: &Params) -> bool {
     self.params(params)["enrich"].as_bool().unwrap_or(true)
   }
@@ -0,0 +1,282 @@
+use actix_web::http::header;
+use json::JsonValue;
+
+use crate::auth::decode_token;
+use crate::commutator::Application;
+use service::error::Error;
+use service::{Account, Context};
+use values::{ID, ID_MIN};
+
+// service commands that are available without authentication
+const PUBLIC: [(&str, &str); 2] = [("authentication", "create"), ("users", "create")];
+
+// service commands of account that don't belong to workspace, everything else requires one
+const ACCOUNT: [(&str, &str); 7] = [
+  ("authentication", "remove"),
+  ("companies", "find"),
+  ("companies", "create"),
+  ("sessions", "find"),
+  ("sessions", "get"),
+  ("sessions", "remove"),
+  ("users", "get"),
+];
+
+/// Check that context is authenticated, account is member of workspace in `oid` of params
+/// or data (for `companies` service workspace is `id`) and roles of member allow the command.
+/// Request without workspace is denied unless command is in `ACCOUNT` list.
+pub(crate) fn authorize(
+  app: &Application,
+  ctx: &Context,
+  path: &str,
+  command: &str,
+  id: Option<&str>,
+  data: Option<&JsonValue>,
+  params: &JsonValue,
+) -> Result<Account, Error> {
+  if PUBLIC.contains(&(path, command)) {
+    if path == "authentication" {
+      app.limits.login(ctx, data)?;
+    }
+    return Ok(ctx.account.read().unwrap().clone());
+  }
+
+  let account = authenticate(app, ctx)?;
+  app.limits.command(ctx, &account)?;
+
+  let ctx = ctx_of(params);
+
+  let workspaces = workspaces(path, id, data, params);
+  if workspaces.is_empty() && !ACCOUNT.contains(&(path, command)) {
+    return Err(Error::Forbidden(format!("`{command}` at `{path}` requires workspace")));
+  }
+
+  for wid in workspaces {
+    let wid = crate::services::string_to_id(wid.to_string())
+      .map_err(|_| Error::Forbidden(format!("no access to workspace `{wid}`")))?;
+
+    let ws = app.wss.get(&wid);
+
+    let roles = match ws.members().roles_of(&account.id)? {
+      Some(roles) => roles,
+      None => return Err(Error::Forbidden(format!("no access to workspace `{}`", wid.to_base64()))),
+    };
+
+    if !ws.roles().allowed(&roles, path, &ctx, command)? {
+      return Err(Error::Forbidden(format!("`{command}` at `{path}` {ctx:?} is not allowed")));
+    }
+  }
+
+  Ok(account)
+}
+
+/// Workspaces referred by request: `oid` of params or data, for `companies` service it's `id`.
+pub(crate) fn workspaces<'a>(
+  path: &str,
+  id: Option<&'a str>,
+  data: Option<&'a JsonValue>,
+  params: &'a JsonValue,
+) -> Vec<&'a str> {
+  let params = if params.is_array() { &params[0] } else { params };
+
+  let mut workspaces = vec![params["oid"].as_str(), data.and_then(|data| data["oid"].as_str())];
+  if path == "companies" {
+    workspaces.push(id);
+  }
+
+  let mut workspaces: Vec<&str> = workspaces.into_iter().flatten().collect();
+  workspaces.dedup();
+  workspaces
+}
+
+pub(crate) fn ctx_of(params: &JsonValue) -> Vec<String> {
+  let params = if params.is_array() { &params[0] } else { params };
+
+  params["ctx"]
+    .members()
+    .filter_map(|c| c.as_str())
+    .map(|c| c.to_string())
+    .collect()
+}
+
+/// Account of context. If context isn't authenticated yet bearer token of request is used.
+/// Login session of authenticated context (socket) is checked on every call.
+pub(crate) fn authenticate(app: &Application, ctx: &Context) -> Result<Account, Error> {
+  let account = ctx.account.read().unwrap().clone();
+  if account.id != ID_MIN {
+    let session = ctx.session.read().unwrap().clone();
+    if let Some(session) = session {
+      if !app.sessions.check(&account.id, &session)? {
+        *ctx.account.write().unwrap() = Account { id: ID_MIN, email: "".into() };
+        *ctx.session.write().unwrap() = None;
+        return Err(Error::NotAuthenticated("session is revoked or expired".into()));
+      }
+    }
+    return Ok(account);
+  }
+
+  let token = ctx
+    .request
+    .as_ref()
+    .and_then(|request| request.headers.get(header::AUTHORIZATION))
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.strip_prefix("Bearer "));
+
+  match token {
+    Some(token) => {
+      let (email, session) =
+        decode_token(app, token).map_err(|e| Error::NotAuthenticated(e.to_string()))?;
+      let account = Account { id: ID::from(email.as_str()), email };
+
+      *ctx.account.write().unwrap() = account.clone();
+      *ctx.session.write().unwrap() = Some(session);
+
+      Ok(account)
+    },
+    None => Err(Error::NotAuthenticated("not authenticated".into())),
+  }
+}
+
+pub(crate) fn is_guest(account: &Account) -> bool {
+  account.id == ID_MIN
+}
+
+/// workspaces where account is member
+pub(crate) fn workspaces_of(app: &Application, id: &ID) -> Result<Vec<ID>, Error> {
+  Ok(
+    app
+      .wss
+      .list()?
+      .into_iter()
+      .filter(|ws| ws.members().is_member(id))
+      .map(|ws| ws.id)
+      .collect(),
+  )
+}
+
+pub(crate) fn http_error(error: Error) -> actix_web::Error {
+  match error {
+    Error::BadRequest(msg) => actix_web::error::ErrorBadRequest(msg),
+    Error::NotAuthenticated(msg) => actix_web::error::ErrorUnauthorized(msg),
+    Error::Forbidden(msg) => actix_web::error::ErrorForbidden(msg),
+    Error::TooManyRequests(msg) => actix_web::error::ErrorTooManyRequests(msg),
+    e => actix_web::error::ErrorInternalServerError(e.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::roles::{OWNER, VIEWER};
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use chrono::Duration;
+  use service::utils::json::JsonParams;
+  use std::sync::Arc;
+
+  #[actix_web::test]
+  async fn test_authorize() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+
+    let wid = ID::random();
+    let ws = app.wss.create(wid).unwrap();
+
+    let member = Account { id: ID::from("member@nae.org"), email: "member@nae.org".into() };
+    let stranger = Account { id: ID::from("stranger@nae.org"), email: "stranger@nae.org".into() };
+    ws.members().add(&member, &[OWNER.to_string()]).unwrap();
+
+    let params = json::object! { oid: wid.to_base64(), ctx: vec!["goods"] };
+
+    // guest
+    let ctx = Context::local();
+    match authorize(&app, &ctx, "memories", "find", None, None, &params) {
+      Err(Error::NotAuthenticated(_)) => {},
+      r => panic!("expected not authenticated, got {r:?}"),
+    }
+    assert!(authorize(&app, &ctx, "authentication", "create", None, None, &params).is_ok());
+
+    // not a member
+    let ctx = Context::local();
+    *ctx.account.write().unwrap() = stranger;
+    let data = json::object! { name: "g1" };
+    match authorize(&app, &ctx, "memories", "create", None, Some(&data), &params) {
+      Err(Error::Forbidden(_)) => {},
+      r => panic!("expected forbidden, got {r:?}"),
+    }
+    let id = wid.to_base64();
+    match authorize(&app, &ctx, "companies", "get", Some(&id), None, &JsonValue::Null) {
+      Err(Error::Forbidden(_)) => {},
+      r => panic!("expected forbidden, got {r:?}"),
+    }
+
+    // member
+    let ctx = Context::local();
+    *ctx.account.write().unwrap() = member.clone();
+    assert!(authorize(&app, &ctx, "memories", "find", None, None, &json::array![params]).is_ok());
+    let data = json::object! { oid: wid.to_base64(), name: "renamed" };
+    assert!(
+      authorize(&app, &ctx, "companies", "patch", Some(&id), Some(&data), &JsonValue::Null).is_ok()
+    );
+
+    // workspace in data is checked too
+    let data = json::object! { oid: ID::random().to_base64(), name: "person" };
+    match authorize(&app, &ctx, "people", "create", None, Some(&data), &JsonValue::Null) {
+      Err(Error::Forbidden(_)) => {},
+      r => panic!("expected forbidden, got {r:?}"),
+    }
+
+    assert_eq!(workspaces_of(&app, &member.id).unwrap(), vec![wid]);
+
+    // no workspace at request
+    for path in ["memories", "reindex", "inventory"] {
+      match authorize(&app, &ctx, path, "find", None, None, &JsonValue::Null) {
+        Err(Error::Forbidden(_)) => {},
+        r => panic!("expected forbidden for {path}, got {r:?}"),
+      }
+    }
+    assert!(authorize(&app, &ctx, "sessions", "find", None, None, &JsonValue::Null).is_ok());
+    assert!(authorize(&app, &ctx, "companies", "find", None, None, &JsonValue::Null).is_ok());
+
+    // viewer can only read
+    let viewer = Account { id: ID::from("viewer@nae.org"), email: "viewer@nae.org".into() };
+    ws.members().add(&viewer, &[VIEWER.to_string()]).unwrap();
+
+    let ctx = Context::local();
+    *ctx.account.write().unwrap() = viewer;
+    assert!(authorize(&app, &ctx, "memories", "get", Some("goods/2023"), None, &params).is_ok());
+    let data = json::object! { name: "g1" };
+    match authorize(&app, &ctx, "memories", "create", None, Some(&data), &params) {
+      Err(Error::Forbidden(_)) => {},
+      r => panic!("expected forbidden, got {r:?}"),
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_revoked_session() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+
+    let member = Account { id: ID::from("member@nae.org"), email: "member@nae.org".into() };
+    let (session, _) = app.sessions.create(&member.id, "test", Duration::days(1)).unwrap();
+    let sid = session["_id"].string();
+
+    // authenticated socket
+    let ctx = Context::local();
+    *ctx.account.write().unwrap() = member.clone();
+    *ctx.session.write().unwrap() = Some(sid.clone());
+    assert_eq!(authenticate(&app, &ctx).unwrap().id, member.id);
+
+    app.sessions.revoke(&member.id, &sid).unwrap();
+
+    match authenticate(&app, &ctx) {
+      Err(Error::NotAuthenticated(_)) => {},
+      r => panic!("expected not authenticated, got {r:?}"),
+    }
+    assert!(is_guest(&ctx.account.read().unwrap()));
+    assert!(ctx.session.read().unwrap().is_none());
+  }
+}
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
 
-use actix_web::http::header;
-use actix_web::{get, post, web, Error, HttpRequest, HttpResponse, Responder};
+use actix_web::http::{header, StatusCode};
+use actix_web::{delete, get, patch, post, put, web, Error, HttpRequest, HttpResponse, Responder};
 use json::{object, JsonValue};
+use qstring::QString;
 
 use crate::animo::db::AnimoDB;
 use crate::animo::memory::Memory;
 use crate::animo::memory::{ChangeTransformation, TransformationKey};
 use crate::commutator::Application;
+use crate::services::Mutation;
 use service::{Context, Services};
 
 pub async fn not_implemented() -> impl Responder {
@@ -40,86 +42,190 @@ pub(crate) async fn memory_modify(
   Ok(HttpResponse::Ok().body(""))
 }
 
-#[post("/api/docs")]
-pub async fn docs_create(
+/// `GET /v1/{service}`
+#[get("/{service}")]
+pub(crate) async fn service_find(
   req: HttpRequest,
   app: web::Data<Application>,
-  data: web::Json<serde_json::Value>,
-  params: web::Query<HashMap<String, String>>,
-) -> Result<HttpResponse, Error> {
-  let data = json::parse(&data.0.to_string()).unwrap();
-
-  let ctx: Vec<String> = params["ctx"].split(",").map(|s| s.to_string()).collect();
-  let oid = params["oid"].clone();
-
-  let params: JsonValue = object! {"ctx": ctx, "oid": oid};
-
-  let ctx = Context::rest(req.head().clone());
-
-  let result = web::block(move || app.service("docs").create(ctx, data, params))
-    .await?
-    .map_err(actix_web::error::ErrorInternalServerError)?;
-
-  let result: serde_json::Value = serde_json::from_str(&result.dump()).unwrap();
-
-  Ok(HttpResponse::Ok().json(result))
+  path: web::Path<String>,
+) -> HttpResponse {
+  call(req, app, path.into_inner(), "find", None, None).await
 }
 
-#[post("/api/docs/update")]
-pub async fn docs_update(
+/// `GET /v1/{service}/{id}`
+#[get("/{service}/{id}")]
+pub(crate) async fn service_get(
   req: HttpRequest,
-  // path: web::Path<(String)>,
   app: web::Data<Application>,
-  data: web::Json<serde_json::Value>,
-  params: web::Query<HashMap<String, String>>,
-) -> Result<HttpResponse, Error> {
-  println!("docs_update {req:?}");
-
-  // let (id) = path.into_inner();
-
-  let data = json::parse(&data.0.to_string()).unwrap();
-
-  let id = params["id"].clone();
-
-  let ctx: Vec<String> = params["ctx"].split(",").map(|s| s.to_string()).collect();
-  let oid = params["oid"].clone();
-
-  let params: JsonValue = object! {"ctx": ctx, "oid": oid};
-
-  let ctx = Context::rest(req.head().clone());
-
-  let result = web::block(move || app.service("docs").update(ctx, id, data, params))
-    .await?
-    .map_err(actix_web::error::ErrorInternalServerError)?;
-
-  let result: serde_json::Value = serde_json::from_str(&result.dump()).unwrap();
+  path: web::Path<(String, String)>,
+) -> HttpResponse {
+  let (service, id) = path.into_inner();
+  call(req, app, service, "get", Some(id), None).await
+}
 
-  Ok(HttpResponse::Ok().json(result))
+/// `POST /v1/{service}`
+#[post("/{service}")]
+pub(crate) async fn service_create(
+  req: HttpRequest,
+  app: web::Data<Application>,
+  path: web::Path<String>,
+  body: web::Bytes,
+) -> HttpResponse {
+  call(req, app, path.into_inner(), "create", None, Some(body)).await
 }
 
-#[get("/api/docs/find")]
-pub async fn docs_find(
+/// `PUT /v1/{service}/{id}`
+#[put("/{service}/{id}")]
+pub(crate) async fn service_update(
   req: HttpRequest,
   app: web::Data<Application>,
-  params: web::Query<HashMap<String, String>>,
-) -> Result<HttpResponse, Error> {
-  let ctx: Vec<String> = params["ctx"].split(",").map(|s| s.to_string()).collect();
+  path: web::Path<(String, String)>,
+  body: web::Bytes,
+) -> HttpResponse {
+  let (service, id) = path.into_inner();
+  call(req, app, service, "update", Some(id), Some(body)).await
+}
 
-  let oid = params["oid"].clone();
+/// `PATCH /v1/{service}/{id}`
+#[patch("/{service}/{id}")]
+pub(crate) async fn service_patch(
+  req: HttpRequest,
+  app: web::Data<Application>,
+  path: web::Path<(String, String)>,
+  body: web::Bytes,
+) -> HttpResponse {
+  let (service, id) = path.into_inner();
+  call(req, app, service, "patch", Some(id), Some(body)).await
+}
 
-  let filter = params["filter"].clone();
+/// `DELETE /v1/{service}/{id}`
+#[delete("/{service}/{id}")]
+pub(crate) async fn service_remove(
+  req: HttpRequest,
+  app: web::Data<Application>,
+  path: web::Path<(String, String)>,
+) -> HttpResponse {
+  let (service, id) = path.into_inner();
+  call(req, app, service, "remove", Some(id), None).await
+}
 
-  let params: JsonValue = object! {"oid": oid, "ctx": ctx, "limit": 2, "skip": 0, "filter": filter};
+// same flow as socket event: authorize, then query service or mutate through application
+async fn call(
+  req: HttpRequest,
+  app: web::Data<Application>,
+  service: String,
+  command: &'static str,
+  id: Option<String>,
+  body: Option<web::Bytes>,
+) -> HttpResponse {
+  let params = query_to_params(req.query_string());
+
+  let data = match body.as_deref() {
+    None => JsonValue::Null,
+    Some(bytes) if bytes.is_empty() => JsonValue::Null,
+    Some(bytes) => match std::str::from_utf8(bytes).ok().and_then(|str| json::parse(str).ok()) {
+      Some(data) => data,
+      None => return error_response(service::error::Error::BadRequest("invalid json".into())),
+    },
+  };
 
   let ctx = Context::rest(req.head().clone());
 
-  let result = web::block(move || app.service("docs").find(ctx, params))
-    .await?
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+  let result = web::block(move || {
+    let data_ref = if data.is_null() { None } else { Some(&data) };
+    crate::access::authorize(&app, &ctx, &service, command, id.as_deref(), data_ref, &params)?;
+
+    let id = id.unwrap_or_default();
+    match command {
+      "find" => app.service(&service).find(ctx, params),
+      "get" => app.service(&service).get(ctx, id, params),
+      "create" => app.handle(Mutation::Create(ctx, service, data, params)),
+      "update" => app.handle(Mutation::Update(ctx, service, id, data, params)),
+      "patch" => app.handle(Mutation::Patch(ctx, service, id, data, params)),
+      "remove" => app.handle(Mutation::Remove(ctx, service, id, params)),
+      _ => unreachable!(),
+    }
+  })
+  .await;
+
+  match result {
+    Ok(Ok(data)) => {
+      let mut response =
+        if command == "create" { HttpResponse::Created() } else { HttpResponse::Ok() };
+      response
+        .append_header(header::ContentType(mime::APPLICATION_JSON))
+        .body(data.dump())
+    },
+    Ok(Err(error)) => error_response(error),
+    Err(error) => error_response(service::error::Error::GeneralError(error.to_string())),
+  }
+}
 
-  let result: serde_json::Value = serde_json::from_str(&result.dump()).unwrap();
+/// Error in the same json shape as socket acknowledgement, status is error's code.
+pub(crate) fn error_response(error: service::error::Error) -> HttpResponse {
+  let data = error.to_json();
+  let status = data["code"]
+    .as_u16()
+    .and_then(|code| StatusCode::from_u16(code).ok())
+    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
 
-  Ok(HttpResponse::Ok().json(result))
+  HttpResponse::build(status)
+    .append_header(header::ContentType(mime::APPLICATION_JSON))
+    .body(data.dump())
+}
+
+/// Query string to params of socket event: `ctx=a,b` is array, `a[b]=c` is nested object,
+/// `a[]=b` is array item, values of `$` keys (`$limit`, `$sort[name]`) are numbers.
+pub(crate) fn query_to_params(query: &str) -> JsonValue {
+  fn insert(target: &mut JsonValue, path: &[&str], value: JsonValue) {
+    match path.split_first() {
+      None => *target = value,
+      Some((&"", rest)) => {
+        if !target.is_array() {
+          *target = JsonValue::new_array();
+        }
+        if rest.is_empty() {
+          target.push(value).ok();
+        } else {
+          let mut item = JsonValue::Null;
+          insert(&mut item, rest, value);
+          target.push(item).ok();
+        }
+      },
+      Some((key, rest)) => {
+        if !target.is_object() {
+          *target = JsonValue::new_object();
+        }
+        insert(&mut target[*key], rest, value)
+      },
+    }
+  }
+
+  let mut params = JsonValue::new_object();
+  for (key, value) in QString::from(query).into_pairs() {
+    let (name, path) = match key.find('[') {
+      Some(pos) => (&key[..pos], &key[pos..]),
+      None => (key.as_str(), ""),
+    };
+    let path: Vec<&str> = path.split(']').filter_map(|part| part.strip_prefix('[')).collect();
+
+    let value = if name == "ctx" && path.is_empty() {
+      value.split(',').filter(|s| !s.is_empty()).collect::<Vec<_>>().into()
+    } else if let (true, Ok(number)) = (name.starts_with('$'), value.parse::<i64>()) {
+      number.into()
+    } else {
+      match value.as_str() {
+        "true" => true.into(),
+        "false" => false.into(),
+        _ => value.into(),
+      }
+    };
+
+    let mut full = vec![name];
+    full.extend(path);
+    insert(&mut params, &full, value);
+  }
+  params
 }
 
 #[get("/api/inventory")]
@@ -139,9 +245,12 @@ pub async fn inventory_find(
 
   let ctx = Context::rest(req.head().clone());
 
-  let result = web::block(move || app.service("inventory").find(ctx, params))
-    .await?
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+  let result = web::block(move || {
+    crate::access::authorize(&app, &ctx, "inventory", "find", None, None, &params)?;
+    app.service("inventory").find(ctx, params)
+  })
+  .await?
+  .map_err(crate::access::http_error)?;
 
   // let result: serde_json::Value = serde_json::from_str(&result.dump()).unwrap();
 
@@ -151,3 +260,132 @@ pub async fn inventory_find(
 
   Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::auth::{signup_procedure, SignUpRequest};
+  use crate::hr::services::companies::Companies;
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use actix_web::{test, App};
+  use std::sync::Arc;
+
+  #[test]
+  fn test_query_to_params() {
+    let params = query_to_params(
+      "oid=yjmg&ctx=warehouse,goods&$limit=5&$skip=10&$sort[name]=-1&filter[name]=g1&tags[]=a&tags[]=b&archived=false",
+    );
+    assert_eq!(
+      params,
+      object! {
+        oid: "yjmg",
+        ctx: ["warehouse", "goods"],
+        "$limit": 5,
+        "$skip": 10,
+        "$sort": { name: -1 },
+        filter: { name: "g1" },
+        tags: ["a", "b"],
+        archived: false,
+      }
+    );
+
+    assert_eq!(query_to_params(""), object! {});
+    assert_eq!(query_to_params("ctx[]=a&ctx[]=b"), object! { ctx: ["a", "b"] });
+  }
+
+  #[actix_web::test]
+  async fn test_rest_commands() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(Companies::new(app.clone()));
+    app.register(crate::services::Members::new(app.clone()));
+
+    let signup = SignUpRequest { email: "rest@test.com".into(), password: "Nae".into() };
+    let (_, tokens) = signup_procedure(&app, signup).unwrap();
+    let bearer = (header::AUTHORIZATION, format!("Bearer {}", tokens.access));
+
+    let server = test::init_service(
+      App::new().app_data(web::Data::new(app)).service(
+        web::scope("/v1")
+          .service(service_find)
+          .service(service_get)
+          .service(service_create)
+          .service(service_update)
+          .service(service_patch)
+          .service(service_remove),
+      ),
+    )
+    .await;
+
+    // not authenticated
+    let req = test::TestRequest::post()
+      .uri("/v1/companies")
+      .set_payload(r#"{"name":"test"}"#)
+      .to_request();
+    let response = test::call_service(&server, req).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let error = json::parse(std::str::from_utf8(&test::read_body(response).await).unwrap()).unwrap();
+    assert_eq!(error["code"], 401);
+
+    // invalid body
+    let req = test::TestRequest::post()
+      .uri("/v1/companies")
+      .insert_header(bearer.clone())
+      .set_payload("{broken")
+      .to_request();
+    let response = test::call_service(&server, req).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error = json::parse(std::str::from_utf8(&test::read_body(response).await).unwrap()).unwrap();
+    assert_eq!(error["name"], "BadRequest");
+
+    let req = test::TestRequest::post()
+      .uri("/v1/companies")
+      .insert_header(bearer.clone())
+      .set_payload(r#"{"name":"test"}"#)
+      .to_request();
+    let response = test::call_service(&server, req).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let company =
+      json::parse(std::str::from_utf8(&test::read_body(response).await).unwrap()).unwrap();
+    let oid = company["_id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+      .uri(&format!("/v1/companies/{oid}"))
+      .insert_header(bearer.clone())
+      .to_request();
+    let response = test::call_and_read_body(&server, req).await;
+    let data = json::parse(std::str::from_utf8(&response).unwrap()).unwrap();
+    assert_eq!(data["name"], "test");
+
+    // creator is the only member
+    let req = test::TestRequest::get()
+      .uri(&format!("/v1/members?oid={oid}&$limit=5"))
+      .insert_header(bearer.clone())
+      .to_request();
+    let response = test::call_and_read_body(&server, req).await;
+    let data = json::parse(std::str::from_utf8(&response).unwrap()).unwrap();
+    assert_eq!(data["total"], 1);
+
+    let req = test::TestRequest::patch()
+      .uri(&format!("/v1/companies/{oid}"))
+      .insert_header(bearer.clone())
+      .set_payload(r#"{"name":"renamed"}"#)
+      .to_request();
+    let response = test::call_and_read_body(&server, req).await;
+    let data = json::parse(std::str::from_utf8(&response).unwrap()).unwrap();
+    assert_eq!(data["name"], "renamed");
+
+    // unknown service
+    let req = test::TestRequest::get()
+      .uri(&format!("/v1/unknown?oid={oid}"))
+      .insert_header(bearer)
+      .to_request();
+    let response = test::call_service(&server, req).await;
+    assert!(!response.status().is_success());
+
+    tmp_dir.close().unwrap();
+  }
+}
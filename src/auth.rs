@@ -10,6 +10,7 @@ use pbkdf2::{
 
 use crate::animo::error::DBError;
 use crate::animo::memory::{ChangeTransformation, TransformationKey, Value};
+use crate::mail::Mail;
 use crate::warehouse::primitive_types;
 use crate::{animo::memory::Memory, animo::shared::DESC, commutator::Application};
 use service::utils::time::now_in_millis;
@@ -18,6 +19,17 @@ use values::ID;
 
 const ALGORITHM: Algorithm = Algorithm::HS256;
 
+// access token is short-lived, session continue by refresh token
+const ACCESS_TOKEN_MINUTES: i64 = 15;
+const SESSION_DAYS: i64 = 30;
+const REMEMBER_ME_SESSION_DAYS: i64 = 365;
+
+const VERIFICATION_HOURS: i64 = 48;
+const PASSWORD_RESET_MINUTES: i64 = 60;
+
+const VERIFICATION: &str = "verification";
+const PASSWORD_RESET: &str = "password_reset";
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct Claims {
   aud: String, // Audience [optional]
@@ -26,9 +38,17 @@ struct Claims {
   iss: String, // Issuer [optional]
   nbf: u128, // Not Before (as UTC timestamp) [optional]
   sub: String, // Subject (whom token refers to) [optional]
+  sid: String, // Session id
+}
+
+pub(crate) struct Tokens {
+  pub(crate) access: String,
+  pub(crate) refresh: String,
+  pub(crate) session: String,
 }
 
-pub(crate) fn decode_token(app: &Application, token: &str) -> Result<String, DBError> {
+/// email and session id of valid token
+pub(crate) fn decode_token(app: &Application, token: &str) -> Result<(String, String), DBError> {
   let key = DecodingKey::from_secret(app.settings.jwt_config.secret.as_bytes());
   match jsonwebtoken::decode::<Claims>(token, &key, &Validation::new(ALGORITHM)) {
     Ok(token) => {
@@ -57,17 +77,19 @@ pub(crate) fn decode_token(app: &Application, token: &str) -> Result<String, DBE
             _ => None,
           };
 
-        match last_logout {
-          Some(ts) => {
-            log::debug!("last_logout {} vs {} = {}", ts, token.claims.iat, token.claims.iat > ts);
-            if token.claims.iat > ts {
-              Ok(token.claims.sub)
-            } else {
-              Err(DBError::from("Unauthorised".to_string()))
-            }
-          },
-          None => Ok(token.claims.sub),
+        if let Some(ts) = last_logout {
+          log::debug!("last_logout {} vs {} = {}", ts, token.claims.iat, token.claims.iat > ts);
+          if token.claims.iat <= ts {
+            return Err(DBError::from("Unauthorised".to_string()));
+          }
         }
+
+        // revoked or expired session
+        if !app.sessions.check(&account_id, &token.claims.sid).map_err(|e| e.to_string())? {
+          return Err(DBError::from("Unauthorised".to_string()));
+        }
+
+        Ok((token.claims.sub, token.claims.sid))
       } else {
         Err(DBError::from("Unauthorised".to_string()))
       }
@@ -109,7 +131,7 @@ pub trait JWTAuth<T> {
 
 impl JWTAuth<Account> for Account {
   fn jwt(app: &Application, token: &str) -> Result<Account, DBError> {
-    let email = decode_token(app, token)?;
+    let (email, _) = decode_token(app, token)?;
     let id = ID::from(email.as_str());
     Ok(Account { id, email })
   }
@@ -174,11 +196,19 @@ pub(crate) struct LoginRequest {
   // #[validate(length(min = 6))]
   pub(crate) password: String,
   pub(crate) remember_me: bool,
+  #[serde(default)]
+  pub(crate) device: String,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct LoginResponse {
   token: String,
+  refresh_token: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub(crate) struct RefreshRequest {
+  pub(crate) refresh_token: String,
 }
 
 #[post("/logout")]
@@ -186,21 +216,38 @@ pub(crate) async fn logout(
   auth: BearerAuth,
   app: web::Data<Application>,
 ) -> Result<HttpResponse, Error> {
-  let account =
-    Account::jwt(app.get_ref(), auth.token()).map_err(actix_web::error::ErrorUnauthorized)?;
+  let (email, session) =
+    decode_token(app.get_ref(), auth.token()).map_err(actix_web::error::ErrorUnauthorized)?;
+  let account = Account { id: ID::from(email.as_str()), email };
 
-  logout_procedure(&app, account).map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+  logout_procedure(&app, account, Some(session))
+    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
 
   Ok(HttpResponse::Ok().json("logged out"))
 }
 
+#[post("/refresh")]
+pub(crate) async fn refresh_post(
+  app: web::Data<Application>,
+  data: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, Error> {
+  match refresh_procedure(app.get_ref(), &data.refresh_token) {
+    Ok((_, tokens)) => Ok(
+      HttpResponse::Ok().json(LoginResponse { token: tokens.access, refresh_token: tokens.refresh }),
+    ),
+    Err(mgs) => Err(actix_web::error::ErrorUnauthorized(mgs)),
+  }
+}
+
 #[post("/signup")]
 pub(crate) async fn signup_post(
   app: web::Data<Application>,
   data: web::Json<SignUpRequest>,
 ) -> Result<HttpResponse, Error> {
   match signup_procedure(app.get_ref(), data.into_inner()) {
-    Ok((_, token)) => Ok(HttpResponse::Ok().json(token)),
+    Ok((_, tokens)) => Ok(
+      HttpResponse::Ok().json(LoginResponse { token: tokens.access, refresh_token: tokens.refresh }),
+    ),
     Err(mgs) => Err(actix_web::error::ErrorUnauthorized(mgs)),
   }
 }
@@ -208,19 +255,11 @@ pub(crate) async fn signup_post(
 pub(crate) fn signup_procedure(
   app: &Application,
   data: SignUpRequest,
-) -> Result<(ID, String), String> {
+) -> Result<(ID, Tokens), String> {
   // data.validate()
   //   .map_err(|e| e.to_string())?;
 
-  let password = data.password.as_bytes();
-
-  let salt = SaltString::generate(&mut OsRng);
-  let password_hash = match Pbkdf2.hash_password(password, &salt) {
-    Ok(hash) => hash.to_string(),
-    Err(e) => {
-      return Err(e.to_string());
-    },
-  };
+  let password_hash = hash_password(&data.password)?;
 
   let account_id = ID::from(data.email.as_str());
 
@@ -231,8 +270,17 @@ pub(crate) fn signup_procedure(
   ];
   app.db.modify(mutation).map_err(|e| e.to_string())?;
 
-  let login_data =
-    LoginRequest { email: data.email.clone(), password: data.password.clone(), remember_me: false };
+  // account is usable before verification, failed mail should not break signup
+  if let Err(e) = verification_procedure(app, &data.email) {
+    log::warn!("verification mail to {} failed: {e}", data.email);
+  }
+
+  let login_data = LoginRequest {
+    email: data.email.clone(),
+    password: data.password.clone(),
+    remember_me: false,
+    device: String::new(),
+  };
 
   login_procedure(app, login_data)
 }
@@ -243,7 +291,9 @@ pub(crate) async fn login_post(
   data: web::Json<LoginRequest>,
 ) -> Result<HttpResponse, Error> {
   match login_procedure(app.get_ref(), data.0) {
-    Ok((_, token)) => Ok(HttpResponse::Ok().json(LoginResponse { token })),
+    Ok((_, tokens)) => Ok(
+      HttpResponse::Ok().json(LoginResponse { token: tokens.access, refresh_token: tokens.refresh }),
+    ),
     Err(mgs) => Err(actix_web::error::ErrorUnauthorized(mgs)),
   }
 }
@@ -251,7 +301,7 @@ pub(crate) async fn login_post(
 pub(crate) fn login_procedure(
   app: &Application,
   data: LoginRequest,
-) -> Result<(ID, String), String> {
+) -> Result<(ID, Tokens), String> {
   if data.email.is_empty() {
     return Err("email can't be empty".into());
   }
@@ -276,35 +326,71 @@ pub(crate) fn login_procedure(
   let password_hash = PasswordHash::new(hash.as_str()).map_err(|e| e.to_string())?;
 
   if Pbkdf2.verify_password(data.password.as_bytes(), &password_hash).is_ok() {
-    // JWT
-    let now = now_in_millis();
-
-    let exp = if data.remember_me {
-      now + Duration::days(365).num_milliseconds() as u128
+    let ttl = if data.remember_me {
+      Duration::days(REMEMBER_ME_SESSION_DAYS)
     } else {
-      now + Duration::hours(1).num_milliseconds() as u128
+      Duration::days(SESSION_DAYS)
     };
 
-    let claims = Claims {
-      aud: app.settings.jwt_config.audience.clone(),
-      iss: app.settings.jwt_config.issuer.clone(),
-      sub: data.email.clone(),
-      iat: now,
-      nbf: now,
-      exp,
-    };
+    let (session, refresh) =
+      app.sessions.create(&account_id, &data.device, ttl).map_err(|e| e.to_string())?;
+    let session = session["_id"].to_string();
 
-    let key = EncodingKey::from_secret(app.settings.jwt_config.secret.as_bytes());
-    let token =
-      jsonwebtoken::encode(&Header::new(ALGORITHM), &claims, &key).map_err(|e| e.to_string())?;
+    let access = access_token(app, &data.email, &session)?;
 
-    Ok((account_id, token))
+    Ok((account_id, Tokens { access, refresh, session }))
   } else {
     Err("invalid password".to_string())
   }
 }
 
-pub(crate) fn logout_procedure(app: &Application, account: Account) -> Result<(), String> {
+/// new access token for session of refresh token, refresh token is rotated
+pub(crate) fn refresh_procedure(app: &Application, token: &str) -> Result<(ID, Tokens), String> {
+  let (account_id, session, refresh) = app.sessions.refresh(token).map_err(|e| e.to_string())?;
+  let session = session["_id"].to_string();
+
+  let email = match app
+    .db
+    .value(TransformationKey::simple(account_id, "email"))
+    .map_err(|e| e.to_string())?
+  {
+    Value::String(email) => email,
+    _ => return Err("not found".to_string()),
+  };
+
+  let access = access_token(app, &email, &session)?;
+
+  Ok((account_id, Tokens { access, refresh, session }))
+}
+
+fn access_token(app: &Application, email: &str, session: &str) -> Result<String, String> {
+  let now = now_in_millis();
+
+  let claims = Claims {
+    aud: app.settings.jwt_config.audience.clone(),
+    iss: app.settings.jwt_config.issuer.clone(),
+    sub: email.to_string(),
+    sid: session.to_string(),
+    iat: now,
+    nbf: now,
+    exp: now + Duration::minutes(ACCESS_TOKEN_MINUTES).num_milliseconds() as u128,
+  };
+
+  let key = EncodingKey::from_secret(app.settings.jwt_config.secret.as_bytes());
+  jsonwebtoken::encode(&Header::new(ALGORITHM), &claims, &key).map_err(|e| e.to_string())
+}
+
+/// revoke session, without session all sessions of account are closed
+pub(crate) fn logout_procedure(
+  app: &Application,
+  account: Account,
+  session: Option<String>,
+) -> Result<(), String> {
+  if let Some(session) = session {
+    app.sessions.revoke(&account.id, &session).map_err(|e| e.to_string())?;
+    return Ok(());
+  }
+
   let now = now_in_millis();
 
   log::debug!("logout {}", now);
@@ -313,9 +399,218 @@ pub(crate) fn logout_procedure(app: &Application, account: Account) -> Result<()
     vec![ChangeTransformation::create(*DESC, account.id, "last_logout", Value::U128(now))];
   app.db.modify(mutation)?;
 
+  app.sessions.revoke_all(&account.id).map_err(|e| e.to_string())?;
+
   Ok(())
 }
 
+fn hash_password(password: &str) -> Result<String, String> {
+  let salt = SaltString::generate(&mut OsRng);
+  match Pbkdf2.hash_password(password.as_bytes(), &salt) {
+    Ok(hash) => Ok(hash.to_string()),
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+/// send email verification token to account's email, unknown email is not reported to caller
+pub(crate) fn verification_procedure(app: &Application, email: &str) -> Result<(), String> {
+  let account_id = ID::from(email);
+  if !is_account(app, &account_id)? {
+    log::debug!("verification for unknown account {email}");
+    return Ok(());
+  }
+  if is_verified(app, &account_id)? {
+    return Ok(());
+  }
+
+  let token = one_time_token(app, &account_id, VERIFICATION, Duration::hours(VERIFICATION_HOURS))?;
+
+  send(app, email, "Confirm your email", "Please confirm your email address.", "verify", &token)
+}
+
+/// mark account's email as verified by token from verification mail
+pub(crate) fn verify_procedure(app: &Application, token: &str) -> Result<ID, String> {
+  let account_id = use_one_time_token(app, token, VERIFICATION)?;
+
+  let mutation = vec![ChangeTransformation::create(
+    *DESC,
+    account_id,
+    "email_verified",
+    Value::U128(now_in_millis()),
+  )];
+  app.db.modify(mutation).map_err(|e| e.to_string())?;
+
+  Ok(account_id)
+}
+
+pub(crate) fn is_verified(app: &Application, account_id: &ID) -> Result<bool, String> {
+  match app
+    .db
+    .value(TransformationKey::simple(*account_id, "email_verified"))
+    .map_err(|e| e.to_string())?
+  {
+    Value::U128(_) => Ok(true),
+    _ => Ok(false),
+  }
+}
+
+/// send password reset token, unknown email is not reported to caller
+pub(crate) fn password_reset_request_procedure(
+  app: &Application,
+  email: &str,
+) -> Result<(), String> {
+  let account_id = ID::from(email);
+  if !is_account(app, &account_id)? {
+    log::debug!("password reset for unknown account {email}");
+    return Ok(());
+  }
+
+  let token =
+    one_time_token(app, &account_id, PASSWORD_RESET, Duration::minutes(PASSWORD_RESET_MINUTES))?;
+
+  send(
+    app,
+    email,
+    "Password reset",
+    "Somebody requested password reset for your account, ignore this mail if it wasn't you.",
+    "reset",
+    &token,
+  )
+}
+
+/// set new password by token from reset mail, all sessions of account are closed
+pub(crate) fn password_reset_procedure(
+  app: &Application,
+  token: &str,
+  password: &str,
+) -> Result<ID, String> {
+  if password.is_empty() {
+    return Err("password can't be empty".into());
+  }
+
+  let account_id = use_one_time_token(app, token, PASSWORD_RESET)?;
+
+  let password_hash = hash_password(password)?;
+
+  let email = match app
+    .db
+    .value(TransformationKey::simple(account_id, "email"))
+    .map_err(|e| e.to_string())?
+  {
+    Value::String(email) => email,
+    _ => return Err("not found".to_string()),
+  };
+
+  // owner of mailbox got the token, so email is verified as well
+  let mutation = vec![
+    ChangeTransformation::create(*DESC, account_id, "password_hash", password_hash.into()),
+    ChangeTransformation::create(*DESC, account_id, "email_verified", Value::U128(now_in_millis())),
+  ];
+  app.db.modify(mutation).map_err(|e| e.to_string())?;
+
+  logout_procedure(app, Account { id: account_id, email }, None)?;
+
+  Ok(account_id)
+}
+
+fn is_account(app: &Application, account_id: &ID) -> Result<bool, String> {
+  match app
+    .db
+    .value(TransformationKey::simple(*account_id, "password_hash"))
+    .map_err(|e| e.to_string())?
+  {
+    Value::String(_) => Ok(true),
+    _ => Ok(false),
+  }
+}
+
+// token: <account id>.<secret>, only hash of secret is stored and new token replace previous one
+fn one_time_token(
+  app: &Application,
+  account_id: &ID,
+  purpose: &str,
+  ttl: Duration,
+) -> Result<String, String> {
+  let secret = ID::random().to_base64();
+  let expires = now_in_millis() + ttl.num_milliseconds() as u128;
+
+  let mutation = vec![
+    ChangeTransformation::create(
+      *DESC,
+      *account_id,
+      &format!("{purpose}_token"),
+      ID::from(secret.as_str()).to_base64().into(),
+    ),
+    ChangeTransformation::create(
+      *DESC,
+      *account_id,
+      &format!("{purpose}_expires"),
+      Value::U128(expires),
+    ),
+  ];
+  app.db.modify(mutation).map_err(|e| e.to_string())?;
+
+  Ok(format!("{}.{secret}", account_id.to_base64()))
+}
+
+fn use_one_time_token(app: &Application, token: &str, purpose: &str) -> Result<ID, String> {
+  let invalid = || "invalid or expired token".to_string();
+
+  let (account, secret) = token.split_once('.').ok_or_else(invalid)?;
+  let account_id = ID::from_base64(account.as_bytes()).map_err(|_| invalid())?;
+
+  let hash = match app
+    .db
+    .value(TransformationKey::simple(account_id, &format!("{purpose}_token")))
+    .map_err(|e| e.to_string())?
+  {
+    Value::String(hash) => hash,
+    _ => return Err(invalid()),
+  };
+  let expires = match app
+    .db
+    .value(TransformationKey::simple(account_id, &format!("{purpose}_expires")))
+    .map_err(|e| e.to_string())?
+  {
+    Value::U128(expires) => expires,
+    _ => return Err(invalid()),
+  };
+
+  if hash != ID::from(secret).to_base64() || expires <= now_in_millis() {
+    return Err(invalid());
+  }
+
+  // token can be used only once
+  let mutation = vec![
+    ChangeTransformation::create(*DESC, account_id, &format!("{purpose}_token"), Value::Nothing),
+    ChangeTransformation::create(*DESC, account_id, &format!("{purpose}_expires"), Value::Nothing),
+  ];
+  app.db.modify(mutation).map_err(|e| e.to_string())?;
+
+  Ok(account_id)
+}
+
+fn send(
+  app: &Application,
+  to: &str,
+  subject: &str,
+  text: &str,
+  action: &str,
+  token: &str,
+) -> Result<(), String> {
+  let mut body = format!("{text}\n\n");
+  let base_url = &app.settings.mail.base_url;
+  if !base_url.is_empty() {
+    body.push_str(&format!("{}/{action}?token={token}\n\n", base_url.trim_end_matches('/')));
+  }
+  body.push_str(&format!("token: {token}\n"));
+
+  app
+    .mailer
+    .send(Mail { to: to.to_string(), subject: subject.to_string(), body })
+    .map_err(|e| e.to_string())
+}
+
 #[post("/ping")]
 pub(crate) async fn ping_post(
   auth: BearerAuth,
@@ -329,21 +624,21 @@ pub(crate) async fn ping_post(
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::animo::memory::{ChangeTransformation, Transformation, TransformationKey, Value};
   use crate::api;
+  use crate::storage::Workspaces;
   use crate::warehouse::test_util::init;
   use actix_web::http::{header, StatusCode};
   use actix_web::web::Bytes;
   use actix_web::{test, web, App};
   use actix_web_httpauth::extractors::bearer::Config;
-  use actix_web_httpauth::middleware::HttpAuthentication;
   use std::sync::Arc;
 
   #[actix_web::test]
   async fn test_register_and_login() {
     let (tmp_dir, settings, db) = init();
 
-    let app = Application::new(Arc::new(settings), Arc::new(db));
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
 
     let server = test::init_service(
       App::new()
@@ -354,6 +649,7 @@ mod tests {
         .service(signup_post)
         .service(login_post)
         .service(logout)
+        .service(refresh_post)
         .service(ping_post)
         .default_service(web::route().to(api::not_implemented)),
     )
@@ -391,8 +687,9 @@ mod tests {
 
     let login_data = crate::auth::LoginRequest {
       email: "tester@nae.org".to_string(),
-      password: "Nae".to_string(),
+      password: "Nae_password".to_string(),
       remember_me: false,
+      device: "test".to_string(),
     };
 
     let req = test::TestRequest::post().uri("/login").set_json(login_data).to_request();
@@ -401,6 +698,19 @@ mod tests {
     let token2 = response.token;
     assert_eq!(token2.len() > 0, true);
 
+    // refresh token is rotated
+    let refresh = RefreshRequest { refresh_token: response.refresh_token.clone() };
+    let req = test::TestRequest::post().uri("/refresh").set_json(refresh).to_request();
+    let refreshed: LoginResponse = test::call_and_read_body_json(&server, req).await;
+    assert_ne!(refreshed.refresh_token, response.refresh_token);
+
+    let req = test::TestRequest::post()
+      .uri("/ping")
+      .insert_header((header::AUTHORIZATION, format!("Bearer {}", refreshed.token)))
+      .to_request();
+    let response = test::call_and_read_body(&server, req).await;
+    assert_eq!(response, Bytes::from_static(b"\"pong\""));
+
     // ping with new token
     let req = test::TestRequest::post()
       .uri("/ping")
@@ -421,4 +731,60 @@ mod tests {
     // TODO app.close();
     tmp_dir.close().unwrap();
   }
+
+  #[actix_web::test]
+  async fn test_verification_and_password_reset() {
+    let (tmp_dir, settings, db) = init();
+
+    let mails = crate::mail::FileMailer { folder: settings.mail.folder.clone() };
+    let wss = crate::storage::Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+
+    let token_of = |mail: &JsonValue| {
+      mail["body"].as_str().unwrap().lines().last().unwrap().replace("token: ", "")
+    };
+
+    let email = "tester@nae.org";
+    let signup = SignUpRequest { email: email.to_string(), password: "Nae".to_string() };
+    let (account, tokens) = signup_procedure(&app, signup).unwrap();
+    assert!(!is_verified(&app, &account).unwrap());
+
+    let sent = mails.sent(email).unwrap();
+    assert_eq!(sent.len(), 1);
+    let token = token_of(&sent[0]);
+
+    assert!(verify_procedure(&app, "wrong.token").is_err());
+    assert_eq!(verify_procedure(&app, &token).unwrap(), account);
+    assert!(is_verified(&app, &account).unwrap());
+    // used only once
+    assert!(verify_procedure(&app, &token).is_err());
+
+    // unknown account is not reported and no mail is sent
+    password_reset_request_procedure(&app, "unknown@nae.org").unwrap();
+    assert!(mails.sent("unknown@nae.org").unwrap().is_empty());
+
+    password_reset_request_procedure(&app, email).unwrap();
+    let sent = mails.sent(email).unwrap();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[1]["subject"], "Password reset");
+    let token = token_of(&sent[1]);
+
+    password_reset_procedure(&app, &token, "new password").unwrap();
+    assert!(password_reset_procedure(&app, &token, "again").is_err());
+
+    // sessions are closed after reset
+    assert!(decode_token(&app, &tokens.access).is_err());
+    assert!(refresh_procedure(&app, &tokens.refresh).is_err());
+
+    let login = |password: &str| LoginRequest {
+      email: email.to_string(),
+      password: password.to_string(),
+      remember_me: false,
+      device: String::new(),
+    };
+    assert!(login_procedure(&app, login("Nae")).is_err());
+    assert!(login_procedure(&app, login("new password")).is_ok());
+
+    tmp_dir.close().unwrap();
+  }
 }
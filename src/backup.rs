@@ -0,0 +1,409 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use json::JsonValue;
+
+use crate::commutator::Application;
+use crate::storage::organizations::Workspace;
+use crate::storage::Workspaces;
+use service::error::Error;
+use service::utils::json::JsonParams;
+use store::elements::dt;
+use store::GetWarehouse;
+use values::ID;
+
+// archive layout:
+//   manifest.json  - { schema, workspace, created }
+//   warehouse.json - warehouse operations of workspace storages
+//   workspace/     - workspace folder (memories tree with symlinks, people, cameras and etc)
+pub(crate) const SCHEMA_VERSION: u64 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const WAREHOUSE: &str = "warehouse.json";
+const WORKSPACE: &str = "workspace";
+
+pub(crate) fn backup(app: &Application, wid: &ID, archive: &Path) -> Result<(), Error> {
+  let ws = app.wss.get(wid);
+  if !ws.folder().exists() {
+    return Err(Error::NotFound(format!("workspace {}", wid.to_base64())));
+  }
+
+  let manifest = json::object! {
+    schema: SCHEMA_VERSION,
+    workspace: wid.to_base64(),
+    created: Utc::now().to_rfc3339(),
+  };
+
+  let warehouse = warehouse_ops(app, &ws)?;
+
+  let file = File::create(archive)
+    .map_err(|e| Error::IOError(format!("can't create {}: {}", archive.to_string_lossy(), e)))?;
+
+  let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+  // keep `latest.json` and `uuid/` index as symlinks
+  builder.follow_symlinks(false);
+
+  append(&mut builder, MANIFEST, manifest.dump())?;
+  append(&mut builder, WAREHOUSE, warehouse.dump())?;
+
+  builder.append_dir_all(WORKSPACE, ws.folder())?;
+
+  builder.into_inner()?.finish()?;
+
+  Ok(())
+}
+
+/// workspace id and archived warehouse operations
+pub(crate) fn contents(archive: &Path) -> Result<(ID, JsonValue), Error> {
+  let mut manifest = JsonValue::Null;
+  let mut warehouse = JsonValue::Null;
+
+  let mut tar = open(archive)?;
+  for entry in tar.entries()? {
+    let mut entry = entry?;
+    let name = entry.path()?.to_string_lossy().to_string();
+    if name == MANIFEST {
+      manifest = read(&mut entry)?;
+    } else if name == WAREHOUSE {
+      warehouse = read(&mut entry)?;
+    }
+  }
+
+  let schema = manifest["schema"].as_u64().unwrap_or(0);
+  if schema != SCHEMA_VERSION {
+    return Err(Error::GeneralError(format!(
+      "unsupported schema version {schema}, expected {SCHEMA_VERSION}"
+    )));
+  }
+
+  let wid = crate::services::string_to_id(manifest["workspace"].string())?;
+
+  Ok((wid, warehouse))
+}
+
+/// archived operations are replayed by reindex, so storages of archive must have none
+pub(crate) fn check_warehouse(app: &Application, archived: &JsonValue) -> Result<(), Error> {
+  let (from, till) = period()?;
+  let warehouse = app.warehouse().database;
+
+  for (storage, _) in archived.entries() {
+    let uuid = uuid::Uuid::parse_str(storage).map_err(|e| Error::GeneralError(e.to_string()))?;
+    let ops = warehouse
+      .get_ops_for_storage(uuid, from, till)
+      .map_err(|e| Error::GeneralError(e.message()))?;
+    if !ops.is_empty() {
+      return Err(Error::GeneralError(format!(
+        "warehouse already has operations of storage {storage}, restore into fresh database"
+      )));
+    }
+  }
+
+  Ok(())
+}
+
+/// unpack archive into new workspace folder, return workspace id and archived warehouse operations
+pub(crate) fn restore(wss: &Workspaces, archive: &Path) -> Result<(ID, JsonValue), Error> {
+  let (wid, warehouse) = contents(archive)?;
+
+  let ws = wss.get(&wid);
+  if ws.folder().exists() && std::fs::read_dir(ws.folder())?.next().is_some() {
+    return Err(Error::GeneralError(format!(
+      "workspace folder {} is not empty",
+      ws.folder().to_string_lossy()
+    )));
+  }
+  let ws = wss.create(wid)?;
+
+  // workspace files
+  let mut tar = open(archive)?;
+  for entry in tar.entries()? {
+    let mut entry = entry?;
+    let path = entry.path()?.to_path_buf();
+    if let Ok(relative) = path.strip_prefix(WORKSPACE) {
+      if relative.as_os_str().is_empty() {
+        continue;
+      }
+      check_entry(&entry, relative)?;
+      check_target(ws.folder(), relative)?;
+
+      let mut target: PathBuf = ws.folder().clone();
+      target.push(relative);
+
+      if let Some(folder) = target.parent() {
+        std::fs::create_dir_all(folder)?;
+      }
+      entry.unpack(&target)?;
+    }
+  }
+
+  Ok((wid, warehouse))
+}
+
+// entry must stay inside of workspace folder: no `..` or root in path, no hard links and
+// symlinks (`latest.json`, `uuid/` index) only relative to folder of workspace
+fn check_entry<R: std::io::Read>(entry: &tar::Entry<R>, relative: &Path) -> Result<(), Error> {
+  let inside = |path: &Path| {
+    let mut depth = 0_usize;
+    for component in path.components() {
+      match component {
+        Component::Normal(_) => depth += 1,
+        Component::CurDir => {},
+        Component::ParentDir if depth > 0 => depth -= 1,
+        _ => return false,
+      }
+    }
+    true
+  };
+
+  let invalid = |reason: &str| {
+    Err(Error::GeneralError(format!("{reason} in archive: {}", relative.to_string_lossy())))
+  };
+
+  if !relative
+    .components()
+    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+  {
+    return invalid("path outside of workspace");
+  }
+
+  match entry.header().entry_type() {
+    tar::EntryType::Regular | tar::EntryType::Directory => Ok(()),
+    tar::EntryType::Symlink => {
+      let link = match entry.link_name()? {
+        Some(link) => link.to_path_buf(),
+        None => return invalid("symlink without target"),
+      };
+      let folder = relative.parent().unwrap_or_else(|| Path::new(""));
+      if link.is_absolute() || !inside(&folder.join(link)) {
+        invalid("symlink outside of workspace")
+      } else {
+        Ok(())
+      }
+    },
+    _ => invalid("unsupported entry type"),
+  }
+}
+
+// chained symlinks escape the folder even if each one is inside: folders of entry and entry
+// itself must not be symlinks unpacked from the same archive before
+fn check_target(folder: &Path, relative: &Path) -> Result<(), Error> {
+  let mut path = folder.to_path_buf();
+  for component in relative.components() {
+    path.push(component);
+    if path.symlink_metadata().map(|m| m.file_type().is_symlink()).unwrap_or(false) {
+      return Err(Error::GeneralError(format!(
+        "path through symlink in archive: {}",
+        relative.to_string_lossy()
+      )));
+    }
+  }
+  Ok(())
+}
+
+/// compare warehouse after reindex with archived operations
+pub(crate) fn verify(app: &Application, wid: &ID, archived: &JsonValue) -> Result<bool, Error> {
+  let ws = app.wss.get(wid);
+  let current = warehouse_ops(app, &ws)?;
+
+  // same operations in any order
+  let dumps = |ops: &JsonValue| {
+    let mut list: Vec<String> = ops.members().map(|op| op.dump()).collect();
+    list.sort();
+    list
+  };
+
+  let storages: BTreeSet<&str> = archived
+    .entries()
+    .chain(current.entries())
+    .map(|(storage, _)| storage)
+    .collect();
+
+  let mut consistent = true;
+  for storage in storages {
+    let (before, after) = (dumps(&archived[storage]), dumps(&current[storage]));
+    if before != after {
+      let missing = before.iter().filter(|op| !after.contains(op)).count();
+      let extra = after.iter().filter(|op| !before.contains(op)).count();
+      log::warn!("storage {storage}: {missing} operations of archive missing, {extra} extra");
+      consistent = false;
+    }
+  }
+
+  Ok(consistent)
+}
+
+fn period() -> Result<(chrono::DateTime<Utc>, chrono::DateTime<Utc>), Error> {
+  let from = dt("1970-01-01").map_err(|e| Error::GeneralError(e.message()))?;
+  let till = dt("9999-12-31").map_err(|e| Error::GeneralError(e.message()))?;
+  Ok((from, till))
+}
+
+fn warehouse_ops(app: &Application, ws: &Workspace) -> Result<JsonValue, Error> {
+  let (from, till) = period()?;
+
+  let warehouse = app.warehouse().database;
+
+  let mut result = JsonValue::new_object();
+  for doc in ws.clone().into_iter() {
+    if doc.mem.ctx.last().map(|s| s.as_str()) != Some("storage") {
+      continue;
+    }
+
+    let storage = match doc.json()?["_uuid"].uuid_or_none() {
+      Some(uuid) => uuid,
+      None => continue,
+    };
+
+    let ops = warehouse
+      .get_ops_for_storage(storage, from, till)
+      .map_err(|e| Error::GeneralError(e.message()))?;
+
+    let ops = serde_json::to_string(&ops).map_err(|e| Error::GeneralError(e.to_string()))?;
+    result[storage.to_string()] = json::parse(&ops).map_err(|e| Error::IOError(e.to_string()))?;
+  }
+
+  Ok(result)
+}
+
+fn append(
+  builder: &mut tar::Builder<GzEncoder<File>>,
+  name: &str,
+  data: String,
+) -> Result<(), Error> {
+  let mut header = tar::Header::new_gnu();
+  header.set_size(data.len() as u64);
+  header.set_mode(0o644);
+  header.set_mtime(Utc::now().timestamp() as u64);
+  header.set_cksum();
+
+  builder.append_data(&mut header, name, data.as_bytes())?;
+  Ok(())
+}
+
+fn open(archive: &Path) -> Result<tar::Archive<GzDecoder<File>>, Error> {
+  let file = File::open(archive)
+    .map_err(|e| Error::IOError(format!("can't open {}: {}", archive.to_string_lossy(), e)))?;
+  Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+fn read<R: std::io::Read>(entry: &mut R) -> crate::services::Result {
+  let mut data = String::new();
+  entry.read_to_string(&mut data)?;
+  json::parse(&data).map_err(|e| Error::IOError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::memories::MemoriesInFiles;
+  use crate::warehouse::test_util::init;
+  use service::{Context, Services};
+  use std::sync::Arc;
+  use tempfile::tempdir;
+
+  #[actix_web::test]
+  async fn test_backup_restore() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(MemoriesInFiles::new(app.clone(), "memories"));
+
+    let wid = ID::random();
+    let goods = app
+      .service("memories")
+      .create(
+        Context::local(),
+        json::object! { name: "g1" },
+        json::object! { oid: wid.to_base64(), ctx: vec!["warehouse", "goods"] },
+      )
+      .unwrap();
+
+    let archive = tmp_dir.path().join("backup.tar.gz");
+    backup(&app, &wid, &archive).unwrap();
+
+    let fresh = tempdir().unwrap();
+    let restored = Workspaces::new(fresh.path().join("companies"));
+
+    let (id, warehouse) = restore(&restored, &archive).unwrap();
+    assert_eq!(id, wid);
+    assert!(warehouse.is_object());
+
+    let ws = restored.get(&wid);
+    let doc = ws.resolve_uuid(&goods["_uuid"].uuid().unwrap()).unwrap();
+    assert_eq!(doc.json().unwrap()["name"], "g1");
+
+    // second restore into same folder must fail
+    assert!(restore(&restored, &archive).is_err());
+  }
+
+  // archive with manifest and given workspace entries
+  fn crafted(archive: &Path, entries: &[(&str, tar::EntryType, Option<&str>)]) {
+    let file = File::create(archive).unwrap();
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let manifest = json::object! { schema: SCHEMA_VERSION, workspace: ID::random().to_base64() };
+    append(&mut builder, MANIFEST, manifest.dump()).unwrap();
+
+    for (name, kind, link) in entries {
+      let mut header = tar::Header::new_gnu();
+      // raw name, `set_path` refuses `..`
+      let path = format!("{WORKSPACE}/{name}");
+      header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+      header.set_entry_type(*kind);
+      header.set_size(0);
+      header.set_mode(0o644);
+      if let Some(link) = link {
+        header.set_link_name(link).unwrap();
+      }
+      header.set_cksum();
+      builder.append(&header, std::io::empty()).unwrap();
+    }
+
+    builder.into_inner().unwrap().finish().unwrap();
+  }
+
+  #[test]
+  fn test_restore_rejects_escaping_entries() {
+    let tmp_dir = tempdir().unwrap();
+    let archive = tmp_dir.path().join("crafted.tar.gz");
+
+    let cases = [
+      ("../../evil.txt", tar::EntryType::Regular, None),
+      ("memories/latest.json", tar::EntryType::Symlink, Some("../../../../etc/passwd")),
+      ("memories/latest.json", tar::EntryType::Symlink, Some("/etc/passwd")),
+      ("memories/hard", tar::EntryType::Link, Some("memories/latest.json")),
+    ];
+
+    for (i, (name, kind, link)) in cases.into_iter().enumerate() {
+      crafted(&archive, &[(name, kind, link)]);
+
+      let wss = Workspaces::new(tmp_dir.path().join(format!("companies{i}")));
+      assert!(restore(&wss, &archive).is_err(), "{name} must be rejected");
+    }
+    assert!(!tmp_dir.path().join("evil.txt").exists());
+
+    // each link is inside, but together they point to parent of workspace folder
+    crafted(
+      &archive,
+      &[
+        ("a", tar::EntryType::Symlink, Some(".")),
+        ("a/b", tar::EntryType::Symlink, Some("..")),
+        ("a/b/evil.txt", tar::EntryType::Regular, None),
+      ],
+    );
+    let wss = Workspaces::new(tmp_dir.path().join("chained"));
+    assert!(restore(&wss, &archive).is_err());
+    assert!(!tmp_dir.path().join("chained").join("evil.txt").exists());
+
+    // relative symlink inside of workspace is fine
+    crafted(&archive, &[("memories/latest.json", tar::EntryType::Symlink, Some("2022.json"))]);
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    assert!(restore(&wss, &archive).is_ok());
+  }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread;
@@ -9,9 +9,14 @@ use json::{array, JsonValue};
 use tokio_cron_scheduler::JobScheduler;
 use uuid::Uuid;
 
+use crate::limits::Limits;
+use crate::mail::Mailer;
 use crate::services::{Event, Mutation};
+use crate::storage::audit::SAudit;
+use crate::storage::organizations::Workspace;
+use crate::storage::sessions::SSessions;
 use crate::text_search::SearchEngine;
-use crate::ws::{engine_io, socket_io, Connect, Disconnect, WsMessage};
+use crate::ws::{Connect, Disconnect, WsMessage};
 use crate::{animo::db::AnimoDB, settings::Settings};
 use crate::{storage::Workspaces, ws};
 use service::error::Error;
@@ -21,6 +26,15 @@ use store::GetWarehouse;
 
 type Socket = Recipient<WsMessage>;
 
+// services that have own log or no workspace
+const NOT_AUDITED: [&str; 4] = ["authentication", "users", "sessions", "audit"];
+
+struct Audit {
+  ws: Workspace,
+  record: JsonValue,
+  before: JsonValue,
+}
+
 #[derive(Clone)]
 pub struct Application {
   pub(crate) settings: Arc<Settings>,
@@ -30,6 +44,9 @@ pub struct Application {
 
   pub wss: Workspaces,
   pub(crate) warehouse: WHStorage,
+  pub(crate) sessions: SSessions,
+  pub(crate) mailer: Arc<dyn Mailer>,
+  pub(crate) limits: Arc<Limits>,
 
   // background dispatcher
   stop: Arc<AtomicBool>,
@@ -58,7 +75,8 @@ impl Application {
       .await
       .map_err(|e| Error::GeneralError(e.to_string()))?;
 
-    let (events_sender, events_receiver) = crossbeam::channel::bounded(1);
+    // mutation (bulk one too) must not wait for delivery of its events to sockets
+    let (events_sender, events_receiver) = crossbeam::channel::unbounded();
     let (sender, receiver) = crossbeam::channel::bounded(1);
     let stop = Arc::new(AtomicBool::new(false));
 
@@ -70,11 +88,14 @@ impl Application {
       wss,
       warehouse: WHStorage::open(&settings.database.inventory)
         .map_err(|e| Error::GeneralError(e.message()))?,
+      sessions: SSessions::new(settings.database.sessions.clone()),
+      mailer: crate::mail::mailer(&settings.mail),
+      limits: Arc::new(Limits::new(&settings.limits)),
       // channels: Arc::new(HashMap::new()),
       stop: stop.clone(),
       events: events_sender,
       sender,
-      search: Arc::new(RwLock::new(SearchEngine::new())),
+      search: Arc::new(RwLock::new(SearchEngine::new(&settings.search))),
     };
 
     thread::spawn({
@@ -106,31 +127,147 @@ impl Application {
   }
 
   pub(crate) fn handle(&self, mutation: Mutation) -> crate::services::Result {
-    match mutation {
+    let audit = self.audit_before(&mutation);
+
+    let result = match mutation {
       Mutation::Create(ctx, name, data, params) => {
-        self.service(&name).create(ctx, data, params).map(|data| {
-          self.emit(Event::Created(name, data.clone()));
+        let bulk = data.is_array();
+        self.service(&name).create(ctx.clone(), data, params.clone()).map(|data| {
+          if bulk {
+            self.emit_bulk(&ctx, &name, &data, &params);
+          } else {
+            self.emit(Event::Created(name, data.clone(), params));
+          }
           data
         })
       },
       Mutation::Update(ctx, name, id, data, params) => {
-        self.service(&name).update(ctx, id, data, params).map(|data| {
-          self.emit(Event::Updated(name, data.clone()));
+        self.service(&name).update(ctx, id, data, params.clone()).map(|data| {
+          self.emit(Event::Updated(name, data.clone(), params));
           data
         })
       },
       Mutation::Patch(ctx, name, id, data, params) => {
-        self.service(&name).patch(ctx, id, data, params).map(|data| {
-          self.emit(Event::Patched(name, data.clone()));
+        self.service(&name).patch(ctx, id, data, params.clone()).map(|data| {
+          self.emit(Event::Patched(name, data.clone(), params));
           data
         })
       },
       Mutation::Remove(ctx, name, id, params) => {
-        self.service(&name).remove(ctx, id, params).map(|data| {
-          self.emit(Event::Removed(name, data.clone()));
+        self.service(&name).remove(ctx, id, params.clone()).map(|data| {
+          self.emit(Event::Removed(name, data.clone(), params));
           data
         })
       },
+    };
+
+    if let (Some(audit), Ok(data)) = (audit, &result) {
+      self.audit_after(audit, data);
+    }
+
+    result
+  }
+
+  // acting account, workspace and state of document before mutation
+  fn audit_before(&self, mutation: &Mutation) -> Option<Audit> {
+    let (command, ctx, name, id, data, params) = match mutation {
+      Mutation::Create(ctx, name, data, params) => ("create", ctx, name, None, Some(data), params),
+      Mutation::Update(ctx, name, id, data, params) => {
+        ("update", ctx, name, Some(id.as_str()), Some(data), params)
+      },
+      Mutation::Patch(ctx, name, id, data, params) => {
+        ("patch", ctx, name, Some(id.as_str()), Some(data), params)
+      },
+      Mutation::Remove(ctx, name, id, params) => {
+        ("remove", ctx, name, Some(id.as_str()), None, params)
+      },
+    };
+
+    if NOT_AUDITED.contains(&name.as_str()) {
+      return None;
+    }
+
+    let wid = *crate::access::workspaces(name, id, data, params).first()?;
+    let wid = crate::services::string_to_id(wid.to_string()).ok()?;
+
+    let before = match id {
+      Some(id) => self
+        .service(name)
+        .get(ctx.clone(), id.to_string(), params.clone())
+        .unwrap_or(JsonValue::Null),
+      None => JsonValue::Null,
+    };
+
+    let account = ctx.account.read().unwrap().clone();
+    let record = json::object! {
+      account: {
+        _id: account.id.to_base64(),
+        email: account.email,
+      },
+      service: name.as_str(),
+      command: command,
+      ctx: crate::access::ctx_of(params),
+      document: id,
+    };
+
+    Some(Audit { ws: self.wss.get(&wid), record, before })
+  }
+
+  fn audit_after(&self, audit: Audit, after: &JsonValue) {
+    // report of bulk create, record for every created or updated document
+    if audit.record["command"] == "create" && after["data"].is_array() {
+      let mut documents = HashSet::new();
+      for row in after["data"].members() {
+        let command = match row["status"].as_str() {
+          Some("created") => "create",
+          Some("updated") => "update",
+          _ => continue,
+        };
+        // document of several rows is saved once
+        if !documents.insert(row["_id"].to_string()) {
+          continue;
+        }
+
+        let mut record = audit.record.clone();
+        record["command"] = command.into();
+        record["document"] = row["_id"].clone();
+        record["summary"] = row["summary"].clone();
+
+        if let Err(e) = audit.ws.audit().append(record) {
+          log::error!("fail to write audit of {}: {e}", audit.ws.id.to_base64());
+        }
+      }
+      return;
+    }
+
+    let Audit { ws, mut record, before } = audit;
+
+    if record["document"].is_null() {
+      record["document"] = after["_id"].clone();
+    }
+
+    if record["command"] != "remove" {
+      let before = if before.is_object() { before } else { JsonValue::new_object() };
+      record["summary"] = SAudit::summary(&before, after);
+    }
+
+    if let Err(e) = ws.audit().append(record) {
+      log::error!("fail to write audit of {}: {e}", ws.id.to_base64());
+    }
+  }
+
+  // report of bulk create lists rows, every created or updated document is separate event
+  fn emit_bulk(&self, ctx: &service::Context, name: &str, report: &JsonValue, params: &JsonValue) {
+    for row in report["data"].members() {
+      let event: fn(String, JsonValue, JsonValue) -> Event = match row["status"].as_str() {
+        Some("created") => Event::Created,
+        Some("updated") => Event::Updated,
+        _ => continue,
+      };
+      match self.service(name).get(ctx.clone(), row["_id"].to_string(), params.clone()) {
+        Ok(data) => self.emit(event(name.to_string(), data, params.clone())),
+        Err(e) => log::warn!("event of {name} {}: {e}", row["_id"]),
+      }
     }
   }
 
@@ -139,10 +276,10 @@ impl Application {
 
     // workaround to close authentication, users and actions service
     let service_name = match &event {
-      Event::Created(name, _) => name,
-      Event::Updated(name, _) => name,
-      Event::Patched(name, _) => name,
-      Event::Removed(name, _) => name,
+      Event::Created(name, _, _) => name,
+      Event::Updated(name, _, _) => name,
+      Event::Patched(name, _, _) => name,
+      Event::Removed(name, _, _) => name,
     };
     if service_name == "authentication" || service_name == "users" {
       // TODO || service_name == "actions" {
@@ -176,15 +313,54 @@ impl Services for Application {
       Arc::new(service::NoService(name.to_string()))
     }
   }
+
+  fn registered(&self) -> Vec<Arc<dyn Service>> {
+    let services = self.services.read().unwrap();
+    let mut list: Vec<Arc<dyn Service>> = services.values().cloned().collect();
+    list.sort_by(|a, b| a.path().cmp(b.path()));
+    list
+  }
 }
 
 #[derive(Clone)]
 pub struct Commutator {
   app: Application,
   sessions: Arc<RwLock<HashMap<Uuid, Socket>>>,
+  // context of socket, events are delivered while it has access
+  contexts: Arc<RwLock<HashMap<Uuid, service::Context>>>,
+  // room name to subscribed sockets
+  rooms: Arc<RwLock<HashMap<String, HashSet<Uuid>>>>,
   stop: Arc<AtomicBool>,
 }
 
+// rooms: "<oid>" for workspace, "<oid>/ctx/<a/b>" for context and its sub-contexts,
+// "<oid>/doc/<id>" for document
+fn room(oid: &str, ctx: &[String], document: Option<&str>) -> String {
+  match document {
+    Some(id) => format!("{oid}/doc/{id}"),
+    None if ctx.is_empty() => oid.to_string(),
+    None => format!("{oid}/ctx/{}", ctx.join("/")),
+  }
+}
+
+// rooms that should receive event
+fn rooms_of(name: &str, data: &JsonValue, params: &JsonValue) -> Vec<String> {
+  let ctx = crate::access::ctx_of(params);
+  let id = data["_id"].as_str();
+
+  let mut rooms = Vec::new();
+  for oid in crate::access::workspaces(name, id, Some(data), params) {
+    rooms.push(room(oid, &[], None));
+    for i in 1..=ctx.len() {
+      rooms.push(room(oid, &ctx[..i], None));
+    }
+    if let Some(id) = id {
+      rooms.push(room(oid, &[], Some(id)));
+    }
+  }
+  rooms
+}
+
 impl Commutator {
   pub(crate) fn new(app: Application, events: Receiver<Event>) -> Commutator {
     let stop = Arc::new(AtomicBool::new(false));
@@ -192,7 +368,8 @@ impl Commutator {
     let com = Commutator {
       app,
       sessions: Arc::new(RwLock::new(HashMap::new())),
-      // rooms: HashMap::new(),
+      contexts: Arc::new(RwLock::new(HashMap::new())),
+      rooms: Arc::new(RwLock::new(HashMap::new())),
       stop: stop.clone(),
     };
 
@@ -203,15 +380,25 @@ impl Commutator {
         while !should_stop.load(Ordering::SeqCst) {
           match events.recv() {
             Ok(event) => {
-              println!("sending to all: {:?}", event);
-              let (name, data) = match event {
-                Event::Created(name, data) => (format!("{name} created"), data),
-                Event::Updated(name, data) => (format!("{name} updated"), data),
-                Event::Patched(name, data) => (format!("{name} patched"), data),
-                Event::Removed(name, data) => (format!("{name} removed"), data),
+              log::debug!("sending to rooms: {:?}", event);
+              let (name, event_name, data, params) = match event {
+                Event::Created(name, data, params) => {
+                  (format!("{name} created"), name, data, params)
+                },
+                Event::Updated(name, data, params) => {
+                  (format!("{name} updated"), name, data, params)
+                },
+                Event::Patched(name, data, params) => {
+                  (format!("{name} patched"), name, data, params)
+                },
+                Event::Removed(name, data, params) => {
+                  (format!("{name} removed"), name, data, params)
+                },
               };
+              let rooms = rooms_of(&event_name, &data, &params);
+              let ctx = crate::access::ctx_of(&params);
               let data = array![JsonValue::String(name.clone()), data];
-              c.event_to_all(data.dump());
+              c.event_to_rooms(data.dump(), &rooms, &event_name, &ctx);
             },
             Err(e) => {
               println!("exist dispatcher thread because of {}", e);
@@ -225,33 +412,134 @@ impl Commutator {
     com
   }
 
-  fn open(&self, sid: &Uuid) {
+  // every subscribed socket get event once even if it's in several rooms
+  fn event_to_rooms(&self, response: String, rooms: &[String], name: &str, ctx: &[String]) {
+    let sids: HashMap<Uuid, String> = {
+      let subscriptions = self.rooms.read().unwrap();
+      let mut sids = HashMap::new();
+      for room in rooms {
+        for sid in subscriptions.get(room).into_iter().flatten() {
+          sids.entry(*sid).or_insert_with(|| room.clone());
+        }
+      }
+      sids
+    };
+
+    let sids: Vec<Uuid> = sids
+      .into_iter()
+      .filter(|(sid, room)| self.allowed(sid, room, name, ctx))
+      .map(|(sid, _)| sid)
+      .collect();
+
     let sessions = self.sessions.read().unwrap();
-    if let Some(socket) = sessions.get(sid) {
-      socket.do_send(WsMessage::open(sid));
-
-      // version 4: "0{\"sid\":\"...\"}"
-      socket.do_send(WsMessage {
-        data: format!("{{\"sid\":\"{}\"}}", sid.to_string()),
-        engine_code: engine_io::MESSAGE.to_string(),
-        socket_code: Some(socket_io::CONNECT.to_string()),
-      });
-      // version 3: "0"
-      // socket.do_send(WsMessage {
-      //   data: "".to_string(),
-      //   engine_code: engine_io::MESSAGE.to_string(),
-      //   socket_code: Some(socket_io::CONNECT.to_string()),
-      // });
-    } else {
-      println!("attempting to send message but couldn't find user id.");
+    for sid in sids {
+      if let Some(socket) = sessions.get(&sid) {
+        socket.do_send(WsMessage::event(response.clone()));
+      }
     }
   }
 
-  fn event_to_all(&self, response: String) {
-    let sessions = self.sessions.read().unwrap();
-    for socket in sessions.values() {
-      socket.do_send(WsMessage::event(response.clone()));
+  // same check as `find` of service: session is active, account is member of room's workspace
+  // and roles allow it; socket leave rooms it lost access to
+  fn allowed(&self, sid: &Uuid, room: &str, name: &str, ctx: &[String]) -> bool {
+    let context = match self.contexts.read().unwrap().get(sid) {
+      Some(context) => context.clone(),
+      None => return false,
+    };
+
+    let account = match crate::access::authenticate(&self.app, &context) {
+      Ok(account) => account,
+      Err(_) => {
+        self.leave_all(sid);
+        return false;
+      },
+    };
+
+    let oid = room.split('/').next().unwrap_or_default();
+    let ws = match crate::services::string_to_id(oid.to_string()) {
+      Ok(wid) => self.app.wss.get(&wid),
+      Err(_) => return false,
+    };
+
+    let roles = match ws.members().roles_of(&account.id) {
+      Ok(Some(roles)) => roles,
+      Ok(None) => {
+        self.leave_workspace(sid, oid);
+        return false;
+      },
+      Err(e) => {
+        log::warn!("roles of socket {sid}: {e}");
+        return false;
+      },
+    };
+
+    ws.roles().allowed(&roles, name, ctx, "find").unwrap_or(false)
+  }
+
+  fn join(&self, sid: &Uuid, context: &service::Context, room: String) {
+    self.contexts.write().unwrap().insert(*sid, context.clone());
+
+    let mut rooms = self.rooms.write().unwrap();
+    rooms.entry(room).or_insert_with(HashSet::new).insert(*sid);
+  }
+
+  fn leave(&self, sid: &Uuid, room: &str) {
+    let mut rooms = self.rooms.write().unwrap();
+    if let Some(sids) = rooms.get_mut(room) {
+      sids.remove(sid);
+      if sids.is_empty() {
+        rooms.remove(room);
+      }
+    }
+  }
+
+  fn leave_all(&self, sid: &Uuid) {
+    let mut rooms = self.rooms.write().unwrap();
+    rooms.retain(|_, sids| {
+      sids.remove(sid);
+      !sids.is_empty()
+    });
+  }
+
+  fn leave_workspace(&self, sid: &Uuid, oid: &str) {
+    let mut rooms = self.rooms.write().unwrap();
+    rooms.retain(|room, sids| {
+      if room.split('/').next() == Some(oid) {
+        sids.remove(sid);
+      }
+      !sids.is_empty()
+    });
+  }
+
+  fn rooms_of_socket(&self, sid: &Uuid) -> Vec<String> {
+    let rooms = self.rooms.read().unwrap();
+    let mut list: Vec<String> = rooms
+      .iter()
+      .filter(|(_, sids)| sids.contains(sid))
+      .map(|(room, _)| room.clone())
+      .collect();
+    list.sort();
+    list
+  }
+
+  // ["subscribe" | "unsubscribe", "rooms", { oid, ctx: ["warehouse", "receive"], document }]
+  fn subscription(&self, msg: &ws::Event) -> crate::services::Result {
+    let params = &msg.data[0];
+
+    let oid = crate::services::oid(params)?;
+    let ctx = crate::access::ctx_of(params);
+    let document = params["document"].as_str();
+
+    let room = room(&oid.to_base64(), &ctx, document);
+    if msg.command == "subscribe" {
+      // events of room are results of `find` at the context
+      crate::access::authorize(&self.app, &msg.ctx, "memories", "find", None, None, params)?;
+      self.join(&msg.sid, &msg.ctx, room);
+    } else {
+      self.leave(&msg.sid, &room);
     }
+
+    Ok(json::object! { rooms: self.rooms_of_socket(&msg.sid) })
   }
 
   fn event(&self, response: String, id_to: &Uuid) {
@@ -263,10 +551,14 @@ impl Commutator {
     }
   }
 
-  fn ack(&self, event_id: String, response: String, id_to: &Uuid) {
+  fn ack(&self, nsp: &str, event_id: String, response: JsonValue, id_to: &Uuid) {
+    // acknowledgement wasn't requested
+    if event_id.is_empty() {
+      return;
+    }
     let sessions = self.sessions.read().unwrap();
     if let Some(socket) = sessions.get(id_to) {
-      socket.do_send(WsMessage::ack(event_id, response));
+      socket.do_send(WsMessage::ack(nsp, event_id, response));
     } else {
       println!("attempting to send message but couldn't find user id.");
     }
@@ -305,10 +597,44 @@ fn id_data_params(mut data: JsonValue) -> Result<(String, JsonValue, JsonValue),
   }
 }
 
+// id, data and params of command as they are passed to service
+fn request_of<'a>(
+  command: &str,
+  data: &'a JsonValue,
+) -> (Option<&'a str>, Option<&'a JsonValue>, &'a JsonValue) {
+  match command {
+    "get" | "remove" => (data[0].as_str(), None, &data[1]),
+    "create" => (None, Some(&data[0]), &data[1]),
+    "update" | "patch" => (data[0].as_str(), Some(&data[1]), &data[2]),
+    _ => (None, None, data),
+  }
+}
+
 impl Handler<ws::Event> for Commutator {
   type Result = ();
 
   fn handle(&mut self, msg: ws::Event, _ctx: &mut Self::Context) -> Self::Result {
+    if msg.path == "rooms" && (msg.command == "subscribe" || msg.command == "unsubscribe") {
+      let response = match self.subscription(&msg) {
+        Ok(data) => json::array![JsonValue::Null, data],
+        Err(err) => json::array![err.to_json()],
+      };
+      return self.ack(&msg.nsp, msg.event_id, response, &msg.sid);
+    }
+
+    let (id, data, params) = request_of(&msg.command, &msg.data);
+    if let Err(err) =
+      crate::access::authorize(&self.app, &msg.ctx, &msg.path, &msg.command, id, data, params)
+    {
+      let response = json::array![err.to_json()];
+      return self.ack(&msg.nsp, msg.event_id, response, &msg.sid);
+    }
+
+    // socket receive events of workspaces it works with
+    for oid in crate::access::workspaces(&msg.path, id, data, params) {
+      self.join(&msg.sid, &msg.ctx, room(oid, &[], None));
+    }
+
     let service = self.app.service(msg.path.as_str());
     let response = match msg.command.as_str() {
       "find" => service.find(msg.ctx, msg.data),
@@ -334,7 +660,7 @@ impl Handler<ws::Event> for Commutator {
       Ok(data) => json::array![JsonValue::Null, data],
       Err(err) => json::array![err.to_json()],
     };
-    self.ack(msg.event_id, response.dump(), &msg.sid)
+    self.ack(&msg.nsp, msg.event_id, response, &msg.sid)
   }
 }
 
@@ -342,12 +668,9 @@ impl Handler<Connect> for Commutator {
   type Result = ();
 
   fn handle(&mut self, msg: Connect, _ctx: &mut Self::Context) -> Self::Result {
-    {
-      let mut sessions = self.sessions.write().unwrap();
-      sessions.insert(msg.sid, msg.socket);
-    }
-
-    self.open(&msg.sid);
+    let mut sessions = self.sessions.write().unwrap();
+    // upgraded transport replace previous one
+    sessions.insert(msg.sid, msg.socket);
   }
 }
 
@@ -357,7 +680,131 @@ impl Handler<Disconnect> for Commutator {
   fn handle(&mut self, msg: Disconnect, _ctx: &mut Self::Context) -> Self::Result {
     let mut sessions = self.sessions.write().unwrap();
     if sessions.remove(&msg.sid).is_some() {
-      // TODO remove from channels
+      self.contexts.write().unwrap().remove(&msg.sid);
+      self.leave_all(&msg.sid);
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::audit::AuditFilter;
+
+  #[test]
+  fn test_rooms_of() {
+    let data = json::object! { _id: "doc1", name: "a" };
+    let params = json::object! { oid: "ws1", ctx: ["warehouse", "receive"] };
+
+    let rooms = rooms_of("memories", &data, &params);
+    assert_eq!(rooms, vec!["ws1", "ws1/ctx/warehouse", "ws1/ctx/warehouse/receive", "ws1/doc/doc1"]);
+
+    // workspace of `companies` is document itself
+    let rooms = rooms_of("companies", &json::object! { _id: "ws2" }, &JsonValue::Null);
+    assert_eq!(rooms, vec!["ws2", "ws2/doc/ws2"]);
+
+    // without workspace nobody get the event
+    assert!(rooms_of("sessions", &JsonValue::Null, &JsonValue::Null).is_empty());
+  }
+
+  #[actix_web::test]
+  async fn test_delivery_access() {
+    let (tmp_dir, settings, db) = crate::warehouse::test_util::init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    let com = Commutator::new(app.clone(), events);
+
+    let wid = values::ID::random();
+    let oid = wid.to_base64();
+    let ws = app.wss.create(wid).unwrap();
+
+    let email = "clerk@nae.org";
+    let member = service::Account { id: values::ID::from(email), email: email.into() };
+    let rules = json::array![
+      { service: "*", ctx: "*", commands: ["find"], effect: "allow" },
+      { service: "memories", ctx: "salary", commands: ["*"], effect: "deny" },
+    ];
+    ws.roles().save("clerk", &rules).unwrap();
+    ws.members().add(&member, &["clerk".to_string()]).unwrap();
+
+    let (session, _) = app.sessions.create(&member.id, "test", chrono::Duration::days(1)).unwrap();
+    let session = session["_id"].to_string();
+
+    let ctx = service::Context::local();
+    *ctx.account.write().unwrap() = member.clone();
+    *ctx.session.write().unwrap() = Some(session.clone());
+
+    let sid = Uuid::new_v4();
+    let goods = vec!["goods".to_string()];
+    let salary = vec!["salary".to_string()];
+
+    // denied context isn't delivered even by workspace room
+    com.join(&sid, &ctx, room(&oid, &[], None));
+    assert!(com.allowed(&sid, &oid, "memories", &goods));
+    assert!(!com.allowed(&sid, &oid, "memories", &salary));
+
+    // removed member leave rooms of workspace
+    ws.members().remove(&member.id).unwrap();
+    assert!(!com.allowed(&sid, &oid, "memories", &goods));
+    assert!(com.rooms_of_socket(&sid).is_empty());
+
+    // revoked session leave every room
+    ws.members().add(&member, &["clerk".to_string()]).unwrap();
+    com.join(&sid, &ctx, room(&oid, &goods, None));
+    app.sessions.revoke(&member.id, &session).unwrap();
+    assert!(!com.allowed(&sid, &room(&oid, &goods, None), "memories", &goods));
+    assert!(com.rooms_of_socket(&sid).is_empty());
+
+    tmp_dir.close().unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_bulk_events() {
+    let (tmp_dir, settings, db) = crate::warehouse::test_util::init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(crate::memories::MemoriesInFiles::new(app.clone(), "memories"));
+
+    let wid = values::ID::random();
+    let params = json::object! { oid: wid.to_base64(), ctx: ["goods"], upsert: "code" };
+    let rows = json::array![
+      { code: "1", name: "g1" },
+      { code: "2", name: "g2" },
+      { code: "1", name: "g1" },
+      "not an object",
+    ];
+
+    // consumer of events as commutator is
+    let consumer = thread::spawn(move || {
+      let mut names = Vec::new();
+      while let Ok(event) = events.recv_timeout(std::time::Duration::from_secs(2)) {
+        match event {
+          Event::Created(_, data, _) => names.push(data["name"].to_string()),
+          e => panic!("unexpected event {e:?}"),
+        }
+      }
+      names
+    });
+
+    let report = app
+      .handle(Mutation::Create(service::Context::local(), "memories".into(), rows, params))
+      .unwrap();
+    assert_eq!(report["created"], 2);
+
+    assert_eq!(consumer.join().unwrap(), vec!["g1", "g2"]);
+
+    // audit record per document instead of one for report
+    let filter = AuditFilter { account: None, document: None, from: None, till: None };
+    let (records, total) = app.wss.get(&wid).audit().find(&filter, 0, 10).unwrap();
+    assert_eq!(total, 2);
+    for (record, name) in records.iter().zip(["g2", "g1"]) {
+      assert_eq!(record["command"], "create");
+      assert!(record["document"].is_string());
+      assert_eq!(record["summary"]["name"]["after"], name);
+    }
+
+    tmp_dir.close().unwrap();
+  }
+}
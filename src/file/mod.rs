@@ -6,6 +6,7 @@ use futures::TryStreamExt;
 use qstring::QString;
 
 use crate::commutator::Application;
+use service::Context;
 use values::ID;
 
 #[get("/picture")]
@@ -25,6 +26,11 @@ pub(crate) async fn get_file(
     Err(e) => return Ok(HttpResponse::from_error(e)),
   };
 
+  let ctx = Context::rest(req.head().clone());
+  let params = json::object! { oid: oid.to_base64() };
+  crate::access::authorize(&app, &ctx, "picture", "get", None, None, &params)
+    .map_err(crate::access::http_error)?;
+
   let path = app.wss.get(&oid).person(&pid).picture().path();
 
   let file = actix_files::NamedFile::open_async(path).await?;
@@ -50,6 +56,21 @@ pub(crate) async fn post_file(
     Err(e) => return Ok(HttpResponse::from_error(e)),
   };
 
+  let ctx = Context::rest(req.head().clone());
+  let params = json::object! { oid: oid.to_base64() };
+  crate::access::authorize(&app, &ctx, "picture", "create", None, None, &params)
+    .map_err(crate::access::http_error)?;
+
+  let max_size = app.limits.picture_size();
+  let length = req
+    .headers()
+    .get(actix_web::http::header::CONTENT_LENGTH)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<usize>().ok());
+  if length.map(|length| length > max_size).unwrap_or(false) {
+    return Ok(HttpResponse::PayloadTooLarge().finish());
+  }
+
   // let action = JsonValue::Null;
 
   // iterate over multipart stream
@@ -74,10 +95,23 @@ pub(crate) async fn post_file(
     }
 
     // File::create is blocking operation, use threadpool
-    let mut f = web::block(|| std::fs::File::create(path)).await??;
+    let mut f = web::block({
+      let path = path.clone();
+      || std::fs::File::create(path)
+    })
+    .await??;
 
     // Field in turn is stream of *Bytes* object
+    let mut size = 0;
     while let Some(chunk) = field.try_next().await? {
+      // content length may be missing or wrong
+      size += chunk.len();
+      if size > max_size {
+        drop(f);
+        web::block(|| std::fs::remove_file(path)).await??;
+        return Ok(HttpResponse::PayloadTooLarge().finish());
+      }
+
       // filesystem operations are blocking, we have to use threadpool
       f = web::block(move || f.write_all(&chunk).map(|_| f)).await??;
     }
@@ -16,6 +16,7 @@ use crate::commutator::Application;
 use crate::hik::auth::WithDigestAuth;
 use crate::hik::data::triggers_parser::TriggerItem;
 use crate::hik::error::{Error, Result};
+use crate::hik::secret::Secret;
 use crate::services::Mutation;
 use crate::storage::SCamera;
 use reqwest::header::CONTENT_TYPE;
@@ -263,8 +264,20 @@ impl ConfigCamera {
     }
   }
 
-  pub(crate) fn data(&self) -> Result<String> {
-    serde_json::to_string(self).map_err(|_e| Error::IOError(format!("fail to generate data json")))
+  /// json of config with encrypted password
+  pub(crate) fn data(&self, secret: &Secret) -> Result<String> {
+    let mut config = self.clone();
+    config.password = secret.encrypt(&self.password)?;
+    serde_json::to_string(&config)
+      .map_err(|_e| Error::IOError(format!("fail to generate data json")))
+  }
+
+  /// config from json with plain or encrypted password
+  pub(crate) fn from_data(data: &str, secret: &Secret) -> Result<ConfigCamera> {
+    let mut config: ConfigCamera =
+      serde_json::from_str(data).map_err(|e| Error::IOError(e.to_string()))?;
+    config.password = secret.decrypt(&config.password)?;
+    Ok(config)
   }
 }
 
@@ -429,7 +442,10 @@ async fn processing(
               event: event
             };
 
-            app.service("events").create(Context::local(), data, JsonValue::Null).unwrap();
+            // only access events are stored, skip heartbeats and pictures
+            if let Err(e) = app.service("events").create(Context::local(), data, JsonValue::Null) {
+              println!("event skipped: {e}");
+            }
 
             // tokio::time::sleep(Duration::from_secs(5)).await;
           }
@@ -717,6 +733,7 @@ impl Events for EventsOnStream {
             let part_str = String::from_utf8(next.body.to_vec())
               .map_err(|e| Error::StreamInvalid(format!("Stream returned non-UTF-8 text: {}", e)))?;
             json::parse(part_str.as_str())
+              .map(access_event)
               .map_err(|e| Error::IOError(format!("fail to parse: {:?} {:?}", e, part_str)))
           },
           "image/jpeg" => {
@@ -741,3 +758,17 @@ impl Events for EventsOnStream {
     }
   }
 }
+
+// access event of alert stream in shape of `AcsEvent` search result
+fn access_event(event: JsonValue) -> JsonValue {
+  let access = &event["AccessControllerEvent"];
+  if !access.is_object() {
+    return event;
+  }
+
+  let mut result = access.clone();
+  result["major"] = access["majorEventType"].clone();
+  result["minor"] = access["subEventType"].clone();
+  result["time"] = event["dateTime"].clone();
+  result
+}
@@ -3,9 +3,13 @@ mod auth;
 mod camera;
 mod data;
 pub(crate) mod error;
+mod secret;
 pub mod services;
+#[cfg(test)]
+mod simulator;
 
 pub use camera::connection;
 pub use camera::Camera;
 pub use camera::ConfigCamera;
 pub use camera::StatusCamera;
+pub use secret::Secret;
@@ -0,0 +1,86 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use blake2::{Blake2s256, Digest};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+
+use crate::hik::error::{Error, Result};
+
+// marker of encrypted value, values without it are plain text of old configs
+const PREFIX: &str = "enc:v1:";
+
+const NONCE_SIZE: usize = 24;
+
+/// Encryption of camera credentials at rest, key is derived from `hik.secret` of settings.
+///
+/// stored value: `enc:v1:` + base64(nonce + ciphertext)
+#[derive(Clone)]
+pub struct Secret {
+  cipher: XChaCha20Poly1305,
+}
+
+impl Secret {
+  pub fn new(secret: &str) -> Self {
+    let key = Blake2s256::digest(secret.as_bytes());
+    Secret { cipher: XChaCha20Poly1305::new(Key::from_slice(&key)) }
+  }
+
+  pub fn encrypt(&self, plain: &str) -> Result<String> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let encrypted = self
+      .cipher
+      .encrypt(&nonce, plain.as_bytes())
+      .map_err(|_| Error::IOError("fail to encrypt".into()))?;
+
+    let mut data = nonce.to_vec();
+    data.extend(encrypted);
+
+    Ok(format!("{PREFIX}{}", STANDARD.encode(data)))
+  }
+
+  /// plain text of value, one without marker is returned as is
+  pub fn decrypt(&self, stored: &str) -> Result<String> {
+    let data = match stored.strip_prefix(PREFIX) {
+      Some(data) => data,
+      None => return Ok(stored.to_string()),
+    };
+
+    let data = STANDARD.decode(data).map_err(|e| Error::IOError(e.to_string()))?;
+    if data.len() < NONCE_SIZE {
+      return Err(Error::IOError("encrypted value is too short".into()));
+    }
+    let (nonce, encrypted) = data.split_at(NONCE_SIZE);
+
+    let plain = self
+      .cipher
+      .decrypt(XNonce::from_slice(nonce), encrypted)
+      .map_err(|_| Error::IOError("fail to decrypt, wrong `hik.secret`?".into()))?;
+
+    String::from_utf8(plain).map_err(|e| Error::IOError(e.to_string()))
+  }
+
+  pub fn is_encrypted(stored: &str) -> bool {
+    stored.starts_with(PREFIX)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_secret() {
+    let secret = Secret::new("1234567890");
+
+    let stored = secret.encrypt("p@ssw0rd").unwrap();
+    assert!(Secret::is_encrypted(&stored));
+    assert!(!stored.contains("p@ssw0rd"));
+    assert_ne!(stored, secret.encrypt("p@ssw0rd").unwrap());
+    assert_eq!(secret.decrypt(&stored).unwrap(), "p@ssw0rd");
+
+    // plain text of old configs
+    assert_eq!(secret.decrypt("p@ssw0rd").unwrap(), "p@ssw0rd");
+
+    assert!(Secret::new("other").decrypt(&stored).is_err());
+  }
+}
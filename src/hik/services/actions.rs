@@ -54,6 +54,10 @@ impl Service for Actions {
     &self.path
   }
 
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "create", "patch"]
+  }
+
   fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
     let _limit = self.limit(&params);
     let skip = self.skip(&params);
@@ -121,7 +125,7 @@ impl Service for Actions {
 
         // let mut cameras = self.app.storage.as_ref().unwrap().get(&oid).camera_configs();
 
-        let camera = self.app.wss.get(&oid).camera(&cid).config()?;
+        let camera = self.app.wss.get(&oid).camera(&cid).config(&self.app.settings.hik.secret())?;
 
         let person = self.app.service("people").get(
           Context::local(),
@@ -157,7 +161,7 @@ impl Service for Actions {
         let pid = crate::services::pid(&params)?;
 
         // let mut cameras = self.app.storage.as_ref().unwrap().get(&oid).camera_configs();
-        let camera = self.app.wss.get(&oid).camera(&cid).config()?;
+        let camera = self.app.wss.get(&oid).camera(&cid).config(&self.app.settings.hik.secret())?;
 
         let person = self.app.service("people").get(
           Context::local(),
@@ -4,7 +4,7 @@ use std::sync::{Arc, Mutex, RwLock};
 use std::time::SystemTime;
 
 use crate::hik::camera::States;
-use crate::hik::{ConfigCamera, StatusCamera};
+use crate::hik::{ConfigCamera, Secret, StatusCamera};
 use crate::services::{string_to_id, Data, Params};
 use crate::storage::{SCamera, Workspaces};
 
@@ -17,6 +17,7 @@ pub struct Cameras {
   path: Arc<String>,
 
   ws: Workspaces,
+  secret: Secret,
 
   // organization id > camera id
   mapping: Arc<RwLock<BTreeMap<ID, Vec<ID>>>>, // TODO switch to ordered hash set
@@ -25,6 +26,8 @@ pub struct Cameras {
 
 impl Cameras {
   pub(crate) fn new(app: Application, path: &str, ws: Workspaces) -> Arc<dyn Service> {
+    let secret = app.settings.hik.secret();
+
     let mut mapping = BTreeMap::new();
     let mut objs = BTreeMap::new();
 
@@ -41,7 +44,7 @@ impl Cameras {
         println!("loading camera {cam:?}");
         let contents = cam.data().unwrap();
 
-        let mut config: crate::hik::ConfigCamera = match serde_json::from_str(contents.as_str()) {
+        let mut config = match ConfigCamera::from_data(&contents, &secret) {
           Ok(o) => o,
           Err(e) => {
             println!("Error on loading camera {cam:?} {e}");
@@ -49,6 +52,19 @@ impl Cameras {
           },
         };
 
+        // encrypt password of config saved before encryption
+        let stored = json::parse(&contents).unwrap_or(JsonValue::Null);
+        if !Secret::is_encrypted(stored["password"].as_str().unwrap_or_default()) {
+          match config.data(&secret) {
+            Ok(data) => {
+              if let Err(e) = cam.save(data) {
+                println!("Error on saving camera {cam:?} {e}");
+              }
+            },
+            Err(e) => println!("Error on encrypting camera {cam:?} {e}"),
+          }
+        }
+
         // reset state and status
         let was_on = config.state.is_on();
         config.status = StatusCamera::disconnect();
@@ -73,6 +89,7 @@ impl Cameras {
       app,
       path: Arc::new(path.to_string()),
       ws,
+      secret,
       mapping: Arc::new(RwLock::new(mapping)),
       objs: Arc::new(RwLock::new(objs)),
     })
@@ -83,7 +100,9 @@ impl Cameras {
     // cam.save(data)?;
 
     let cam = self.ws.get(&config.oid).camera(&config.id).create()?;
-    let data = config.data().map_err(|e| service::error::Error::IOError(e.to_string()))?;
+    let data = config
+      .data(&self.secret)
+      .map_err(|e| service::error::Error::IOError(e.to_string()))?;
     cam.save(data)?;
     Ok(JsonValue::Null)
   }
@@ -101,6 +120,10 @@ impl Service for Cameras {
     &self.path
   }
 
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "create", "update", "patch"]
+  }
+
   fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
     let oid = crate::services::oid(&params)?;
 
@@ -286,3 +309,68 @@ impl Service for Cameras {
     Err(service::error::Error::NotImplemented)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::warehouse::test_util::init;
+
+  #[actix_web::test]
+  async fn test_cameras_credentials() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    let secret = app.settings.hik.secret();
+
+    let oid = ID::random();
+
+    // config saved before encryption
+    let legacy = ConfigCamera {
+      id: ID::random(),
+      oid,
+      name: "entrance".into(),
+      dev_index: "".into(),
+      event_type: "".into(),
+      protocol: "http".into(),
+      ip: "127.0.0.1".into(),
+      port: None,
+      username: "admin".into(),
+      password: "legacy-secret".into(),
+      status: StatusCamera::default(),
+      state: crate::hik::camera::State::default(),
+      jh: None,
+    };
+    let camera = app.wss.get(&oid).camera(&legacy.id);
+    camera.save(serde_json::to_string(&legacy).unwrap()).unwrap();
+
+    app.register(Cameras::new(app.clone(), "cameras", app.wss.clone()));
+
+    assert!(!camera.data().unwrap().contains("legacy-secret"));
+    assert_eq!(camera.config(&secret).unwrap().password, "legacy-secret");
+
+    let created = app
+      .service("cameras")
+      .create(
+        Context::local(),
+        json::object! {
+          oid: oid.to_base64(),
+          name: "exit",
+          username: "admin",
+          password: "new-secret",
+        },
+        JsonValue::Null,
+      )
+      .unwrap();
+    assert!(created["password"].is_null());
+
+    let camera = app.wss.get(&oid).camera(&string_to_id(created["_id"].string()).unwrap());
+    assert!(!camera.data().unwrap().contains("new-secret"));
+    assert_eq!(camera.config(&secret).unwrap().password, "new-secret");
+
+    // wrong key
+    assert!(camera.config(&Secret::new("other")).is_err());
+
+    tmp_dir.close().unwrap();
+  }
+}
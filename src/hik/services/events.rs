@@ -29,6 +29,10 @@ impl Service for Events {
     &self.path
   }
 
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "create"]
+  }
+
   fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
     let oid = crate::services::oid(&params)?;
     // let cid = self.cid(&params)?;
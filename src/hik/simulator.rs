@@ -0,0 +1,528 @@
+use actix_multipart::Multipart;
+use actix_web::dev::ServerHandle;
+use actix_web::http::header;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use digest_auth::{AuthContext, AuthorizationHeader, HttpMethod};
+use futures::TryStreamExt;
+use json::JsonValue;
+use rand::Rng;
+use std::sync::{Arc, Mutex};
+
+// recorded camera responses
+pub(crate) const ALERT_STREAM: &str = include_str!("fixtures/alert_stream.json");
+pub(crate) const ACS_EVENTS: &str = include_str!("fixtures/acs_events.json");
+
+const REALM: &str = "DS-Simulator";
+const BOUNDARY: &str = "MIME_boundary";
+
+// picture sent after access event, enough to be stored as `.jpeg`
+const PICTURE: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0xFF, 0xD9];
+
+/// Requests of registration endpoints received by simulator
+#[derive(Debug, Default)]
+pub(crate) struct Recorded {
+  // `UserInfo` records
+  pub(crate) users: Vec<JsonValue>,
+  // `FPID` and picture size of face records
+  pub(crate) faces: Vec<(String, usize)>,
+}
+
+struct State {
+  username: String,
+  password: String,
+
+  // parts of `alertStream`
+  stream: Vec<JsonValue>,
+  // `MatchList` of `AcsEvent` search
+  events: Vec<JsonValue>,
+
+  recorded: Arc<Mutex<Recorded>>,
+}
+
+/// Mock ISAPI server of Hikvision access control terminal with digest authentication.
+/// `alertStream` replay events as multipart parts and close connection, `AcsEvent` search
+/// page through recorded events, user and face registrations are recorded.
+pub(crate) struct Simulator {
+  pub(crate) port: u16,
+  pub(crate) recorded: Arc<Mutex<Recorded>>,
+
+  handle: ServerHandle,
+}
+
+impl Simulator {
+  /// `stream` - events of alert stream, `events` - result of `AcsEvent` search
+  pub(crate) fn start(
+    username: &str,
+    password: &str,
+    stream: &str,
+    events: &str,
+  ) -> std::io::Result<Simulator> {
+    let parse = |data: &str| {
+      json::parse(data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    };
+
+    let recorded = Arc::new(Mutex::new(Recorded::default()));
+
+    let state = web::Data::new(State {
+      username: username.to_string(),
+      password: password.to_string(),
+      stream: parse(stream)?.members().cloned().collect(),
+      events: parse(events)?["AcsEventSearchResult"]["MatchList"].members().cloned().collect(),
+      recorded: recorded.clone(),
+    });
+
+    let server = HttpServer::new(move || {
+      App::new()
+        .app_data(state.clone())
+        .route("/ISAPI/System/deviceInfo", web::get().to(device_info))
+        .route("/ISAPI/Event/notification/alertStream", web::get().to(alert_stream))
+        .route("/ISAPI/AccessControl/AcsEvent", web::post().to(acs_event))
+        .route("/ISAPI/AccessControl/UserInfo/Record", web::post().to(user_record))
+        .route("/ISAPI/Intelligent/FDLib/FDSetUp", web::put().to(face_setup))
+        .route("/ISAPI/ContentMgmt/DeviceMgmt/deviceList", web::post().to(device_list))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))?;
+
+    let port = server.addrs()[0].port();
+
+    let server = server.run();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    Ok(Simulator { port, recorded, handle })
+  }
+
+  pub(crate) async fn stop(self) {
+    self.handle.stop(false).await;
+  }
+}
+
+fn challenge(nonce: &str) -> String {
+  format!("Digest realm=\"{REALM}\", qop=\"auth\", nonce=\"{nonce}\", algorithm=MD5")
+}
+
+fn unauthorized() -> HttpResponse {
+  let nonce: u128 = rand::thread_rng().gen();
+  HttpResponse::Unauthorized()
+    .insert_header((header::WWW_AUTHENTICATE, challenge(&format!("{nonce:032x}"))))
+    .finish()
+}
+
+// check digest response by calculating expected one for same nonce and cnonce
+fn authorized(req: &HttpRequest, state: &State) -> bool {
+  let answer = match req
+    .headers()
+    .get(header::AUTHORIZATION)
+    .and_then(|h| h.to_str().ok())
+    .map(AuthorizationHeader::parse)
+  {
+    Some(Ok(answer)) => answer,
+    _ => return false,
+  };
+
+  if answer.username != state.username || answer.uri != req.path() {
+    return false;
+  }
+
+  let mut prompt = match digest_auth::parse(&challenge(&answer.nonce)) {
+    Ok(prompt) => prompt,
+    Err(_) => return false,
+  };
+
+  let mut context = AuthContext::new_with_method(
+    state.username.as_str(),
+    state.password.as_str(),
+    answer.uri.as_str(),
+    Option::<&[u8]>::None,
+    HttpMethod::from(req.method().as_str()),
+  );
+  if let Some(cnonce) = &answer.cnonce {
+    context.set_custom_cnonce(cnonce.as_str());
+  }
+
+  match prompt.respond(&context) {
+    Ok(expected) => expected.response == answer.response,
+    Err(_) => false,
+  }
+}
+
+fn ok(data: JsonValue) -> HttpResponse {
+  HttpResponse::Ok().content_type("application/json").body(data.dump())
+}
+
+fn status_ok() -> HttpResponse {
+  ok(json::object! { statusCode: 1, statusString: "OK", subStatusCode: "ok" })
+}
+
+async fn device_info(req: HttpRequest, state: web::Data<State>) -> HttpResponse {
+  if !authorized(&req, &state) {
+    return unauthorized();
+  }
+
+  HttpResponse::Ok().content_type("application/xml").body(
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<DeviceInfo version="2.0" xmlns="http://www.hikvision.com/ver20/XMLSchema">
+<deviceName>Simulator</deviceName>
+<deviceID>9d2f3b0a-5c1e-4f8a-9b7d-000000000001</deviceID>
+<model>DS-K1T341AM</model>
+<serialNumber>DS-K1T341AM20220101V030230ENSIMULATOR</serialNumber>
+<macAddress>44:47:cc:8a:26:1d</macAddress>
+<firmwareVersion>V3.2.30</firmwareVersion>
+<firmwareReleasedDate>build 220420</firmwareReleasedDate>
+<deviceType>ACS</deviceType>
+</DeviceInfo>"#,
+  )
+}
+
+fn part(content_type: &str, body: &[u8]) -> web::Bytes {
+  let mut part = format!(
+    "--{BOUNDARY}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n",
+    body.len()
+  )
+  .into_bytes();
+  part.extend_from_slice(body);
+  part.extend_from_slice(b"\r\n");
+  web::Bytes::from(part)
+}
+
+async fn alert_stream(req: HttpRequest, state: web::Data<State>) -> HttpResponse {
+  if !authorized(&req, &state) {
+    return unauthorized();
+  }
+
+  let mut parts = Vec::with_capacity(state.stream.len() * 2);
+  for event in &state.stream {
+    parts.push(part("application/json; charset=\"UTF-8\"", event.dump().as_bytes()));
+    if event["AccessControllerEvent"]["picturesNumber"].as_usize().unwrap_or(0) > 0 {
+      parts.push(part("image/jpeg", PICTURE));
+    }
+  }
+
+  HttpResponse::Ok()
+    .content_type(format!("multipart/mixed; boundary={BOUNDARY}"))
+    .streaming(futures::stream::iter(parts.into_iter().map(Ok::<_, actix_web::Error>)))
+}
+
+async fn acs_event(req: HttpRequest, body: web::Bytes, state: web::Data<State>) -> HttpResponse {
+  if !authorized(&req, &state) {
+    return unauthorized();
+  }
+
+  let request = json::parse(&String::from_utf8_lossy(&body)).unwrap_or(JsonValue::Null);
+  let search = &request["AcsEventSearchDescription"];
+
+  let total = state.events.len();
+  let from = search["searchResultPosition"].as_usize().unwrap_or(0).min(total);
+  let till = (from + search["maxResults"].as_usize().unwrap_or(30)).min(total);
+
+  ok(json::object! {
+    AcsEventSearchResult: {
+      searchID: search["searchID"].clone(),
+      responseStatusStrg: if till < total { "MORE" } else { "OK" },
+      numOfMatches: till - from,
+      totalMatches: total,
+      MatchList: JsonValue::Array(state.events[from..till].to_vec()),
+    }
+  })
+}
+
+async fn user_record(req: HttpRequest, body: web::Bytes, state: web::Data<State>) -> HttpResponse {
+  if !authorized(&req, &state) {
+    return unauthorized();
+  }
+
+  let request = json::parse(&String::from_utf8_lossy(&body)).unwrap_or(JsonValue::Null);
+
+  let mut recorded = state.recorded.lock().unwrap();
+  recorded.users.extend(request["UserInfo"].members().cloned());
+
+  status_ok()
+}
+
+async fn face_setup(
+  req: HttpRequest,
+  mut payload: Multipart,
+  state: web::Data<State>,
+) -> HttpResponse {
+  if !authorized(&req, &state) {
+    return unauthorized();
+  }
+
+  let mut record = JsonValue::Null;
+  let mut picture = 0;
+  while let Ok(Some(mut field)) = payload.try_next().await {
+    let name = field.name().to_string();
+
+    let mut data = Vec::new();
+    while let Ok(Some(chunk)) = field.try_next().await {
+      data.extend_from_slice(&chunk);
+    }
+
+    match name.as_str() {
+      "FaceDataRecord" => {
+        record = json::parse(&String::from_utf8_lossy(&data)).unwrap_or(JsonValue::Null)
+      },
+      "img" => picture = data.len(),
+      _ => {},
+    }
+  }
+
+  let fpid = match record["FPID"].as_str() {
+    Some(fpid) if picture > 0 => fpid.to_string(),
+    _ => {
+      let error = json::object! {
+        statusCode: 6,
+        statusString: "Invalid Content",
+        subStatusCode: "badParameters",
+      };
+      return HttpResponse::BadRequest().content_type("application/json").body(error.dump());
+    },
+  };
+
+  let mut recorded = state.recorded.lock().unwrap();
+  recorded.faces.push((fpid, picture));
+
+  status_ok()
+}
+
+async fn device_list(req: HttpRequest, state: web::Data<State>) -> HttpResponse {
+  if !authorized(&req, &state) {
+    return unauthorized();
+  }
+
+  ok(json::object! {
+    SearchResult: {
+      numOfMatches: 1,
+      totalMatches: 1,
+      MatchList: [{
+        Device: {
+          devIndex: "SIM-1",
+          devName: "Simulator",
+          devType: "AccessControl",
+          protocolType: "ISAPI",
+          devStatus: "online",
+        }
+      }]
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::commutator::Application;
+  use crate::hik::services::actions::Actions;
+  use crate::hik::services::{Cameras, Events};
+  use crate::hr::services::attendance_report::AttendanceReport;
+  use crate::services::People;
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use chrono::{TimeZone, Utc};
+  use service::utils::json::JsonParams;
+  use service::{Context, Services};
+  use std::time::Duration;
+  use values::ID;
+
+  const USERNAME: &str = "admin";
+  const PASSWORD: &str = "Sim-12345";
+
+  fn register(app: &mut Application) {
+    app.register(People::new(app.clone()));
+    app.register(Events::new(app.clone(), "events", app.wss.clone()));
+    app.register(Actions::new(app.clone(), "actions", app.wss.clone()));
+    app.register(AttendanceReport::new(app.clone(), app.wss.clone()));
+    app.register(Cameras::new(app.clone(), "cameras", app.wss.clone()));
+  }
+
+  // camera connected to simulator, `dev_index` switch it to `AcsEvent` polling
+  fn camera(app: &Application, oid: &ID, port: u16, dev_index: &str) -> ID {
+    let cameras = app.service("cameras");
+    let camera = cameras
+      .create(
+        Context::local(),
+        json::object! {
+          oid: oid.to_base64(),
+          name: "entrance",
+          eventType: "in",
+          devIndex: dev_index,
+          protocol: "http",
+          ip: "127.0.0.1",
+          port: port.to_string(),
+          username: USERNAME,
+          password: PASSWORD,
+        },
+        JsonValue::Null,
+      )
+      .unwrap();
+
+    let id = camera["_id"].string();
+    cameras
+      .patch(Context::local(), id.clone(), json::object! { enabled: true }, JsonValue::Null)
+      .unwrap();
+
+    ID::from_base64(id.as_bytes()).unwrap()
+  }
+
+  fn disable(app: &Application, cid: &ID) {
+    app
+      .service("cameras")
+      .patch(Context::local(), cid.to_base64(), json::object! { enabled: false }, JsonValue::Null)
+      .unwrap();
+  }
+
+  async fn wait_for(what: &str, check: impl Fn() -> bool) {
+    for _ in 0..200 {
+      if check() {
+        return;
+      }
+      tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("timeout waiting for {what}");
+  }
+
+  #[actix_web::test]
+  async fn test_simulator_alert_stream() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    register(&mut app);
+
+    let simulator = Simulator::start(USERNAME, PASSWORD, ALERT_STREAM, ACS_EVENTS).unwrap();
+
+    let oid = ID::random();
+    for (name, no) in [("Alice", "1"), ("Bob", "2")] {
+      app
+        .service("people")
+        .create(
+          Context::local(),
+          json::object! { oid: oid.to_base64(), name: name, employeeNoString: no },
+          JsonValue::Null,
+        )
+        .unwrap();
+    }
+
+    let cid = camera(&app, &oid, simulator.port, "");
+
+    // access events only, door event and heartbeat are skipped
+    let date = Utc.with_ymd_and_hms(2022, 8, 12, 0, 0, 0).unwrap();
+    let storage = app.wss.get(&oid).camera(&cid);
+    wait_for("events", || storage.events_on_date(date).len() == 8).await;
+
+    disable(&app, &cid);
+
+    let events = storage.events_on_date(date);
+    let first = events[0].load().unwrap();
+    assert_eq!(first["event"]["employeeNoString"], "1");
+    assert_eq!(first["event"]["attendanceStatus"], "checkIn");
+    assert_eq!(events[0].id, "2022-08-12T04:01:15.000Z");
+
+    let report = app
+      .service("attendance-report")
+      .find(Context::local(), json::object! { oid: oid.to_base64(), date: "2022-08-12" })
+      .unwrap();
+    assert_eq!(report["total"], 2);
+
+    let intervals = |name: &str| -> Vec<JsonValue> {
+      let item = report["data"].members().find(|item| item["person"]["name"] == name).unwrap();
+      item["intervals"].members().cloned().collect()
+    };
+
+    let alice = intervals("Alice");
+    assert_eq!(alice.len(), 1);
+    assert_eq!(alice[0]["from"], "2022-08-12T04:01:15.000Z");
+    assert_eq!(alice[0]["till"], "2022-08-12T13:05:20.000Z");
+
+    // repeated check in within minute is same interval
+    let bob = intervals("Bob");
+    assert_eq!(bob.len(), 2);
+    assert_eq!(bob[0]["from"], "2022-08-12T04:30:02.000Z");
+    assert_eq!(bob[0]["last_from"], "2022-08-12T04:30:31.000Z");
+    assert_eq!(bob[0]["till"], "2022-08-12T08:00:45.000Z");
+    assert_eq!(bob[1]["from"], "2022-08-12T09:02:10.000Z");
+    assert_eq!(bob[1]["till"], "2022-08-12T13:00:05.000Z");
+
+    simulator.stop().await;
+    tmp_dir.close().unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_simulator_polling_and_registration() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    register(&mut app);
+
+    let simulator = Simulator::start(USERNAME, PASSWORD, ALERT_STREAM, ACS_EVENTS).unwrap();
+
+    let oid = ID::random();
+    let cid = camera(&app, &oid, simulator.port, "SIM-1");
+
+    let date = Utc.with_ymd_and_hms(2022, 8, 11, 0, 0, 0).unwrap();
+    let storage = app.wss.get(&oid).camera(&cid);
+    wait_for("events", || storage.events_on_date(date).len() == 30).await;
+
+    disable(&app, &cid);
+
+    // user and face registration
+    let person = app
+      .service("people")
+      .create(
+        Context::local(),
+        json::object! { oid: oid.to_base64(), name: "Carol", gender: "female" },
+        JsonValue::Null,
+      )
+      .unwrap();
+    let pid = ID::from_base64(person["_id"].string().as_bytes()).unwrap();
+    std::fs::write(app.wss.get(&oid).person(&pid).picture().path(), PICTURE).unwrap();
+
+    let params = json::object! { oid: oid.to_base64(), cid: cid.to_base64(), pid: pid.to_base64() };
+    for command in ["hikvision-create_user", "hikvision-register_image"] {
+      app
+        .service("actions")
+        .create(
+          Context::local(),
+          json::object! { command: command, params: params.clone() },
+          JsonValue::Null,
+        )
+        .unwrap();
+    }
+
+    let recorded = simulator.recorded.clone();
+    wait_for("registration", || {
+      let recorded = recorded.lock().unwrap();
+      !recorded.users.is_empty() && !recorded.faces.is_empty()
+    })
+    .await;
+
+    {
+      let recorded = recorded.lock().unwrap();
+      assert_eq!(recorded.users[0]["employeeNo"], pid.to_clear());
+      assert_eq!(recorded.users[0]["name"], "Carol");
+      assert_eq!(recorded.users[0]["gender"], "female");
+      assert_eq!(recorded.faces, vec![(pid.to_clear(), PICTURE.len())]);
+    }
+
+    simulator.stop().await;
+    tmp_dir.close().unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_simulator_wrong_password() {
+    let simulator = Simulator::start(USERNAME, PASSWORD, ALERT_STREAM, ACS_EVENTS).unwrap();
+
+    let client = reqwest::Client::new();
+    let url = format!("http://127.0.0.1:{}/ISAPI/System/deviceInfo", simulator.port);
+
+    use crate::hik::auth::WithDigestAuth;
+    let response = client.get(&url).send_with_digest_auth(USERNAME, "wrong").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client.get(&url).send_with_digest_auth(USERNAME, PASSWORD).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    simulator.stop().await;
+  }
+}
@@ -0,0 +1,642 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, SecondsFormat, Utc};
+use json::JsonValue;
+use std::ops::Sub;
+
+use service::error::Error;
+
+// distance from scheduled period at which check in or out still belongs to it
+const MARGIN_HOURS: i64 = 4;
+
+/// Kinds of leave recorded by `attendance-adjustments`, business trip is counted as worked
+/// scheduled time
+pub(crate) const LEAVES: [&str; 4] = ["vacation", "sick", "business_trip", "leave"];
+
+/// Work schedule of shift:
+/// `{ name, start: "09:00", end: "18:00", break: 60, grace: 5, days: [1, 2, 3, 4, 5],
+/// timezone: "+05:00" }`
+///
+/// `end` not after `start` is night shift ending next day, `days` are ISO weekdays (1 is Monday)
+/// of shift start, `break` and `grace` (allowed lateness) are in minutes.
+#[derive(Debug, Clone)]
+pub(crate) struct Shift {
+  start: NaiveTime,
+  end: NaiveTime,
+  break_time: i64,
+  grace: i64,
+  days: Vec<u32>,
+  pub(crate) offset: FixedOffset,
+}
+
+impl Shift {
+  pub(crate) fn from_json(data: &JsonValue) -> Result<Shift, Error> {
+    let time = |name: &str| {
+      NaiveTime::parse_from_str(data[name].as_str().unwrap_or_default(), "%H:%M")
+        .map_err(|_| Error::GeneralError(format!("shift `{name}` must be HH:MM")))
+    };
+
+    let days = if data["days"].is_array() {
+      data["days"]
+        .members()
+        .filter_map(|d| d.as_u32())
+        .filter(|d| (1..=7).contains(d))
+        .collect()
+    } else {
+      vec![1, 2, 3, 4, 5]
+    };
+
+    let offset = match data["timezone"].as_str() {
+      Some(timezone) => DateTime::parse_from_rfc3339(&format!("2000-01-01T00:00:00{timezone}"))
+        .map(|dt| *dt.offset())
+        .map_err(|_| Error::GeneralError(format!("invalid shift timezone `{timezone}`")))?,
+      None => utc(),
+    };
+
+    Ok(Shift {
+      start: time("start")?,
+      end: time("end")?,
+      break_time: data["break"].as_i64().unwrap_or(0).max(0),
+      grace: data["grace"].as_i64().unwrap_or(0).max(0),
+      days,
+      offset,
+    })
+  }
+
+  /// scheduled period of shift started at `day`, none at day off
+  pub(crate) fn period(&self, day: NaiveDate) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    if !self.days.contains(&day.weekday().number_from_monday()) {
+      return None;
+    }
+
+    let end_day = if self.end <= self.start { day.succ_opt()? } else { day };
+
+    let start = day.and_time(self.start).and_local_timezone(self.offset).single()?;
+    let end = end_day.and_time(self.end).and_local_timezone(self.offset).single()?;
+
+    Some((start.with_timezone(&Utc), end.with_timezone(&Utc)))
+  }
+
+  // minutes of work by schedule
+  fn scheduled(&self, period: &(DateTime<Utc>, DateTime<Utc>)) -> i64 {
+    (period.1.sub(period.0).num_minutes() - self.break_time).max(0)
+  }
+}
+
+pub(crate) fn utc() -> FixedOffset {
+  FixedOffset::east_opt(0).unwrap()
+}
+
+fn to_json(dt: Option<DateTime<Utc>>) -> JsonValue {
+  dt.map(|d| d.to_rfc3339_opts(SecondsFormat::Millis, true).into())
+    .unwrap_or(JsonValue::Null)
+}
+
+/// Presence between check in and check out, repeated check in or out within minute extend
+/// `last_from` or `last_till`. Interval is `manual` if any of its check in or out is correction,
+/// `authors` are accounts that made corrections.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Interval {
+  pub(crate) from: Option<DateTime<Utc>>,
+  pub(crate) last_from: Option<DateTime<Utc>>,
+  pub(crate) till: Option<DateTime<Utc>>,
+  pub(crate) last_till: Option<DateTime<Utc>>,
+  pub(crate) manual: bool,
+  pub(crate) authors: Vec<String>,
+}
+
+impl Interval {
+  fn check_in(dt: DateTime<Utc>, correction: Option<&str>) -> Self {
+    let mut interval = Interval { from: Some(dt), last_from: Some(dt), ..Interval::empty() };
+    interval.mark(correction);
+    interval
+  }
+
+  fn check_out(dt: DateTime<Utc>, correction: Option<&str>) -> Self {
+    let mut interval = Interval { till: Some(dt), last_till: Some(dt), ..Interval::empty() };
+    interval.mark(correction);
+    interval
+  }
+
+  fn empty() -> Self {
+    Interval {
+      from: None,
+      last_from: None,
+      till: None,
+      last_till: None,
+      manual: false,
+      authors: vec![],
+    }
+  }
+
+  fn mark(&mut self, correction: Option<&str>) {
+    if let Some(author) = correction {
+      self.manual = true;
+      if !author.is_empty() && !self.authors.iter().any(|a| a == author) {
+        self.authors.push(author.to_string());
+      }
+    }
+  }
+
+  // moment interval is attributed by
+  fn anchor(&self) -> Option<DateTime<Utc>> {
+    self.from.or(self.till)
+  }
+
+  fn minutes(&self) -> i64 {
+    match (self.from, self.till) {
+      (Some(from), Some(till)) => till.sub(from).num_minutes().max(0),
+      _ => 0,
+    }
+  }
+
+  pub(crate) fn to_json(&self) -> JsonValue {
+    json::object! {
+      from: to_json(self.from),
+      last_from: to_json(self.last_from),
+      till: to_json(self.till),
+      last_till: to_json(self.last_till),
+      manual: self.manual,
+      authors: self.authors.clone(),
+    }
+  }
+}
+
+/// add check in (`is_in`) or check out at `dt` to intervals, events expected in time order,
+/// `correction` is author of `attendance-adjustments` record, `None` for camera event
+pub(crate) fn register(
+  intervals: &mut Vec<Interval>,
+  is_in: bool,
+  dt: DateTime<Utc>,
+  correction: Option<&str>,
+) {
+  let current = match intervals.last_mut() {
+    Some(current) => current,
+    None => {
+      intervals.push(if is_in {
+        Interval::check_in(dt, correction)
+      } else {
+        Interval::check_out(dt, correction)
+      });
+      return;
+    },
+  };
+
+  if is_in {
+    match current.last_from {
+      Some(last) if dt.sub(last) <= Duration::minutes(1) => {
+        current.last_from = Some(dt);
+        current.mark(correction);
+      },
+      _ => intervals.push(Interval::check_in(dt, correction)),
+    }
+  } else if current.till.is_none() {
+    current.till = Some(dt);
+    current.last_till = Some(dt);
+    current.mark(correction);
+  } else {
+    match current.last_till {
+      Some(last) if dt.sub(last) <= Duration::minutes(1) => {
+        current.last_till = Some(dt);
+        current.mark(correction);
+      },
+      _ => intervals.push(Interval::check_out(dt, correction)),
+    }
+  }
+}
+
+/// Leave of one of `LEAVES` kinds from `from` till `till` (inclusive), recorded by `author`
+#[derive(Debug, Clone)]
+pub(crate) struct Leave {
+  pub(crate) kind: String,
+  pub(crate) from: NaiveDate,
+  pub(crate) till: NaiveDate,
+  pub(crate) author: Option<String>,
+}
+
+/// Attendance of person at day, durations are in minutes
+#[derive(Debug, Clone)]
+pub(crate) struct Day {
+  pub(crate) date: NaiveDate,
+  pub(crate) period: Option<(DateTime<Utc>, DateTime<Utc>)>,
+  pub(crate) intervals: Vec<Interval>,
+  pub(crate) leave: Option<String>,
+  pub(crate) leave_author: Option<String>,
+
+  pub(crate) worked: i64,
+  pub(crate) late: i64,
+  pub(crate) early_leave: i64,
+  pub(crate) overtime: i64,
+  pub(crate) absent: bool,
+  // scheduled period isn't over yet, so absence or early leave isn't known
+  pub(crate) pending: bool,
+  // check in without check out or other way around
+  pub(crate) incomplete: bool,
+}
+
+impl Day {
+  fn new(date: NaiveDate, period: Option<(DateTime<Utc>, DateTime<Utc>)>) -> Self {
+    Day {
+      date,
+      period,
+      intervals: vec![],
+      leave: None,
+      leave_author: None,
+      worked: 0,
+      late: 0,
+      early_leave: 0,
+      overtime: 0,
+      absent: false,
+      pending: false,
+      incomplete: false,
+    }
+  }
+
+  fn calculate(&mut self, shift: Option<&Shift>, now: DateTime<Utc>) {
+    self.incomplete = self.intervals.iter().any(|i| i.from.is_none() || i.till.is_none());
+
+    let presence: i64 = self.intervals.iter().map(|i| i.minutes()).sum();
+
+    let (shift, period) = match (shift, self.period) {
+      (Some(shift), Some(period)) => (shift, period),
+      // no schedule
+      (None, _) => {
+        self.worked = presence;
+        return;
+      },
+      // day off
+      (Some(_), None) => {
+        self.worked = presence;
+        self.overtime = presence;
+        return;
+      },
+    };
+
+    self.pending = period.1 > now;
+
+    if self.intervals.is_empty() {
+      match self.leave.as_deref() {
+        Some("business_trip") => self.worked = shift.scheduled(&period),
+        Some(_) => {},
+        None => self.absent = !self.pending,
+      }
+      return;
+    }
+
+    // away time between intervals is taken as break
+    let complete: Vec<&Interval> =
+      self.intervals.iter().filter(|i| i.from.is_some() && i.till.is_some()).collect();
+    let away: i64 = complete
+      .windows(2)
+      .map(|pair| pair[1].from.unwrap().sub(pair[0].till.unwrap()).num_minutes().max(0))
+      .sum();
+    self.worked = (presence - (shift.break_time - away).max(0)).max(0);
+
+    // not expected at work in full
+    if self.leave.is_some() {
+      self.overtime = (self.worked - shift.scheduled(&period)).max(0);
+      return;
+    }
+
+    if let Some(first) = self.intervals.iter().filter_map(|i| i.from).min() {
+      let late = first.sub(period.0).num_minutes();
+      if late > shift.grace {
+        self.late = late;
+      }
+    }
+
+    if let Some(last) = self.intervals.iter().filter_map(|i| i.till).max() {
+      if !self.pending {
+        self.early_leave = period.1.sub(last).num_minutes().max(0);
+      }
+    }
+
+    self.overtime = (self.worked - shift.scheduled(&period)).max(0);
+  }
+
+  /// accounts that recorded leave or corrections of the day
+  fn authors(&self) -> Vec<String> {
+    let mut authors: Vec<String> = self.leave_author.iter().cloned().collect();
+    for author in self.intervals.iter().flat_map(|i| i.authors.iter()) {
+      if !authors.contains(author) {
+        authors.push(author.clone());
+      }
+    }
+    authors
+  }
+
+  pub(crate) fn to_json(&self) -> JsonValue {
+    json::object! {
+      date: self.date.format("%Y-%m-%d").to_string(),
+      scheduled: match self.period {
+        Some((from, till)) => json::object! { from: to_json(Some(from)), till: to_json(Some(till)) },
+        None => JsonValue::Null,
+      },
+      intervals: JsonValue::Array(self.intervals.iter().map(|i| i.to_json()).collect()),
+      leave: self.leave.clone(),
+      manual: self.leave.is_some() || self.intervals.iter().any(|i| i.manual),
+      authors: self.authors(),
+      worked: self.worked,
+      late: self.late,
+      early_leave: self.early_leave,
+      overtime: self.overtime,
+      absent: self.absent,
+      pending: self.pending,
+      incomplete: self.incomplete,
+    }
+  }
+}
+
+/// Days from `from` till `till` (inclusive) with intervals attributed to scheduled period they
+/// are close to (night shift check out belongs to day shift started), otherwise to local date.
+/// Days covered by leaves are not absences, neither are days with schedule that isn't over yet.
+pub(crate) fn days(
+  shift: Option<&Shift>,
+  intervals: &[Interval],
+  leaves: &[Leave],
+  from: NaiveDate,
+  till: NaiveDate,
+) -> Vec<Day> {
+  let now = Utc::now();
+  let offset = shift.map(|s| s.offset).unwrap_or_else(utc);
+  let margin = Duration::hours(MARGIN_HOURS);
+
+  let mut days: Vec<Day> = from
+    .iter_days()
+    .take_while(|d| *d <= till)
+    .map(|date| Day::new(date, shift.and_then(|s| s.period(date))))
+    .collect();
+
+  // periods of days around range, to skip intervals of them
+  let before = from.pred_opt().and_then(|d| shift.and_then(|s| s.period(d)));
+
+  for interval in intervals {
+    let anchor = match interval.anchor() {
+      Some(anchor) => anchor,
+      None => continue,
+    };
+
+    if let Some((start, end)) = before {
+      if anchor >= start.sub(margin) && anchor <= end + margin {
+        continue;
+      }
+    }
+
+    let scheduled = days.iter().position(|day| match day.period {
+      Some((start, end)) => anchor >= start.sub(margin) && anchor <= end + margin,
+      None => false,
+    });
+
+    let position = match scheduled {
+      Some(position) => Some(position),
+      None => {
+        let date = anchor.with_timezone(&offset).date_naive();
+        days.iter().position(|day| day.date == date)
+      },
+    };
+
+    if let Some(position) = position {
+      days[position].intervals.push(interval.clone());
+    }
+  }
+
+  for day in &mut days {
+    let leave = leaves
+      .iter()
+      .rev()
+      .find(|leave| leave.from <= day.date && day.date <= leave.till);
+    day.leave = leave.map(|leave| leave.kind.clone());
+    day.leave_author = leave.and_then(|leave| leave.author.clone());
+    day.calculate(shift, now);
+  }
+
+  days
+}
+
+/// Sums of days, durations are in minutes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Totals {
+  pub(crate) worked: i64,
+  pub(crate) late: i64,
+  pub(crate) early_leave: i64,
+  pub(crate) overtime: i64,
+  pub(crate) absences: i64,
+  // days with presence
+  pub(crate) present: i64,
+  // days on leave
+  pub(crate) leaves: i64,
+}
+
+impl Totals {
+  pub(crate) fn of(days: &[Day]) -> Self {
+    let mut totals = Totals::default();
+    for day in days {
+      totals.worked += day.worked;
+      totals.late += day.late;
+      totals.early_leave += day.early_leave;
+      totals.overtime += day.overtime;
+      if day.absent {
+        totals.absences += 1;
+      }
+      if !day.intervals.is_empty() {
+        totals.present += 1;
+      }
+      if day.leave.is_some() {
+        totals.leaves += 1;
+      }
+    }
+    totals
+  }
+
+  pub(crate) fn add(&mut self, other: &Totals) {
+    self.worked += other.worked;
+    self.late += other.late;
+    self.early_leave += other.early_leave;
+    self.overtime += other.overtime;
+    self.absences += other.absences;
+    self.present += other.present;
+    self.leaves += other.leaves;
+  }
+
+  pub(crate) fn to_json(&self) -> JsonValue {
+    json::object! {
+      worked: self.worked,
+      late: self.late,
+      early_leave: self.early_leave,
+      overtime: self.overtime,
+      absences: self.absences,
+      present: self.present,
+      leaves: self.leaves,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::TimeZone;
+
+  fn at(s: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+  }
+
+  fn date(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+  }
+
+  fn intervals(events: &[(bool, &str)]) -> Vec<Interval> {
+    let mut intervals = vec![];
+    for (is_in, dt) in events {
+      register(&mut intervals, *is_in, at(dt), None);
+    }
+    intervals
+  }
+
+  #[test]
+  fn test_day_shift() {
+    let shift = Shift::from_json(&json::object! {
+      start: "09:00", end: "18:00", "break": 60, grace: 5, timezone: "+05:00"
+    })
+    .unwrap();
+
+    // Friday 2022-08-12, weekend and Monday
+    let intervals = intervals(&[
+      (true, "2022-08-12T09:20:00+05:00"),
+      (true, "2022-08-12T09:20:30+05:00"),
+      (false, "2022-08-12T13:00:00+05:00"),
+      (true, "2022-08-12T13:30:00+05:00"),
+      (false, "2022-08-12T17:30:00+05:00"),
+      (true, "2022-08-13T10:00:00+05:00"),
+      (false, "2022-08-13T12:00:00+05:00"),
+    ]);
+    assert_eq!(intervals.len(), 3);
+
+    let days = days(Some(&shift), &intervals, &[], date("2022-08-12"), date("2022-08-15"));
+    assert_eq!(days.len(), 4);
+
+    let friday = &days[0];
+    assert_eq!(friday.intervals.len(), 2);
+    assert_eq!(friday.late, 20);
+    assert_eq!(friday.early_leave, 30);
+    // 220 + 240 minutes of presence, 30 of 60 minutes break taken outside
+    assert_eq!(friday.worked, 430);
+    assert_eq!(friday.overtime, 0);
+    assert!(!friday.absent);
+
+    // work at day off is overtime
+    let saturday = &days[1];
+    assert_eq!(saturday.period, None);
+    assert_eq!((saturday.worked, saturday.overtime), (120, 120));
+
+    assert!(!days[2].absent);
+    assert!(days[3].absent);
+
+    let totals = Totals::of(&days);
+    assert_eq!(totals.worked, 550);
+    assert_eq!(totals.absences, 1);
+    assert_eq!(totals.present, 2);
+  }
+
+  #[test]
+  fn test_night_shift() {
+    let shift = Shift::from_json(&json::object! {
+      start: "22:00", end: "06:00", "break": 30, days: [1, 2, 3, 4, 5, 6, 7]
+    })
+    .unwrap();
+
+    let (start, end) = shift.period(date("2022-08-12")).unwrap();
+    assert_eq!(start, Utc.with_ymd_and_hms(2022, 8, 12, 22, 0, 0).unwrap());
+    assert_eq!(end, Utc.with_ymd_and_hms(2022, 8, 13, 6, 0, 0).unwrap());
+
+    let intervals = intervals(&[
+      // check out of shift started before range
+      (false, "2022-08-12T06:05:00Z"),
+      (true, "2022-08-12T21:50:00Z"),
+      (false, "2022-08-13T07:00:00Z"),
+      (true, "2022-08-13T22:30:00Z"),
+    ]);
+
+    let days = days(Some(&shift), &intervals, &[], date("2022-08-12"), date("2022-08-14"));
+
+    // crossing midnight is one day
+    assert_eq!(days[0].intervals.len(), 1);
+    assert_eq!(days[0].worked, 9 * 60 + 10 - 30);
+    assert_eq!(days[0].overtime, 9 * 60 + 10 - 30 - (8 * 60 - 30));
+    assert_eq!(days[0].late, 0);
+    assert_eq!(days[0].early_leave, 0);
+
+    // no check out yet
+    assert_eq!(days[1].late, 30);
+    assert!(days[1].incomplete);
+    assert_eq!(days[1].worked, 0);
+
+    assert!(days[2].absent);
+  }
+
+  #[test]
+  fn test_without_shift() {
+    let intervals = intervals(&[(true, "2022-08-12T09:00:00Z"), (false, "2022-08-12T17:00:00Z")]);
+
+    let days = days(None, &intervals, &[], date("2022-08-12"), date("2022-08-13"));
+    assert_eq!(days[0].worked, 480);
+    assert_eq!(days[0].overtime, 0);
+    assert!(!days[1].absent);
+
+    assert!(Shift::from_json(&json::object! { start: "9", end: "18:00" }).is_err());
+  }
+
+  #[test]
+  fn test_leaves_and_corrections() {
+    let shift =
+      Shift::from_json(&json::object! { start: "09:00", end: "18:00", "break": 60 }).unwrap();
+
+    // forgotten badge at Wednesday morning is corrected
+    let mut intervals = vec![];
+    register(&mut intervals, true, at("2022-08-17T09:00:00Z"), Some("hr"));
+    register(&mut intervals, false, at("2022-08-17T18:00:00Z"), None);
+    assert!(intervals[0].manual);
+    assert_eq!(intervals[0].authors, vec!["hr".to_string()]);
+
+    let leave = |kind: &str, day: &str| Leave {
+      kind: kind.into(),
+      from: date(day),
+      till: date(day),
+      author: Some("manager".into()),
+    };
+    let leaves = [leave("vacation", "2022-08-15"), leave("business_trip", "2022-08-16")];
+
+    let days = days(Some(&shift), &intervals, &leaves, date("2022-08-15"), date("2022-08-18"));
+
+    assert_eq!(days[0].leave.as_deref(), Some("vacation"));
+    assert_eq!((days[0].absent, days[0].worked), (false, 0));
+    assert_eq!(days[0].to_json()["manual"], true);
+    assert_eq!(days[0].to_json()["authors"][0], "manager");
+
+    assert_eq!(days[1].leave.as_deref(), Some("business_trip"));
+    assert_eq!(days[1].worked, 480);
+
+    assert_eq!(days[2].worked, 480);
+    assert_eq!(days[2].to_json()["intervals"][0]["manual"], true);
+    assert_eq!(days[2].to_json()["intervals"][0]["authors"][0], "hr");
+    assert_eq!(days[2].to_json()["authors"].len(), 1);
+
+    assert!(days[3].absent);
+
+    let totals = Totals::of(&days);
+    assert_eq!((totals.leaves, totals.absences, totals.present), (2, 1, 1));
+  }
+
+  #[test]
+  fn test_future_days() {
+    let shift =
+      Shift::from_json(&json::object! { start: "09:00", end: "18:00", "break": 60 }).unwrap();
+
+    let from = Utc::now().date_naive() + Duration::days(7);
+    let days = days(Some(&shift), &[], &[], from, from + Duration::days(6));
+
+    assert!(days.iter().any(|day| day.period.is_some()));
+    for day in &days {
+      assert!(!day.absent);
+      assert_eq!(day.pending, day.period.is_some());
+    }
+    assert_eq!(Totals::of(&days).absences, 0);
+  }
+}
@@ -1 +1,3 @@
+pub(crate) mod attendance;
 pub(crate) mod services;
+pub(crate) mod timesheet;
@@ -0,0 +1,402 @@
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use json::JsonValue;
+use std::sync::Arc;
+
+use crate::hr::attendance::{Leave, LEAVES};
+use crate::services::{string_to_id, Data, Params};
+use crate::{commutator::Application, storage::Workspaces};
+use service::error::Error;
+use service::utils::json::JsonParams;
+use service::{Context, Schema, Service};
+use values::ID;
+
+/// Manual records of attendance:
+/// - leave `{ person, "type": "vacation" | "sick" | "business_trip" | "leave",
+///   dates: { from, till }, reason }`
+/// - correction `{ person, "type": "in" | "out", time: "2022-08-12T09:00:00+05:00", reason }`
+///
+/// `author` is account that created record, `updated_by` one that changed it last, both are set
+/// by service. `attendance-report` merges records with camera events and marks results as manual
+/// with their authors.
+pub(crate) struct AttendanceAdjustments {
+  app: Application,
+  name: String,
+
+  ws: Workspaces,
+}
+
+/// Adjustment of person
+pub(crate) enum Adjustment {
+  Leave(Leave),
+  // check in (`true`) or out at time
+  Correction(bool, DateTime<Utc>),
+}
+
+impl Adjustment {
+  pub(crate) fn from_json(data: &JsonValue) -> Result<Adjustment, Error> {
+    if data["reason"].string().trim().is_empty() {
+      return Err(Error::GeneralError("`reason` required".into()));
+    }
+
+    let date = |name: &str| {
+      NaiveDate::parse_from_str(&data["dates"][name].string(), "%Y-%m-%d")
+        .map_err(|_| Error::GeneralError(format!("`dates.{name}` must be YYYY-MM-DD")))
+    };
+
+    match data["type"].string().as_str() {
+      kind @ ("in" | "out") => {
+        let time = DateTime::parse_from_rfc3339(&data["time"].string())
+          .map_err(|_| Error::GeneralError("`time` must be RFC 3339".into()))?;
+        Ok(Adjustment::Correction(kind == "in", time.into()))
+      },
+      kind if LEAVES.contains(&kind) => {
+        let (from, till) = (date("from")?, date("till")?);
+        if till < from {
+          return Err(Error::GeneralError("`dates.till` before `dates.from`".into()));
+        }
+        let author = data["author"].string_or_none();
+        Ok(Adjustment::Leave(Leave { kind: kind.to_string(), from, till, author }))
+      },
+      kind => Err(Error::GeneralError(format!("unknown adjustment type `{kind}`"))),
+    }
+  }
+
+  fn overlaps(&self, from: NaiveDate, till: NaiveDate) -> bool {
+    match self {
+      Adjustment::Leave(leave) => leave.from <= till && from <= leave.till,
+      Adjustment::Correction(_, time) => {
+        let date = time.date_naive();
+        from <= date && date <= till
+      },
+    }
+  }
+}
+
+impl AttendanceAdjustments {
+  pub(crate) fn new(app: Application, ws: Workspaces) -> Arc<dyn Service> {
+    Arc::new(AttendanceAdjustments { app, name: "attendance-adjustments".to_string(), ws })
+  }
+
+  fn prepare(&self, data: &Data, id: ID) -> Result<JsonValue, Error> {
+    if !data.is_object() {
+      return Err(Error::GeneralError("only object allowed".into()));
+    }
+
+    let oid = crate::services::oid(data)?;
+    let pid = string_to_id(data["person"].string())
+      .map_err(|_| Error::GeneralError("`person` required".into()))?;
+    if !self.ws.get(&oid).person(&pid).path.exists() {
+      return Err(Error::NotFound(format!("person {}", pid.to_base64())));
+    }
+
+    Adjustment::from_json(data)?;
+
+    let mut obj = data.clone();
+    obj["_id"] = id.to_base64().into();
+    obj["reason"] = data["reason"].string().trim().into();
+    obj.remove("oid");
+    obj.remove("author");
+    obj.remove("updated_by");
+
+    Ok(obj)
+  }
+}
+
+fn account_id(ctx: &Context) -> String {
+  ctx.account.read().unwrap().id.to_base64()
+}
+
+impl Service for AttendanceAdjustments {
+  fn path(&self) -> &str {
+    &self.name
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    let dates = json::object! {
+      type: "object",
+      description: "of leave",
+      properties: {
+        from: { type: "string", format: "date" },
+        till: { type: "string", format: "date" },
+      },
+    };
+    let properties = json::object! {
+      person: { type: "string" },
+      type: { type: "string", enum: ["vacation", "sick", "business_trip", "leave", "in", "out"] },
+      dates: dates.clone(),
+      time: { type: "string", format: "date-time", description: "of correction" },
+      reason: { type: "string" },
+    };
+
+    let mut response = properties.clone();
+    response["_id"] = json::object! { type: "string" };
+    response["author"] = json::object! { type: "string", description: "account created record" };
+    response["updated_by"] = json::object! { type: "string" };
+    response["updated"] = json::object! { type: "string", format: "date-time" };
+
+    Some(Schema::new(
+      json::object! { type: "object", properties: { person: { type: "string" }, dates: dates } },
+      json::object! {
+        type: "object",
+        required: ["person", "type", "reason"],
+        properties: properties,
+      },
+      json::object! { type: "object", properties: response },
+    ))
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let person = self.params(&params)["person"].string();
+    let range = self
+      .date_range(&params)?
+      .map(|range| (range.0.date_naive(), range.1.date_naive()));
+
+    let mut list: Vec<JsonValue> = self
+      .ws
+      .get(&oid)
+      .adjustments()
+      .iter()
+      .map(|o| o.json())
+      .filter(|o| person.is_empty() || o["person"].string() == person)
+      .filter(|o| match (range, Adjustment::from_json(o)) {
+        (None, _) => true,
+        (Some((from, till)), Ok(adjustment)) => adjustment.overlaps(from, till),
+        (Some(_), Err(_)) => false,
+      })
+      .collect();
+
+    // latest changes first
+    list.sort_by(|a, b| b["updated"].string().cmp(&a["updated"].string()));
+
+    let total = list.len();
+    let list = list.into_iter().skip(skip).take(limit).collect();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let id = string_to_id(id)?;
+    self.ws.get(&oid).adjustment(id).load()
+  }
+
+  fn create(&self, ctx: Context, data: Data, _params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&data)?;
+
+    let id = ID::random();
+    let mut obj = self.prepare(&data, id)?;
+    obj["author"] = account_id(&ctx).into();
+    obj["updated"] = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true).into();
+
+    self.ws.get(&oid).adjustment(id).save(obj.dump())?;
+
+    Ok(obj)
+  }
+
+  fn update(
+    &self,
+    ctx: Context,
+    id: String,
+    data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    let oid = crate::services::oid(&data)?;
+
+    let id = string_to_id(id)?;
+    let storage = self.ws.get(&oid).adjustment(id);
+    let before = storage.load()?;
+
+    let mut obj = self.prepare(&data, id)?;
+    obj["author"] = before["author"].clone();
+    obj["updated_by"] = account_id(&ctx).into();
+    obj["updated"] = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true).into();
+
+    storage.save(obj.dump())?;
+
+    Ok(obj)
+  }
+
+  fn patch(&self, ctx: Context, id: String, data: Data, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    if !data.is_object() {
+      return Err(Error::GeneralError("only object allowed".into()));
+    }
+
+    let id = string_to_id(id)?;
+    let storage = self.ws.get(&oid).adjustment(id);
+
+    let mut obj = storage.load()?;
+    let author = obj["author"].clone();
+    for (n, v) in data.entries() {
+      if n != "_id" {
+        obj[n] = v.clone();
+      }
+    }
+    obj["oid"] = oid.to_base64().into();
+
+    let mut obj = self.prepare(&obj, id)?;
+    obj["author"] = author;
+    obj["updated_by"] = account_id(&ctx).into();
+    obj["updated"] = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true).into();
+
+    storage.save(obj.dump())?;
+
+    Ok(obj)
+  }
+
+  fn remove(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    let id = string_to_id(id)?;
+
+    self.ws.get(&oid).adjustment(id).delete()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::hr::services::attendance_report::AttendanceReport;
+  use crate::hr::services::shifts::Shifts;
+  use crate::services::People;
+  use crate::warehouse::test_util::init;
+  use service::Services;
+
+  #[actix_web::test]
+  async fn test_attendance_adjustments() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(People::new(app.clone()));
+    app.register(Shifts::new(app.clone(), app.wss.clone()));
+    app.register(AttendanceReport::new(app.clone(), app.wss.clone()));
+    app.register(AttendanceAdjustments::new(app.clone(), app.wss.clone()));
+
+    let oid = ID::random();
+
+    let create = |service: &str, mut data: JsonValue| -> crate::services::Result {
+      data["oid"] = oid.to_base64().into();
+      app.service(service).create(Context::local(), data, JsonValue::Null)
+    };
+
+    let shift = create("shifts", json::object! { start: "09:00", end: "18:00", "break": 60 })
+      .unwrap()["_id"]
+      .string();
+    let pid = create("people", json::object! { name: "Alice", employeeNoString: "1", shift: shift })
+      .unwrap()["_id"]
+      .string();
+
+    // reason is required, type must be known
+    assert!(create("attendance-adjustments", json::object! { person: pid.clone(), "type": "sick" })
+      .is_err());
+    assert!(create(
+      "attendance-adjustments",
+      json::object! { person: pid.clone(), "type": "holiday", reason: "?" }
+    )
+    .is_err());
+
+    // author is set by service, not by client
+    let author = Context::local().account.read().unwrap().id.to_base64();
+    let vacation = create(
+      "attendance-adjustments",
+      json::object! {
+        person: pid.clone(), "type": "vacation", reason: "annual", author: "someone else",
+        dates: { from: "2022-08-11", till: "2022-08-12" }
+      },
+    )
+    .unwrap();
+    assert_eq!(vacation["author"].string(), author);
+    assert!(vacation["updated_by"].is_null());
+
+    let correction = create(
+      "attendance-adjustments",
+      json::object! {
+        person: pid.clone(), "type": "in", time: "2022-08-15T09:00:00Z", reason: "forgot badge"
+      },
+    )
+    .unwrap();
+
+    let patched = app
+      .service("attendance-adjustments")
+      .patch(
+        Context::local(),
+        correction["_id"].string(),
+        json::object! { reason: "badge left at home", author: "someone else" },
+        json::object! { oid: oid.to_base64() },
+      )
+      .unwrap();
+    assert_eq!(patched["author"].string(), author);
+    assert_eq!(patched["updated_by"].string(), author);
+
+    // camera registered check out only
+    let camera = app.wss.get(&oid).camera(&ID::random());
+    let dt: DateTime<Utc> = DateTime::parse_from_rfc3339("2022-08-15T18:00:00Z").unwrap().into();
+    let event = json::object! {
+      event: {
+        major: 5, minor: 75, time: "2022-08-15T18:00:00Z", employeeNoString: "1",
+        attendanceStatus: "checkOut"
+      }
+    };
+    camera
+      .event(&"2022-08-15T18:00:00.000Z".to_string(), &dt)
+      .create()
+      .unwrap()
+      .save(event.dump())
+      .unwrap();
+
+    let found = app
+      .service("attendance-adjustments")
+      .find(
+        Context::local(),
+        json::object! { oid: oid.to_base64(), dates: { from: "2022-08-12", till: "2022-08-12" } },
+      )
+      .unwrap();
+    assert_eq!(found["total"], 1);
+    assert_eq!(found["data"][0]["type"], "vacation");
+
+    let report = app
+      .service("attendance-report")
+      .find(
+        Context::local(),
+        json::object! { oid: oid.to_base64(), dates: { from: "2022-08-12", till: "2022-08-15" } },
+      )
+      .unwrap();
+    let days = &report["data"][0]["days"];
+
+    assert_eq!(days[0]["leave"], "vacation");
+    assert_eq!(days[0]["absent"], false);
+    assert_eq!(days[0]["manual"], true);
+    assert_eq!(days[0]["authors"][0].string(), author);
+
+    assert_eq!(days[3]["intervals"].len(), 1);
+    assert_eq!(days[3]["intervals"][0]["from"], "2022-08-15T09:00:00.000Z");
+    assert_eq!(days[3]["intervals"][0]["manual"], true);
+    assert_eq!(days[3]["intervals"][0]["authors"][0].string(), author);
+    assert_eq!(days[3]["worked"], 480);
+    assert_eq!(report["data"][0]["totals"]["leaves"], 1);
+    assert_eq!(report["data"][0]["totals"]["absences"], 0);
+
+    app
+      .service("attendance-adjustments")
+      .remove(Context::local(), vacation["_id"].string(), json::object! { oid: oid.to_base64() })
+      .unwrap();
+
+    let report = app
+      .service("attendance-report")
+      .find(Context::local(), json::object! { oid: oid.to_base64(), date: "2022-08-12" })
+      .unwrap();
+    assert_eq!(report["data"][0]["days"][0]["absent"], true);
+
+    tmp_dir.close().unwrap();
+  }
+}
@@ -1,15 +1,17 @@
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use json::JsonValue;
 use std::collections::HashMap;
-use std::ops::Sub;
 use std::sync::Arc;
 
+use crate::hr::attendance::{self, Interval, Leave, Shift, Totals};
+use crate::hr::services::attendance_adjustments::Adjustment;
 use crate::services::{Data, Params};
 use crate::storage::SCamera;
 use crate::{commutator::Application, storage::Workspaces};
 use service::error::Error;
 use service::utils::json::JsonParams;
-use service::{Context, Service};
+use service::utils::time::DateRange;
+use service::{Context, Schema, Service};
 use values::ID;
 
 pub struct AttendanceReport {
@@ -32,7 +34,7 @@ impl AttendanceReport {
       .filter(|(_, e)| e.is_object())
       .collect();
 
-    let event_type = match camera.config() {
+    let event_type = match camera.config(&self.app.settings.hik.secret()) {
       Ok(config) => config.event_type,
       Err(_) => "".into(),
     };
@@ -50,51 +52,103 @@ impl AttendanceReport {
   }
 }
 
-#[derive(Debug, Clone)]
-struct State {
-  intervals: Vec<Interval>,
-}
-
-impl Default for State {
-  fn default() -> Self {
-    State { intervals: vec![] }
+impl Service for AttendanceReport {
+  fn path(&self) -> &str {
+    &self.name
   }
-}
-
-#[derive(Debug, Clone)]
-struct Interval {
-  from: Option<DateTime<Utc>>,
-  last_from: Option<DateTime<Utc>>,
-  till: Option<DateTime<Utc>>,
-  last_till: Option<DateTime<Utc>>,
-}
 
-impl Interval {
-  fn to_json(&self) -> JsonValue {
-    json::object! {
-      from: self.from.map(|d| d.to_rfc3339_opts(SecondsFormat::Millis, true).into()).unwrap_or(JsonValue::Null),
-      last_from: self.last_from.map(|d| d.to_rfc3339_opts(SecondsFormat::Millis, true).into()).unwrap_or(JsonValue::Null),
-      till: self.till.map(|d| d.to_rfc3339_opts(SecondsFormat::Millis, true).into()).unwrap_or(JsonValue::Null),
-      last_till: self.last_till.map(|d| d.to_rfc3339_opts(SecondsFormat::Millis, true).into()).unwrap_or(JsonValue::Null),
-    }
+  fn commands(&self) -> &[&str] {
+    &["find"]
   }
-}
 
-impl Service for AttendanceReport {
-  fn path(&self) -> &str {
-    &self.name
+  fn schema(&self) -> Option<Schema> {
+    let interval = json::object! {
+      type: "object",
+      properties: {
+        from: { type: "string", format: "date-time" },
+        last_from: { type: "string", format: "date-time" },
+        till: { type: "string", format: "date-time" },
+        last_till: { type: "string", format: "date-time" },
+        manual: { type: "boolean" },
+        authors: { type: "array", items: { type: "string" } },
+      },
+    };
+    let minutes = json::object! { type: "integer", description: "minutes" };
+    Some(Schema::new(
+      json::object! {
+        type: "object",
+        properties: {
+          date: { type: "string", format: "date" },
+          dates: {
+            type: "object",
+            properties: {
+              from: { type: "string", format: "date" },
+              till: { type: "string", format: "date" },
+            },
+          },
+          division: { type: "string" },
+        },
+      },
+      JsonValue::Null,
+      json::object! {
+        type: "object",
+        properties: {
+          _id: { type: "string" },
+          person: { type: "object" },
+          department: { type: "object" },
+          shift: { type: "string" },
+          days: {
+            type: "array",
+            items: {
+              type: "object",
+              properties: {
+                date: { type: "string", format: "date" },
+                scheduled: { type: "object" },
+                intervals: { type: "array", items: interval.clone() },
+                leave: { type: "string" },
+                manual: { type: "boolean" },
+                authors: { type: "array", items: { type: "string" } },
+                worked: minutes.clone(),
+                late: minutes.clone(),
+                early_leave: minutes.clone(),
+                overtime: minutes,
+                absent: { type: "boolean" },
+                pending: { type: "boolean" },
+                incomplete: { type: "boolean" },
+              },
+            },
+          },
+          intervals: { type: "array", items: interval },
+          totals: { type: "object" },
+        },
+      },
+    ))
   }
 
   fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
-    let _limit = self.limit(&params);
-    let _skip = self.skip(&params);
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
 
     let oid = crate::services::oid(&params)?;
+    let ws = self.ws.get(&oid);
 
-    let people = self.ws.get(&oid).people();
+    // single `date` keeps intervals of the day at item as before ranges
+    let (DateRange(from, till), single) = if let Some(range) = self.date_range(&params)? {
+      (range, false)
+    } else if let Some(date) = self.date("date", &params)? {
+      (DateRange(date, date), true)
+    } else {
+      return Err(Error::GeneralError("`date` or `dates` required".into()));
+    };
+    if till < from {
+      return Err(Error::GeneralError("`dates.till` before `dates.from`".into()));
+    }
+    let date = from.to_string();
+    let (from, till) = (from.date_naive(), till.date_naive());
 
     let people: Vec<(ID, JsonValue)> = {
-      let it = people.iter().map(|p| (p.id, p.load().unwrap())).filter(|(_, p)| p.is_object());
+      let it = ws.people().into_iter().map(|p| (p.id, p.load().unwrap_or(JsonValue::Null)));
+      let it = it.filter(|(_, p)| p.is_object());
 
       let division = self.params(&params)["division"].string();
       if division.is_empty() {
@@ -104,173 +158,152 @@ impl Service for AttendanceReport {
       }
     };
 
-    println!("people {}", people.len());
-
     // mapping from short id to long one
     let mut mapping = HashMap::with_capacity(people.len());
-    let mut statuses = HashMap::new();
-
     people.iter().for_each(|(id, data)| {
       let str = data["employeeNoString"].string();
       let id_on_camera = if str.is_empty() { id.to_clear() } else { str };
-      mapping.insert(id_on_camera, id);
+      mapping.insert(id_on_camera, *id);
     });
-    println!("mapping {}", mapping.len());
-
-    if mapping.len() == 0 {
-      let list = vec![];
-      return Ok(json::object! {
-        data: JsonValue::Array(list),
-        total: 0,
-        "$skip": 0,
-      });
-    }
-
-    // expected events (short id, ?)
-    // let expected_events = HashMap::new();
-
-    // list of expected events ?
-
-    // data
-    // let date = if let Some(d) = self.date(&params)? {
-    //   // Utc.ymd(2022, 11, 11);
-    //   DateRange(d, d)
-    // } else {
-    //   if let Some(dr) = self.date_range(&params)? {
-    //     dr
-    //   } else {
-    //     return Err(Error::GeneralError("`date` or `dates` required".into()))
-    //   }
-    // };
-    let date = if let Some(date) = self.date("date", &params)? {
-      date
-    } else {
-      return Err(Error::GeneralError("`date` required".into()));
-    };
 
+    // night shifts and timezones move events out of range dates
     let mut events: Vec<(String, JsonValue)> = Vec::with_capacity(100_000);
-
-    for camera in self.ws.get(&oid).cameras() {
-      self.events(camera, date, &mut events);
+    let (first, last) = (from - Duration::days(1), till + Duration::days(2));
+    for camera in ws.cameras() {
+      for day in first.iter_days().take_while(|d| *d <= last) {
+        let date = Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap());
+        self.events(camera.clone(), date, &mut events);
+      }
     }
 
-    events.sort_by(|(a, _), (b, _)| a.cmp(b));
+    // check in or out (`true`) and author of correction of people
+    let mut checks: HashMap<ID, Vec<(DateTime<Utc>, bool, Option<String>)>> = HashMap::new();
+    for (_event_id, event) in events {
+      let event = &event["event"];
 
-    println!("events {}", events.len());
+      let pid = match mapping.get(&event["employeeNoString"].string()) {
+        Some(pid) => pid,
+        None => continue,
+      };
 
-    // person > state
+      let is_in = match event["event_type"].string().as_str() {
+        "in" => true,
+        "out" => false,
+        _ => continue,
+      };
 
-    for (_event_id, event) in events {
-      let event = &event["event"];
-      let event_type = event["event_type"].string();
-
-      let short_id = event["employeeNoString"].string();
-
-      if let Some(&pid) = mapping.get(&short_id) {
-        let time = event["time"].string();
-        let dt: DateTime<Utc> = match DateTime::parse_from_rfc3339(&time) {
-          Ok(dt) => dt.into(),
-          Err(_) => continue,
-        };
-
-        let status = statuses.entry(pid.clone()).or_insert(State::default());
-        if let Some(current) = status.intervals.last_mut() {
-          match event_type.as_str() {
-            "in" => {
-              if let Some(last) = current.last_from {
-                if dt.sub(last) > chrono::Duration::minutes(1) {
-                  status.intervals.push(Interval {
-                    from: Some(dt),
-                    last_from: Some(dt),
-                    till: None,
-                    last_till: None,
-                  });
-                } else {
-                  current.last_from = Some(dt)
-                }
-              } else {
-                status.intervals.push(Interval {
-                  from: Some(dt),
-                  last_from: Some(dt),
-                  till: None,
-                  last_till: None,
-                });
-              }
-            },
-            "out" => {
-              if current.till.is_none() {
-                current.till = Some(dt);
-                current.last_till = Some(dt);
-              } else {
-                if let Some(last_till) = current.last_till {
-                  // println!("last till {dt} - {last_till} = {}", dt.sub(last_till));
-                  if dt.sub(last_till) > chrono::Duration::minutes(1) {
-                    status.intervals.push(Interval {
-                      from: None,
-                      last_from: None,
-                      till: Some(dt),
-                      last_till: Some(dt),
-                    });
-                  } else {
-                    current.last_till = Some(dt);
-                  }
-                } else {
-                  // can't be
-                  todo!()
-                }
-              }
-            },
-            _ => continue, // must not happen
-          }
-        } else {
-          match event_type.as_str() {
-            "in" => {
-              status.intervals.push(Interval {
-                from: Some(dt),
-                last_from: Some(dt),
-                till: None,
-                last_till: None,
-              });
-            },
-            "out" => {
-              status.intervals.push(Interval {
-                from: None,
-                last_from: None,
-                till: Some(dt),
-                last_till: Some(dt),
-              });
-            },
-            _ => continue, // must not happen
-          }
-        }
-      }
+      let dt: DateTime<Utc> = match DateTime::parse_from_rfc3339(&event["time"].string()) {
+        Ok(dt) => dt.into(),
+        Err(_) => continue,
+      };
+
+      checks.entry(*pid).or_default().push((dt, is_in, None));
     }
 
-    println!("statuses {}", statuses.len());
+    let mut leaves: HashMap<ID, Vec<Leave>> = HashMap::new();
+    for adjustment in ws.adjustments() {
+      let data = adjustment.json();
+      let pid = match crate::services::string_to_id(data["person"].string()) {
+        Ok(pid) => pid,
+        Err(_) => continue,
+      };
+      match Adjustment::from_json(&data) {
+        Ok(Adjustment::Correction(is_in, dt)) => {
+          checks.entry(pid).or_default().push((dt, is_in, Some(data["author"].string())))
+        },
+        Ok(Adjustment::Leave(leave)) => leaves.entry(pid).or_default().push(leave),
+        Err(e) => log::warn!("adjustment {}: {e}", adjustment.id.to_base64()),
+      }
+    }
 
-    let mut list = vec![];
+    let mut intervals: HashMap<ID, Vec<Interval>> = HashMap::new();
+    for (pid, mut checks) in checks {
+      checks.sort_by_key(|(dt, ..)| *dt);
 
-    for (pid, state) in statuses.into_iter() {
-      let intervals = state.intervals.into_iter().map(|o| o.to_json()).collect::<Vec<_>>();
+      let intervals = intervals.entry(pid).or_default();
+      for (dt, is_in, correction) in checks {
+        attendance::register(intervals, is_in, dt, correction.as_deref());
+      }
+    }
 
-      let person = self.ws.get(&oid).person(&pid).load()?;
+    let mut shifts: HashMap<String, Option<Shift>> = HashMap::new();
+    let mut departments: HashMap<String, (String, usize, Totals)> = HashMap::new();
+
+    let mut list = Vec::with_capacity(people.len());
+    for (pid, person) in people {
+      let shift_id = person["shift"].string();
+      let shift = shifts
+        .entry(shift_id.clone())
+        .or_insert_with(|| {
+          let id = crate::services::string_to_id(shift_id.clone()).ok()?;
+          let data = ws.shift(id).load().ok()?;
+          match Shift::from_json(&data) {
+            Ok(shift) => Some(shift),
+            Err(e) => {
+              log::warn!("shift {shift_id}: {e}");
+              None
+            },
+          }
+        })
+        .as_ref();
+
+      let person_intervals = intervals.remove(&pid).unwrap_or_default();
+      let person_leaves = leaves.remove(&pid).unwrap_or_default();
+      let days = attendance::days(shift, &person_intervals, &person_leaves, from, till);
+      let totals = Totals::of(&days);
+
+      let division = person["division"].string();
+      let department = departments.entry(division.clone()).or_insert_with(|| {
+        let name = crate::services::string_to_id(division.clone())
+          .ok()
+          .and_then(|id| ws.department(id).load().ok())
+          .map(|data| data["name"].string())
+          .filter(|name| !name.is_empty())
+          .unwrap_or_else(|| division.clone());
+        (name, 0, Totals::default())
+      });
+      department.1 += 1;
+      department.2.add(&totals);
 
-      let id = format!("{}_{}_{}", oid.to_base64(), pid.to_base64(), date.to_string());
+      let id = format!("{}_{}_{}_{}", oid.to_base64(), pid.to_base64(), from, till);
 
-      list.push(json::object! {
+      let mut item = json::object! {
         _id: id,
         person: person,
-        intervals: JsonValue::Array(intervals)
-      });
+        department: json::object! { _id: division, name: department.0.clone() },
+        shift: if shift.is_some() { JsonValue::String(shift_id) } else { JsonValue::Null },
+        days: JsonValue::Array(days.iter().map(|day| day.to_json()).collect()),
+        totals: totals.to_json(),
+      };
+      if single {
+        item["_id"] = format!("{}_{}_{}", oid.to_base64(), pid.to_base64(), date).into();
+        item["intervals"] = item["days"][0]["intervals"].clone();
+      }
+      list.push(item);
     }
 
-    // let list = list.into_iter().skip(skip).take(total).map(|o| o.json()).collect();
+    list.sort_by(|a, b| a["person"]["name"].string().cmp(&b["person"]["name"].string()));
+
+    let mut departments: Vec<_> = departments.into_iter().collect();
+    departments.sort_by(|(_, (a, ..)), (_, (b, ..))| a.cmp(b));
+    let departments = departments
+      .into_iter()
+      .map(|(id, (name, people, totals))| {
+        let mut data = totals.to_json();
+        data["department"] = json::object! { _id: id, name: name };
+        data["people"] = people.into();
+        data
+      })
+      .collect();
+
     let total = list.len();
+    let list = list.into_iter().skip(skip).take(limit).collect();
 
     Ok(json::object! {
       data: JsonValue::Array(list),
       total: total,
-      "$skip": 0, // skip,
+      "$skip": skip,
+      departments: JsonValue::Array(departments),
     })
   }
 
@@ -306,3 +339,112 @@ impl Service for AttendanceReport {
     Err(Error::NotImplemented)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::hr::services::departments::Departments;
+  use crate::hr::services::shifts::Shifts;
+  use crate::services::People;
+  use crate::warehouse::test_util::init;
+  use service::Services;
+
+  #[actix_web::test]
+  async fn test_attendance_report_range() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(People::new(app.clone()));
+    app.register(Departments::new(app.clone(), app.wss.clone()));
+    app.register(Shifts::new(app.clone(), app.wss.clone()));
+    app.register(AttendanceReport::new(app.clone(), app.wss.clone()));
+
+    let oid = ID::random();
+
+    let create = |service: &str, data: JsonValue| -> String {
+      let mut data = data;
+      data["oid"] = oid.to_base64().into();
+      app.service(service).create(Context::local(), data, JsonValue::Null).unwrap()["_id"].string()
+    };
+
+    let security = create("departments", json::object! { name: "Security" });
+    let night = create(
+      "shifts",
+      json::object! {
+        name: "night", start: "22:00", end: "06:00", "break": 30, grace: 5,
+        days: [1, 2, 3, 4, 5, 6, 7]
+      },
+    );
+    let day =
+      create("shifts", json::object! { name: "day", start: "09:00", end: "18:00", "break": 60 });
+
+    create(
+      "people",
+      json::object! {
+        name: "Nina", employeeNoString: "7", division: security.clone(), shift: night
+      },
+    );
+    create("people", json::object! { name: "Dan", division: security.clone(), shift: day });
+
+    let camera = app.wss.get(&oid).camera(&ID::random());
+    for (status, time) in [
+      ("checkIn", "2022-08-12T21:55:00Z"),
+      ("checkOut", "2022-08-13T06:10:00Z"),
+      ("checkIn", "2022-08-13T22:20:00Z"),
+      ("checkOut", "2022-08-14T06:00:00Z"),
+    ] {
+      let dt: DateTime<Utc> = DateTime::parse_from_rfc3339(time).unwrap().into();
+      let id = dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+      let event = json::object! {
+        event: { major: 5, minor: 75, time: time, employeeNoString: "7", attendanceStatus: status }
+      };
+      camera.event(&id, &dt).create().unwrap().save(event.dump()).unwrap();
+    }
+
+    let report = app
+      .service("attendance-report")
+      .find(
+        Context::local(),
+        json::object! { oid: oid.to_base64(), dates: { from: "2022-08-12", till: "2022-08-13" } },
+      )
+      .unwrap();
+    assert_eq!(report["total"], 2);
+
+    let person = |name: &str| -> JsonValue {
+      report["data"]
+        .members()
+        .find(|item| item["person"]["name"] == name)
+        .unwrap()
+        .clone()
+    };
+
+    // night shift is reported at day it starts
+    let nina = person("Nina");
+    assert_eq!(nina["department"]["name"], "Security");
+    assert_eq!(nina["days"].len(), 2);
+    assert_eq!(nina["days"][0]["intervals"].len(), 1);
+    assert_eq!(nina["days"][0]["intervals"][0]["till"], "2022-08-13T06:10:00.000Z");
+    assert_eq!(nina["days"][0]["worked"], 465);
+    assert_eq!(nina["days"][0]["overtime"], 15);
+    assert_eq!(nina["days"][1]["late"], 20);
+    assert_eq!(nina["days"][1]["worked"], 430);
+    assert_eq!(nina["totals"]["worked"], 895);
+    assert_eq!(nina["totals"]["present"], 2);
+
+    // absent at Friday, Saturday is day off
+    let dan = person("Dan");
+    assert_eq!(dan["days"][0]["absent"], true);
+    assert_eq!(dan["days"][1]["scheduled"], JsonValue::Null);
+    assert_eq!(dan["days"][1]["absent"], false);
+    assert_eq!(dan["totals"]["absences"], 1);
+
+    assert_eq!(report["departments"].len(), 1);
+    assert_eq!(report["departments"][0]["department"]["name"], "Security");
+    assert_eq!(report["departments"][0]["people"], 2);
+    assert_eq!(report["departments"][0]["worked"], 895);
+    assert_eq!(report["departments"][0]["absences"], 1);
+
+    tmp_dir.close().unwrap();
+  }
+}
@@ -2,6 +2,7 @@ use json::JsonValue;
 use std::sync::Arc;
 
 use crate::services::{Data, Params};
+use crate::storage::roles::OWNER;
 use crate::{commutator::Application, storage::Workspaces};
 use service::error::Error;
 use service::{Context, Service};
@@ -23,11 +24,17 @@ impl Service for Companies {
     &self.name
   }
 
-  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+  fn find(&self, ctx: Context, params: Params) -> crate::services::Result {
     let _limit = self.limit(&params);
     let skip = self.skip(&params);
 
-    let list = self.app.wss.list()?;
+    let account = ctx.account.read().unwrap().clone();
+
+    let mut list = self.app.wss.list()?;
+    // local context see all workspaces
+    if !crate::access::is_guest(&account) {
+      list.retain(|ws| ws.members().is_member(&account.id));
+    }
     let total = list.len();
 
     let list = list.into_iter().skip(skip).take(total).map(|o| o.json()).collect();
@@ -44,7 +51,7 @@ impl Service for Companies {
     self.app.wss.get(&id).load()
   }
 
-  fn create(&self, _ctx: Context, data: Data, _params: Params) -> crate::services::Result {
+  fn create(&self, ctx: Context, data: Data, _params: Params) -> crate::services::Result {
     if !data.is_object() {
       Err(Error::GeneralError("only object allowed".into()))
     } else {
@@ -53,7 +60,14 @@ impl Service for Companies {
       let mut obj = data.clone();
       obj["_id"] = JsonValue::String(id.to_base64());
 
-      self.app.wss.create(id)?.save(obj.dump())?;
+      let ws = self.app.wss.create(id)?;
+      ws.save(obj.dump())?;
+
+      // creator get access to new workspace
+      let account = ctx.account.read().unwrap().clone();
+      if !crate::access::is_guest(&account) {
+        ws.members().add(&account, &[OWNER.to_string()])?;
+      }
 
       Ok(obj)
     }
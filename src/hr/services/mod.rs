@@ -1,3 +1,4 @@
+pub(crate) mod attendance_adjustments;
 pub(crate) mod attendance_report;
 pub(crate) mod companies;
 pub(crate) mod departments;
@@ -0,0 +1,382 @@
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::{Datelike, NaiveDate};
+use json::JsonValue;
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+
+use crate::commutator::Application;
+use service::error::Error;
+use service::utils::json::JsonParams;
+use service::{Context, Services};
+
+// page size of `attendance-report`, it is limited by service
+const PAGE: usize = 100;
+
+const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// Code of day at timesheet grid: `V` vacation, `S` sick, `B` business trip, `O` other leave,
+/// `A` absent, `L` late, `P` present, empty for day off.
+pub(crate) fn code(day: &JsonValue) -> &'static str {
+  match day["leave"].as_str() {
+    Some("vacation") => return "V",
+    Some("sick") => return "S",
+    Some("business_trip") => return "B",
+    Some(_) => return "O",
+    None => {},
+  }
+
+  if day["absent"].as_bool().unwrap_or(false) {
+    "A"
+  } else if day["intervals"].is_empty() {
+    ""
+  } else if day["late"].as_i64().unwrap_or(0) > 0 {
+    "L"
+  } else {
+    "P"
+  }
+}
+
+fn hours(minutes: &JsonValue) -> f64 {
+  (minutes.as_f64().unwrap_or(0.0) / 60.0 * 100.0).round() / 100.0
+}
+
+// spreadsheet treat text starting with these as formula
+fn text_cell(value: &str) -> String {
+  if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+    format!("'{value}")
+  } else {
+    value.to_string()
+  }
+}
+
+struct Row {
+  name: String,
+  department: String,
+  codes: Vec<&'static str>,
+  totals: JsonValue,
+}
+
+/// Monthly timesheet of organization: person × day grid of codes and summary with totals of
+/// people and departments.
+pub(crate) struct Timesheet {
+  days: Vec<NaiveDate>,
+  rows: Vec<Row>,
+  departments: Vec<JsonValue>,
+}
+
+impl Timesheet {
+  /// first and last dates of `YYYY-MM`
+  pub(crate) fn month(month: &str) -> Result<(NaiveDate, NaiveDate), Error> {
+    let from = NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+      .map_err(|_| Error::GeneralError("`month` must be YYYY-MM".into()))?;
+
+    let next = if from.month() == 12 {
+      NaiveDate::from_ymd_opt(from.year() + 1, 1, 1)
+    } else {
+      NaiveDate::from_ymd_opt(from.year(), from.month() + 1, 1)
+    };
+    let till = next
+      .and_then(|d| d.pred_opt())
+      .ok_or_else(|| Error::GeneralError("invalid month".into()))?;
+
+    Ok((from, till))
+  }
+
+  /// collect all pages of `attendance-report` for `oid` and `division` (optional)
+  pub(crate) fn collect(
+    app: &Application,
+    ctx: Context,
+    oid: &str,
+    division: &str,
+    from: NaiveDate,
+    till: NaiveDate,
+  ) -> Result<Self, Error> {
+    let days: Vec<NaiveDate> = from.iter_days().take_while(|d| *d <= till).collect();
+
+    let mut rows = vec![];
+    let mut departments = vec![];
+    loop {
+      let params = json::object! {
+        oid: oid,
+        division: division,
+        dates: { from: from.to_string(), till: till.to_string() },
+        "$limit": PAGE,
+        "$skip": rows.len(),
+      };
+      let page = app.service("attendance-report").find(ctx.clone(), params)?;
+
+      if departments.is_empty() {
+        departments = page["departments"].members().cloned().collect();
+      }
+
+      let before = rows.len();
+      for item in page["data"].members() {
+        rows.push(Row {
+          name: item["person"]["name"].string(),
+          department: item["department"]["name"].string(),
+          codes: item["days"].members().map(code).collect(),
+          totals: item["totals"].clone(),
+        });
+      }
+
+      if rows.len() == before || rows.len() >= page["total"].as_usize().unwrap_or(0) {
+        break;
+      }
+    }
+
+    Ok(Timesheet { days, rows, departments })
+  }
+
+  fn grid(&self) -> Vec<Vec<String>> {
+    let mut header = vec!["Person".to_string(), "Department".to_string()];
+    header.extend(self.days.iter().map(|d| d.day().to_string()));
+    header.extend(["Present".to_string(), "Absences".to_string(), "Worked, h".to_string()]);
+
+    let mut lines = vec![header];
+    for row in &self.rows {
+      let mut line = vec![row.name.clone(), row.department.clone()];
+      line.extend(row.codes.iter().map(|c| c.to_string()));
+      line.push(row.totals["present"].as_i64().unwrap_or(0).to_string());
+      line.push(row.totals["absences"].as_i64().unwrap_or(0).to_string());
+      line.push(hours(&row.totals["worked"]).to_string());
+      lines.push(line);
+    }
+    lines
+  }
+
+  fn summary(&self) -> Vec<Vec<String>> {
+    fn line(name: String, department: String, people: String, totals: &JsonValue) -> Vec<String> {
+      vec![
+        name,
+        department,
+        people,
+        totals["present"].as_i64().map(|n| n.to_string()).unwrap_or_default(),
+        totals["absences"].as_i64().unwrap_or(0).to_string(),
+        totals["leaves"].as_i64().unwrap_or(0).to_string(),
+        hours(&totals["worked"]).to_string(),
+        totals["late"].as_i64().unwrap_or(0).to_string(),
+        totals["early_leave"].as_i64().unwrap_or(0).to_string(),
+        hours(&totals["overtime"]).to_string(),
+      ]
+    }
+
+    let mut lines = vec![[
+      "Person",
+      "Department",
+      "People",
+      "Present",
+      "Absences",
+      "Leave, days",
+      "Worked, h",
+      "Late, min",
+      "Early leave, min",
+      "Overtime, h",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()];
+
+    for row in &self.rows {
+      lines.push(line(row.name.clone(), row.department.clone(), "".into(), &row.totals));
+    }
+    for department in &self.departments {
+      let name = department["department"]["name"].string();
+      let people = department["people"].as_usize().unwrap_or(0).to_string();
+      lines.push(line("".into(), name, people, department));
+    }
+    lines
+  }
+
+  /// `timesheet` grid or `summary` sheet as csv
+  pub(crate) fn csv(&self, sheet: &str) -> Result<Vec<u8>, Error> {
+    let lines = match sheet {
+      "" | "timesheet" => self.grid(),
+      "summary" => self.summary(),
+      _ => return Err(Error::GeneralError(format!("unknown sheet `{sheet}`"))),
+    };
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for mut line in lines {
+      // person and department are entered by users
+      for value in line.iter_mut().take(2) {
+        *value = text_cell(value);
+      }
+      writer.write_record(&line).map_err(|e| Error::IOError(e.to_string()))?;
+    }
+    writer.into_inner().map_err(|e| Error::IOError(e.to_string()))
+  }
+
+  /// workbook with `Timesheet` and `Summary` sheets
+  pub(crate) fn xlsx(&self) -> Result<Vec<u8>, Error> {
+    fn write(sheet: &mut Worksheet, lines: Vec<Vec<String>>) -> Result<(), XlsxError> {
+      let bold = Format::new().set_bold();
+      for (row, line) in lines.iter().enumerate() {
+        for (col, value) in line.iter().enumerate() {
+          let (row, col) = (row as u32, col as u16);
+          if row == 0 {
+            sheet.write_string_with_format(row, col, value, &bold)?;
+          } else if let (true, Ok(number)) = (col > 1, value.parse::<f64>()) {
+            sheet.write_number(row, col, number)?;
+          } else {
+            sheet.write_string(row, col, value)?;
+          }
+        }
+      }
+      sheet.set_column_width(0, 30.0)?;
+      sheet.set_column_width(1, 20.0)?;
+      Ok(())
+    }
+
+    let build = || -> Result<Vec<u8>, XlsxError> {
+      let mut workbook = Workbook::new();
+      write(workbook.add_worksheet().set_name("Timesheet")?, self.grid())?;
+      write(workbook.add_worksheet().set_name("Summary")?, self.summary())?;
+      workbook.save_to_buffer()
+    };
+
+    build().map_err(|e| Error::IOError(e.to_string()))
+  }
+}
+
+/// `GET /v1/attendance/timesheet?oid=..&month=2022-08&division=..&format=xlsx`
+///
+/// `format` is `csv` (default) or `xlsx`, csv has one sheet selected by `sheet` (`timesheet`
+/// or `summary`).
+#[get("/attendance/timesheet")]
+pub(crate) async fn timesheet(req: HttpRequest, app: web::Data<Application>) -> HttpResponse {
+  let params = crate::api::query_to_params(req.query_string());
+  let ctx = Context::rest(req.head().clone());
+
+  let result = web::block(move || {
+    crate::access::authorize(&app, &ctx, "attendance-report", "find", None, None, &params)?;
+
+    let month = params["month"].string();
+    let (from, till) = Timesheet::month(&month)?;
+    let timesheet = Timesheet::collect(
+      &app,
+      ctx,
+      &params["oid"].string(),
+      &params["division"].string(),
+      from,
+      till,
+    )?;
+
+    match params["format"].as_str().unwrap_or("csv") {
+      "csv" => {
+        let sheet = params["sheet"].string();
+        let name =
+          format!("timesheet-{month}{}.csv", if sheet == "summary" { "-summary" } else { "" });
+        Ok((timesheet.csv(&sheet)?, "text/csv; charset=utf-8", name))
+      },
+      "xlsx" => Ok((timesheet.xlsx()?, XLSX, format!("timesheet-{month}.xlsx"))),
+      format => Err(Error::GeneralError(format!("unknown format `{format}`"))),
+    }
+  })
+  .await;
+
+  match result {
+    Ok(Ok((body, content_type, name))) => HttpResponse::Ok()
+      .append_header((header::CONTENT_TYPE, content_type))
+      .append_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{name}\"")))
+      .body(body),
+    Ok(Err(error)) => crate::api::error_response(error),
+    Err(error) => crate::api::error_response(Error::GeneralError(error.to_string())),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_codes() {
+    let day = |data: JsonValue| code(&data);
+
+    assert_eq!(day(json::object! { absent: true, intervals: [] }), "A");
+    assert_eq!(day(json::object! { absent: false, intervals: [] }), "");
+    assert_eq!(day(json::object! { late: 0, intervals: [{}] }), "P");
+    assert_eq!(day(json::object! { late: 12, intervals: [{}] }), "L");
+    assert_eq!(day(json::object! { absent: false, leave: "vacation", intervals: [] }), "V");
+    assert_eq!(day(json::object! { leave: "business_trip", intervals: [] }), "B");
+
+    let (from, till) = Timesheet::month("2022-12").unwrap();
+    assert_eq!((from.to_string(), till.to_string()), ("2022-12-01".into(), "2022-12-31".into()));
+    assert_eq!(Timesheet::month("2024-02").unwrap().1.day(), 29);
+    assert!(Timesheet::month("2024").is_err());
+
+    assert_eq!(text_cell("=HYPERLINK(\"http://x\")"), "'=HYPERLINK(\"http://x\")");
+    assert_eq!(text_cell("@SUM(A1)"), "'@SUM(A1)");
+    assert_eq!(text_cell("-1+2"), "'-1+2");
+    assert_eq!(text_cell("Alice"), "Alice");
+  }
+
+  #[actix_web::test]
+  async fn test_timesheet_export() {
+    use crate::hr::services::attendance_report::AttendanceReport;
+    use crate::hr::services::shifts::Shifts;
+    use crate::services::People;
+    use crate::storage::Workspaces;
+    use crate::warehouse::test_util::init;
+    use chrono::{DateTime, Utc};
+    use std::sync::Arc;
+    use values::ID;
+
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(People::new(app.clone()));
+    app.register(Shifts::new(app.clone(), app.wss.clone()));
+    app.register(AttendanceReport::new(app.clone(), app.wss.clone()));
+
+    let oid = ID::random();
+
+    let create = |service: &str, mut data: JsonValue| -> String {
+      data["oid"] = oid.to_base64().into();
+      app.service(service).create(Context::local(), data, JsonValue::Null).unwrap()["_id"].string()
+    };
+
+    let shift = create("shifts", json::object! { start: "09:00", end: "18:00", "break": 60 });
+    create("people", json::object! { name: "Alice", employeeNoString: "1", shift: shift });
+
+    // on time at Monday, late at Tuesday
+    let camera = app.wss.get(&oid).camera(&ID::random());
+    for (status, time) in [
+      ("checkIn", "2022-08-01T09:00:00Z"),
+      ("checkOut", "2022-08-01T18:00:00Z"),
+      ("checkIn", "2022-08-02T09:30:00Z"),
+      ("checkOut", "2022-08-02T18:00:00Z"),
+    ] {
+      let dt: DateTime<Utc> = DateTime::parse_from_rfc3339(time).unwrap().into();
+      let id = dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+      let event = json::object! {
+        event: { major: 5, minor: 75, time: time, employeeNoString: "1", attendanceStatus: status }
+      };
+      camera.event(&id, &dt).create().unwrap().save(event.dump()).unwrap();
+    }
+
+    let (from, till) = Timesheet::month("2022-08").unwrap();
+    let timesheet =
+      Timesheet::collect(&app, Context::local(), &oid.to_base64(), "", from, till).unwrap();
+
+    let csv = String::from_utf8(timesheet.csv("timesheet").unwrap()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("Person,Department,1,2,3,"));
+    assert!(lines[0].ends_with(",31,Present,Absences,\"Worked, h\""));
+
+    // 2022-08-06 and 07 are weekend, 21 working days without two present
+    let cells: Vec<&str> = lines[1].split(',').collect();
+    assert_eq!(cells[..9], ["Alice", "", "P", "L", "A", "A", "A", "", ""]);
+    assert_eq!(cells[33..], ["2", "21", "15.5"]);
+
+    let summary = String::from_utf8(timesheet.csv("summary").unwrap()).unwrap();
+    assert!(summary.lines().nth(1).unwrap().starts_with("Alice,,,2,21,0,15.5,30,0,0"));
+
+    // xlsx is zip archive
+    assert!(timesheet.xlsx().unwrap().starts_with(b"PK"));
+    assert!(timesheet.csv("other").is_err());
+
+    tmp_dir.close().unwrap();
+  }
+}
@@ -1,9 +1,10 @@
 use crate::commutator::Application;
 use crate::services::{Data, Params};
 use chrono::{DateTime, Utc};
+use json::JsonValue;
 use service::error::Error;
 use service::utils::json::JsonParams;
-use service::{Context, Service};
+use service::{Context, Schema, Service};
 use std::sync::Arc;
 use store::batch::Batch;
 use store::elements::ToJson;
@@ -24,6 +25,42 @@ impl Service for Inventory {
     &self.path
   }
 
+  fn commands(&self) -> &[&str] {
+    &["find"]
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    let dates = json::object! {
+      type: "object",
+      properties: {
+        from: { type: "string", format: "date" },
+        till: { type: "string", format: "date" },
+      },
+    };
+    Some(Schema::new(
+      json::object! {
+        type: "object",
+        properties: {
+          storage: { type: "string", format: "uuid" },
+          dates: dates.clone(),
+          filter: {
+            type: "object",
+            description: "report of goods batch at storage",
+            properties: {
+              storage: { type: "string", format: "uuid" },
+              goods: { type: "string", format: "uuid" },
+              batch_id: { type: "string", format: "uuid" },
+              batch_date: { type: "string", format: "date-time" },
+              dates: dates,
+            },
+          },
+        },
+      },
+      JsonValue::Null,
+      json::object! { type: "object", description: "movement report of storage or goods" },
+    ))
+  }
+
   fn find(&self, ctx: Context, params: Params) -> crate::services::Result {
     let _oid = crate::services::oid(&params)?;
 
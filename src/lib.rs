@@ -1,4 +1,6 @@
+mod access;
 mod auth;
+mod backup;
 pub mod commutator;
 mod file;
 pub mod inventory;
@@ -12,7 +14,11 @@ mod ws;
 pub mod animo;
 pub mod api;
 mod hr;
+mod limits;
+mod mail;
+mod openapi;
 pub mod memories;
+mod reindex;
 mod text_search;
 pub mod use_cases;
 pub mod warehouse;
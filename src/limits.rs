@@ -0,0 +1,140 @@
+use json::JsonValue;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::settings::LimitsConfig;
+use service::error::Error;
+use service::{Account, Context};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+// counters are dropped by expire when there are more of them
+const CLEANUP_AT: usize = 10_000;
+
+/// Rate and size limits of requests. Rates are counted in fixed one minute windows
+/// by ip of request and by account (email for login attempts).
+pub(crate) struct Limits {
+  config: LimitsConfig,
+  counters: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl Limits {
+  pub(crate) fn new(config: &LimitsConfig) -> Self {
+    Limits { config: config.clone(), counters: Mutex::new(HashMap::new()) }
+  }
+
+  pub(crate) fn picture_size(&self) -> usize {
+    self.config.picture_size
+  }
+
+  pub(crate) fn message_size(&self) -> usize {
+    self.config.message_size
+  }
+
+  /// `authentication` create attempt
+  pub(crate) fn login(&self, ctx: &Context, data: Option<&JsonValue>) -> Result<(), Error> {
+    let max = self.config.login_per_minute;
+    let email = data.and_then(|data| data["email"].as_str()).map(|e| e.trim().to_lowercase());
+
+    let mut keys = Vec::new();
+    if let Some(ip) = ip_of(ctx) {
+      keys.push(format!("login/ip/{ip}"));
+    }
+    if let Some(email) = email {
+      keys.push(format!("login/email/{email}"));
+    }
+
+    if self.hit(&keys, max) {
+      Ok(())
+    } else {
+      Err(Error::TooManyRequests("too many login attempts, try again later".into()))
+    }
+  }
+
+  /// service command of authenticated account
+  pub(crate) fn command(&self, ctx: &Context, account: &Account) -> Result<(), Error> {
+    let max = self.config.commands_per_minute;
+
+    let mut keys = vec![format!("commands/account/{}", account.id.to_base64())];
+    if let Some(ip) = ip_of(ctx) {
+      keys.push(format!("commands/ip/{ip}"));
+    }
+
+    if self.hit(&keys, max) {
+      Ok(())
+    } else {
+      Err(Error::TooManyRequests("too many requests, try again later".into()))
+    }
+  }
+
+  // count hit for every key, false if any of them is over the limit
+  fn hit(&self, keys: &[String], max: u32) -> bool {
+    let now = Instant::now();
+    let mut counters = self.counters.lock().unwrap();
+
+    if counters.len() > CLEANUP_AT {
+      counters.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+    }
+
+    let mut allowed = true;
+    for key in keys {
+      let (start, count) = counters.entry(key.clone()).or_insert((now, 0));
+      if now.duration_since(*start) >= WINDOW {
+        *start = now;
+        *count = 0;
+      }
+      *count += 1;
+      if *count > max {
+        allowed = false;
+      }
+    }
+    allowed
+  }
+}
+
+// local context has no request and isn't limited by ip
+fn ip_of(ctx: &Context) -> Option<String> {
+  ctx
+    .request
+    .as_ref()
+    .and_then(|request| request.peer_addr)
+    .map(|addr| addr.ip().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use values::ID;
+
+  #[test]
+  fn test_limits() {
+    let config = LimitsConfig { login_per_minute: 2, commands_per_minute: 3, ..Default::default() };
+    let limits = Limits::new(&config);
+
+    let ctx = Context::local();
+    let data = json::object! { strategy: "local", email: "Limit@test.com", password: "1" };
+
+    assert!(limits.login(&ctx, Some(&data)).is_ok());
+    assert!(limits.login(&ctx, Some(&data)).is_ok());
+    match limits.login(&ctx, Some(&data)) {
+      Err(Error::TooManyRequests(_)) => {},
+      result => panic!("unexpected {result:?}"),
+    }
+
+    // email is counted case insensitive, other one isn't limited
+    let data = json::object! { strategy: "local", email: "limit@test.com " };
+    assert!(limits.login(&ctx, Some(&data)).is_err());
+    let data = json::object! { strategy: "local", email: "other@test.com" };
+    assert!(limits.login(&ctx, Some(&data)).is_ok());
+
+    let account = Account { id: ID::from("limit@test.com"), email: "limit@test.com".into() };
+    for _ in 0..3 {
+      assert!(limits.command(&ctx, &account).is_ok());
+    }
+    assert!(limits.command(&ctx, &account).is_err());
+
+    let other = Account { id: ID::from("other@test.com"), email: "other@test.com".into() };
+    assert!(limits.command(&ctx, &other).is_ok());
+  }
+}
@@ -0,0 +1,137 @@
+use chrono::Utc;
+use json::JsonValue;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::settings::MailConfig;
+use crate::storage::save;
+use service::error::Error;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Mail {
+  pub(crate) to: String,
+  pub(crate) subject: String,
+  pub(crate) body: String,
+}
+
+impl Mail {
+  fn to_json(&self) -> JsonValue {
+    json::object! {
+      to: self.to.as_str(),
+      subject: self.subject.as_str(),
+      body: self.body.as_str(),
+      sent: service::utils::time::time_to_string(Utc::now()),
+    }
+  }
+}
+
+pub(crate) trait Mailer: Send + Sync {
+  fn send(&self, mail: Mail) -> Result<(), Error>;
+}
+
+/// transport by `mail.transport` setting: `smtp`, `file` or `log` (default)
+pub(crate) fn mailer(config: &MailConfig) -> Arc<dyn Mailer> {
+  match config.transport.as_str() {
+    "smtp" => Arc::new(SmtpMailer::new(config)),
+    "file" => Arc::new(FileMailer { folder: config.folder.clone() }),
+    _ => Arc::new(LogMailer {}),
+  }
+}
+
+pub(crate) struct SmtpMailer {
+  from: String,
+  host: String,
+  port: u16,
+  username: String,
+  password: String,
+}
+
+impl SmtpMailer {
+  fn new(config: &MailConfig) -> Self {
+    SmtpMailer {
+      from: config.from.clone(),
+      host: config.host.clone(),
+      port: config.port,
+      username: config.username.clone(),
+      password: config.password.clone(),
+    }
+  }
+}
+
+impl Mailer for SmtpMailer {
+  fn send(&self, mail: Mail) -> Result<(), Error> {
+    let message = Message::builder()
+      .from(
+        self
+          .from
+          .parse()
+          .map_err(|e| Error::GeneralError(format!("from address: {e}")))?,
+      )
+      .to(mail.to.parse().map_err(|e| Error::GeneralError(format!("to address: {e}")))?)
+      .subject(mail.subject)
+      .body(mail.body)
+      .map_err(|e| Error::GeneralError(e.to_string()))?;
+
+    let mut transport =
+      SmtpTransport::relay(&self.host).map_err(|e| Error::GeneralError(e.to_string()))?;
+    transport = transport.port(self.port);
+    if !self.username.is_empty() {
+      transport =
+        transport.credentials(Credentials::new(self.username.clone(), self.password.clone()));
+    }
+
+    transport
+      .build()
+      .send(&message)
+      .map(|_| ())
+      .map_err(|e| Error::GeneralError(e.to_string()))
+  }
+}
+
+/// keep mails as json files, for tests and local development
+pub(crate) struct FileMailer {
+  pub(crate) folder: PathBuf,
+}
+
+impl FileMailer {
+  /// mails sent to address, oldest first
+  pub(crate) fn sent(&self, to: &str) -> Result<Vec<JsonValue>, Error> {
+    let mut result = Vec::new();
+    if !self.folder.exists() {
+      return Ok(result);
+    }
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&self.folder)
+      .map_err(|e| Error::IOError(e.to_string()))?
+      .filter_map(|entry| entry.ok().map(|e| e.path()))
+      .filter(|path| path.extension().map(|e| e == "json").unwrap_or(false))
+      .collect();
+    paths.sort();
+
+    for path in paths {
+      let mail = crate::storage::load(&path)?;
+      if mail["to"] == to {
+        result.push(mail);
+      }
+    }
+    Ok(result)
+  }
+}
+
+impl Mailer for FileMailer {
+  fn send(&self, mail: Mail) -> Result<(), Error> {
+    let name = format!("{}-{}.json", Utc::now().timestamp_nanos(), uuid::Uuid::new_v4());
+    save(&self.folder.join(name), mail.to_json().dump())
+  }
+}
+
+pub(crate) struct LogMailer {}
+
+impl Mailer for LogMailer {
+  fn send(&self, mail: Mail) -> Result<(), Error> {
+    log::info!("mail to {}: {}\n{}", mail.to, mail.subject, mail.body);
+    Ok(())
+  }
+}
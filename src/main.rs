@@ -1,7 +1,6 @@
 use crate::commutator::{Application, Commutator};
 use actix::{Actor, Addr};
 use actix_cors::Cors;
-// use actix_ratelimit::{MemoryStore, MemoryStoreActor, RateLimiter};
 use actix_web::dev::ServiceRequest;
 use actix_web::http::header;
 use actix_web::{http, middleware, web, App, HttpServer};
@@ -22,7 +21,9 @@ use std::{io, thread};
 use structopt::StructOpt;
 use uuid::Uuid;
 
+mod access;
 mod auth;
+mod backup;
 mod commutator;
 mod file;
 mod inventory;
@@ -36,7 +37,11 @@ mod ws;
 mod animo;
 mod api;
 mod hr;
+mod limits;
+mod mail;
+mod openapi;
 mod memories;
+mod reindex;
 mod text_search;
 mod use_cases;
 pub mod warehouse;
@@ -49,11 +54,12 @@ use crate::animo::{Animo, Time, Topology};
 use crate::hik::error::Error;
 use crate::hik::services::actions::Actions;
 use crate::hik::services::{Cameras, Events};
+use crate::hr::services::attendance_adjustments::AttendanceAdjustments;
 use crate::hr::services::attendance_report::AttendanceReport;
 use crate::hr::services::companies::Companies;
 use crate::hr::services::departments::Departments;
 use crate::hr::services::shifts::Shifts;
-use crate::memories::MemoriesInFiles;
+use crate::memories::{Changes, MemoriesInFiles, References};
 use crate::services::People;
 use crate::settings::Settings;
 use crate::storage::organizations::Workspace;
@@ -78,49 +84,123 @@ struct Opt {
   /// Data folder
   #[structopt(short, long, default_value = "./data", parse(from_os_str))]
   data: PathBuf,
+
+  /// Workspace id for backup
+  #[structopt(short, long)]
+  workspace: Option<String>,
+
+  /// Archive file for backup and restore
+  #[structopt(short, long, parse(from_os_str))]
+  archive: Option<PathBuf>,
+
+  /// Context prefix for reindex, example: warehouse/receive
+  #[structopt(long)]
+  ctx: Option<String>,
+
+  /// Reindex documents created from date (YYYY-MM-DD)
+  #[structopt(long)]
+  from: Option<String>,
+
+  /// Reindex documents created till date (YYYY-MM-DD)
+  #[structopt(long)]
+  till: Option<String>,
+
+  /// Resume reindex job
+  #[structopt(long)]
+  job: Option<String>,
+
+  /// Account email to grant access to workspace
+  #[structopt(long)]
+  email: Option<String>,
 }
 
-async fn reindex(
-  settings: Arc<Settings>,
+async fn reindex(app: Application, jobs: reindex::Jobs, opt: &Opt) -> io::Result<()> {
+  let id = match &opt.job {
+    // resume from last checkpoint
+    Some(id) => id.clone(),
+    None => {
+      let scope = reindex::Scope::from_json(&json::object! {
+        oid: opt.workspace.clone(),
+        ctx: opt.ctx.clone(),
+        dates: { from: opt.from.clone(), till: opt.till.clone() },
+      })?;
+      jobs.create(scope)?["_id"].to_string()
+    },
+  };
+
+  println!("reindex job {id}");
+
+  let state = jobs.run(&app, &id)?;
+
+  for error in state["errors"].members() {
+    println!("{} {}: {}", error["workspace"], error["document"], error["error"]);
+  }
+  println!("{}: processed {}, failed {}", state["status"], state["processed"], state["failed"]);
+
+  Ok(())
+}
+
+async fn backup(
   app: Application,
-  com: Addr<Commutator>,
+  workspace: Option<String>,
+  archive: Option<PathBuf>,
 ) -> io::Result<()> {
-  let mut count = 0;
-  for ws in app.wss.list()? {
-    for doc in ws.clone().into_iter() {
-      // println!("{:?} {:?}", doc.id, doc.json().unwrap());
-      count += 1;
-
-      let ctx = &doc.mem.ctx;
-
-      let before = JsonValue::Null;
-      let mut after = doc.json().unwrap();
-
-      // inject uuid if missing
-      if after["_uuid"].is_null() {
-        let uuid = Uuid::new_v4().to_string();
-        after["_uuid"] = uuid.clone().into();
-
-        storage::memories::index_uuid(
-          &doc.mem.top_folder,
-          &doc.path.parent().unwrap().into(),
-          uuid.as_str(),
-        )?;
-      }
+  let wid = match workspace {
+    Some(wid) => services::string_to_id(wid)?,
+    None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "--workspace required")),
+  };
+  let archive = match archive {
+    Some(path) => path,
+    None => PathBuf::from(format!("{}.tar.gz", wid.to_base64())),
+  };
 
-      // delete batch from document if it exists
-      after.remove("batch");
+  backup::backup(&app, &wid, &archive)?;
 
-      text_search::handle_mutation(&app, ctx, &before, &after).unwrap();
+  println!("workspace {} saved to {}", wid.to_base64(), archive.to_string_lossy());
 
-      let after =
-        store::elements::receive_data(&app, ws.id.to_string().as_str(), after, ctx, before).unwrap();
+  Ok(())
+}
 
-      storage::save(&doc.path, after.dump())?;
-    }
+async fn restore(app: Application, jobs: reindex::Jobs, archive: Option<PathBuf>) -> io::Result<()> {
+  let archive = match archive {
+    Some(path) => path,
+    None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "--archive required")),
+  };
+
+  let (_, warehouse) = backup::contents(&archive)?;
+  backup::check_warehouse(&app, &warehouse)?;
+
+  let (wid, warehouse) = backup::restore(&app.wss, &archive)?;
+  println!("workspace {} restored from {}", wid.to_base64(), archive.to_string_lossy());
+
+  // rebuild warehouse, search index and references from restored documents
+  let job = jobs.create(reindex::Scope { oid: Some(wid), ..reindex::Scope::default() })?;
+  let state = jobs.run(&app, &job["_id"].to_string())?;
+  println!("reindex: processed {}, failed {}", state["processed"], state["failed"]);
+
+  if !backup::verify(&app, &wid, &warehouse)? {
+    println!("warning: warehouse operations differ from archived ones");
   }
 
-  println!("count {count}");
+  Ok(())
+}
+
+async fn member(
+  app: Application,
+  workspace: Option<String>,
+  email: Option<String>,
+) -> io::Result<()> {
+  let (wid, email) = match (workspace, email) {
+    (Some(wid), Some(email)) => (services::string_to_id(wid)?, email.trim().to_lowercase()),
+    _ => {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "--workspace and --email required"))
+    },
+  };
+
+  let account = service::Account { id: values::ID::from(email.as_str()), email };
+  app.wss.get(&wid).members().add(&account, &[storage::roles::OWNER.to_string()])?;
+
+  println!("{} has access to workspace {}", account.email, wid.to_base64());
 
   Ok(())
 }
@@ -132,6 +212,9 @@ async fn server(settings: Arc<Settings>, app: Application, com: Addr<Commutator>
 
   log::info!("starting up {address}:{port} for {domain}");
 
+  // long-polling sessions are shared by workers
+  let polling = ws::Polling::default();
+
   HttpServer::new(move || {
     // let auth = HttpAuthentication::bearer(auth::validator);
 
@@ -149,17 +232,19 @@ async fn server(settings: Arc<Settings>, app: Application, com: Addr<Commutator>
       .max_age(3600);
 
     App::new()
-      // .wrap(
-      //   RateLimiter::new(MemoryStoreActor::from(store.clone()).start())
-      //     .with_interval(Duration::from_secs(60))
-      //     .with_max_requests(100),
-      // )
+      // rate limits are checked at `access::authorize`, see `limits::Limits`
       .wrap(cors)
       .app_data(web::Data::new(app.clone()))
       .app_data(web::Data::new(com.clone()))
+      .app_data(web::Data::new(polling.clone()))
       .wrap(middleware::Logger::default())
       // .wrap(auth)
-      .service(web::scope("/socket.io").service(ws::start_connection))
+      .service(
+        web::scope("/socket.io")
+          .app_data(web::PayloadConfig::new(app.limits.message_size()))
+          .service(ws::start_connection)
+          .service(ws::polling_post),
+      )
       .service(web::scope("/"))
       .service(
         web::scope("/v1")
@@ -167,7 +252,16 @@ async fn server(settings: Arc<Settings>, app: Application, com: Addr<Commutator>
           .service(file::get_file)
           .service(file::post_file)
           .service(api::memory_query)
-          .service(api::memory_modify),
+          .service(api::memory_modify)
+          .service(openapi::openapi)
+          .service(hr::timesheet::timesheet)
+          // generic routes last, after specific ones
+          .service(api::service_find)
+          .service(api::service_get)
+          .service(api::service_create)
+          .service(api::service_update)
+          .service(api::service_patch)
+          .service(api::service_remove),
       )
       // .route("/ws/", web::get().to(websocket))
       .default_service(web::route().to(api::not_implemented))
@@ -183,14 +277,19 @@ async fn startup() -> std::io::Result<()> {
 
   let opt = Opt::from_args();
 
-  let settings = std::sync::Arc::new(Settings::new().unwrap());
+  let mut settings = Settings::new().unwrap();
+  if opt.mode == "restore" {
+    // restore into fresh data folder, databases of configured one stay untouched
+    settings.database = settings::Database::at(&opt.data);
+  }
+  let settings = std::sync::Arc::new(settings);
   println!("db starting up");
   let db: AnimoDB = Memory::init(settings.database.memory.clone()).unwrap();
   println!("db started up");
 
   println!("app starting up");
   let (mut app, events_receiver) =
-    Application::new(settings.clone(), Arc::new(db), Workspaces::new("./data/companies/"))
+    Application::new(settings.clone(), Arc::new(db), Workspaces::new(opt.data.join("companies")))
       .await
       .map_err(|e| io::Error::new(io::ErrorKind::Unsupported, e))?;
 
@@ -203,11 +302,37 @@ async fn startup() -> std::io::Result<()> {
   app.register(services::Users::new(app.clone(), "users"));
 
   app.register(Companies::new(app.clone()));
+  app.register(services::Members::new(app.clone()));
+  app.register(services::Roles::new(app.clone()));
+  app.register(services::Sessions::new(app.clone()));
+  app.register(services::Audit::new(app.clone()));
+  app.register(services::Barcodes::new(app.clone()));
+  app.register(services::Synonyms::new(app.clone()));
   app.register(People::new(app.clone()));
 
   app.register(MemoriesInFiles::new(app.clone(), "memories"));
+  app.register(References::new(app.clone()));
+  app.register(Changes::new(app.clone()));
   app.register(Inventory::new(app.clone()));
 
+  let jobs = reindex::Jobs::new(opt.data.join("reindex"))?;
+  app.register(reindex::service::Reindex::new(app.clone(), jobs.clone()));
+
+  if settings.hik.enabled {
+    if settings.hik.secret.is_empty() {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "hik.secret required"));
+    }
+
+    app.register(Departments::new(app.clone(), app.wss.clone()));
+    app.register(Shifts::new(app.clone(), app.wss.clone()));
+    app.register(Events::new(app.clone(), "events", app.wss.clone()));
+    app.register(Actions::new(app.clone(), "actions", app.wss.clone()));
+    app.register(AttendanceReport::new(app.clone(), app.wss.clone()));
+    app.register(AttendanceAdjustments::new(app.clone(), app.wss.clone()));
+    // connects enabled cameras
+    app.register(Cameras::new(app.clone(), "cameras", app.wss.clone()));
+  }
+
   println!("app started up");
 
   println!("com starting up");
@@ -215,7 +340,10 @@ async fn startup() -> std::io::Result<()> {
   println!("com started up");
 
   match opt.mode.as_str() {
-    "reindex" => reindex(settings, app, com).await,
+    "reindex" => reindex(app, jobs, &opt).await,
+    "backup" => backup(app, opt.workspace, opt.archive).await,
+    "restore" => restore(app, jobs, opt.archive).await,
+    "member" => member(app, opt.workspace, opt.email).await,
     "server" => server(settings, app, com).await,
     "import" => {
       match opt.case.as_str() {
@@ -0,0 +1,95 @@
+use json::JsonValue;
+use service::error::Error;
+use service::{Context, Service};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::commutator::Application;
+use crate::services::{Data, Params};
+
+// params: { oid, since: 0, $limit: 100, documents: true }
+// response `last` is sequence number of latest change in workspace,
+// client continue with `since` = `seq` of last received change
+pub struct Changes {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl Changes {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(Changes { app, path: Arc::new("changes".to_string()) })
+  }
+}
+
+impl Service for Changes {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn commands(&self) -> &[&str] {
+    &["find"]
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let wsid = crate::services::oid(&params)?;
+
+    let limit = self.limit(&params);
+
+    let since = self.params(&params)["since"].as_u64().unwrap_or(0);
+    let documents = self.params(&params)["documents"].as_bool().unwrap_or(false);
+
+    let ws = self.app.wss.get(&wsid);
+    let changes = ws.changes();
+
+    let last = changes.last()?;
+    let mut list = changes.since(since, limit)?;
+
+    if documents {
+      for change in &mut list {
+        let doc = match change["_uuid"].as_str().map(Uuid::parse_str) {
+          Some(Ok(uuid)) => ws.resolve_uuid(&uuid),
+          _ => change["_id"].as_str().and_then(|id| ws.resolve_id(id)),
+        };
+        change["document"] = doc.and_then(|doc| doc.json().ok()).unwrap_or(JsonValue::Null);
+      }
+    }
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      last: last,
+      "$skip": 0,
+    })
+  }
+
+  fn get(&self, _ctx: Context, _id: String, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn create(&self, _ctx: Context, _data: Data, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn patch(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn remove(&self, _ctx: Context, _id: String, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+}
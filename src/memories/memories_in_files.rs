@@ -5,15 +5,18 @@ use json::JsonValue;
 use rust_decimal::Decimal;
 use service::error::Error;
 use service::utils::json::{JsonMerge, JsonParams};
-use service::{Context, Service};
+use service::{Account, Context, Schema, Service};
 use std::collections::HashMap;
 use std::sync::Arc;
 use store::balance::BalanceForGoods;
 use store::elements::ToJson;
+use store::operations::OpMutation;
 use store::GetWarehouse;
 use uuid::Uuid;
 
 use crate::services::{Data, Params};
+use crate::storage::memories::{Pending, LOCK};
+use crate::storage::organizations::Workspace;
 use crate::storage::Workspaces;
 
 use crate::commutator::Application;
@@ -32,6 +35,179 @@ impl MemoriesInFiles {
   pub fn new(app: Application, name: &str) -> Arc<dyn Service> {
     Arc::new(MemoriesInFiles { app, name: Arc::new(name.to_string()) })
   }
+
+  // params: { oid, ctx, upsert: "code" } or { oid, ctx, upsert: ["name", "uom"] }
+  // without `upsert` every row create new document. When warehouse rejects operations
+  // nothing is saved.
+  fn bulk(
+    &self,
+    account: &Account,
+    ws: &Workspace,
+    ctx: Vec<String>,
+    rows: Data,
+    key: &JsonValue,
+  ) -> crate::services::Result {
+    let key: Vec<String> = if let Some(name) = key.as_str() {
+      vec![name.to_string()]
+    } else {
+      key.members().filter_map(|n| n.as_str()).map(|n| n.to_string()).collect()
+    };
+
+    let natural_key = |doc: &JsonValue| -> Option<String> {
+      let values: Vec<JsonValue> = key.iter().map(|n| doc[n.as_str()].clone()).collect();
+      if values.iter().any(|v| v.is_null()) {
+        None
+      } else {
+        Some(JsonValue::Array(values).dump())
+      }
+    };
+
+    let memories = ws.memories(ctx);
+
+    // documents and warehouse are changed together, so nothing is saved until warehouse accepts
+    // operations of all rows
+    let _lock = LOCK.lock().unwrap();
+
+    // existing documents by natural key
+    let mut index: HashMap<String, Vec<String>> = HashMap::new();
+    if !key.is_empty() {
+      for doc in memories.list(None)? {
+        let doc = match doc.json() {
+          Ok(doc) => doc,
+          Err(_) => continue,
+        };
+        if doc["status"].string() == "deleted".to_string() {
+          continue;
+        }
+        if let Some(value) = natural_key(&doc) {
+          index.entry(value).or_default().push(doc["_id"].string());
+        }
+      }
+    }
+
+    // checked changes and their positions by document id
+    let mut pending: Vec<Pending> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    let mut report = Vec::with_capacity(rows.len());
+    let (mut created, mut updated, mut failed) = (0, 0, 0);
+
+    for (row, data) in rows.members().enumerate() {
+      let result = if !data.is_object() {
+        Err(Error::GeneralError("only object allowed".into()))
+      } else if key.is_empty() {
+        memories
+          .prepare_create(&self.app, data.clone())
+          .map(|doc| ("created", doc.data.clone(), Some(doc)))
+      } else {
+        match natural_key(data) {
+          None => Err(Error::GeneralError(format!("key {key:?} is missing"))),
+          Some(value) => match index.get(&value).map(|ids| ids.as_slice()) {
+            None | Some([]) => memories.prepare_create(&self.app, data.clone()).map(|doc| {
+              index.insert(value, vec![doc.data["_id"].string()]);
+              ("created", doc.data.clone(), Some(doc))
+            }),
+            Some([id]) => {
+              let id = id.clone();
+              let mut patch = data.clone();
+              patch.remove("_id");
+              patch.remove("_uuid");
+
+              if let Some(position) = positions.get(&id) {
+                // same document at previous row
+                let doc = &mut pending[*position];
+                doc.merge(&self.app, &patch).map(|changed| {
+                  (if changed { "updated" } else { "unchanged" }, doc.data.clone(), None)
+                })
+              } else {
+                memories
+                  .get(&id)
+                  .ok_or(Error::NotFound(format!("id '{id}' not found")))
+                  .and_then(|doc| doc.json())
+                  .and_then(|before| {
+                    let after = before.merge(&patch);
+                    if after == before {
+                      Ok(("unchanged", before, None))
+                    } else {
+                      memories
+                        .prepare_update(&self.app, id, after)
+                        .map(|doc| ("updated", doc.data.clone(), Some(doc)))
+                    }
+                  })
+              }
+            },
+            Some(ids) => Err(Error::Conflict(format!("{} documents match key {value}", ids.len()))),
+          },
+        }
+      };
+
+      match result {
+        Ok((status, data, doc)) => {
+          match status {
+            "created" => created += 1,
+            "updated" => updated += 1,
+            _ => {},
+          }
+
+          if let Some(doc) = doc {
+            positions.insert(data["_id"].string(), pending.len());
+            pending.push(doc);
+          }
+
+          report.push(json::object! {
+            row: row,
+            status: status,
+            _id: data["_id"].clone(),
+            _uuid: data["_uuid"].clone(),
+          });
+        },
+        Err(error) => {
+          failed += 1;
+          report.push(json::object! {
+            row: row,
+            status: "failed",
+            error: error.to_json(),
+          });
+        },
+      }
+    }
+
+    let ops: Vec<OpMutation> = pending.iter().flat_map(|doc| doc.ops.iter().cloned()).collect();
+    if !ops.is_empty() {
+      if let Err(e) = self.app.warehouse().mutate(&ops) {
+        for doc in pending {
+          doc.discard()?;
+        }
+        return Err(Error::GeneralError(format!("bulk warehouse mutation failed: {}", e.message())));
+      }
+    }
+
+    // audit record every changed document
+    let mut summaries: HashMap<String, JsonValue> = HashMap::new();
+    for doc in pending {
+      summaries.insert(doc.data["_id"].string(), doc.summary());
+      doc.save(&self.app, account, true)?;
+    }
+    for row in report.iter_mut() {
+      if let Some(summary) = summaries.get(&row["_id"].string()) {
+        row["summary"] = summary.clone();
+      }
+    }
+
+    if !ops.is_empty() {
+      if let Err(e) = crate::text_search::handle_stock(&self.app, ws, &ops) {
+        log::warn!("search index: {e:?}");
+      }
+    }
+
+    Ok(json::object! {
+      data: JsonValue::Array(report),
+      created: created,
+      updated: updated,
+      failed: failed,
+      warehouse: { ops: ops.len() },
+    })
+  }
 }
 
 impl Service for MemoriesInFiles {
@@ -39,6 +215,44 @@ impl Service for MemoriesInFiles {
     &self.name
   }
 
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "create", "update", "patch"]
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    Some(Schema::new(
+      json::object! {
+        type: "object",
+        properties: {
+          filter: { type: "object", description: "fields document must be equal to" },
+          reverse: { type: "boolean" },
+          search: { type: "string", description: "full text search" },
+          cursor: { type: "string", description: "`next` of previous search page" },
+          facets: { type: "array", items: { type: "string" } },
+          drilldown: { type: "array", items: { type: "string" } },
+          enrich: { type: "boolean", description: "resolve references, `true` by default" },
+          upsert: {
+            type: "array",
+            items: { type: "string" },
+            description: "natural key of bulk create",
+          },
+        },
+      },
+      json::object! {
+        description: "document, array of documents for bulk create",
+        oneOf: [{ type: "object" }, { type: "array", items: { type: "object" } }],
+      },
+      json::object! {
+        type: "object",
+        properties: {
+          _id: { type: "string" },
+          _uuid: { type: "string", format: "uuid" },
+          status: { type: "string" },
+        },
+      },
+    ))
+  }
+
   fn find(&self, ctx: Context, params: Params) -> crate::services::Result {
     // println!("find account {:?}", ctx.account.read().unwrap());
 
@@ -52,31 +266,6 @@ impl Service for MemoriesInFiles {
 
     let reverse = self.params(&params)["reverse"].as_bool().unwrap_or(false);
 
-    // workaround
-    if ctx == vec!["drugs"] {
-      let ws = self.app.wss.get(&wsid);
-
-      let search = self.params(&params)["search"].as_str().unwrap_or_default();
-
-      let (total, result) = {
-        let engine = self.app.search.read().unwrap();
-        engine.search(search, limit, skip)
-      };
-
-      let list: Vec<JsonValue> = result
-        .into_iter()
-        // .skip(skip)
-        // .take(limit)
-        .map(|id| id.resolve_to_json_object(&ws))
-        .collect();
-
-      return Ok(json::object! {
-        data: JsonValue::Array(list),
-        total: total,
-        "$skip": skip,
-      });
-    }
-
     // workaround
     if ctx == vec!["warehouse", "stock"] {
       if skip != 0 {
@@ -104,41 +293,37 @@ impl Service for MemoriesInFiles {
 
     let ws = self.app.wss.get(&wsid);
     let memories = ws.memories(ctx.clone());
-    let list = memories.list(Some(reverse))?;
+
+    // facets: ["category", "storage", "has_balance"] or paths like "/category/<uuid>" for children,
+    // drilldown: ["/category/<uuid>", "/has_balance/true"]
+    let facets = strings(&self.params(&params)["facets"]);
+    let drilldown = strings(&self.params(&params)["drilldown"]);
+    let faceted = !facets.is_empty() || !drilldown.is_empty();
+
+    let mut facet_counts = JsonValue::Null;
+    // position after last item of page for search results
+    let mut next: Option<String> = None;
 
     let search = &self.params(&params)["search"];
     let filters = &self.params(&params)["filter"];
-    let (total, mut list): (isize, Vec<JsonValue>) = if let Some(search) = search.as_str() {
-      let mut total = 0;
-      let list: Vec<JsonValue> = list
-        .into_iter()
-        .map(|o| o.json().unwrap_or_else(|_| JsonValue::Null))
-        .filter(|o| o.is_object())
-        .filter(|o| show_deleted(&ctx) || o["status"].string() != "deleted".to_string())
-        .filter(|o| {
-          for (_name, v) in o.entries() {
-            if let Some(str) = v.as_str() {
-              if str.contains(search) {
-                return true;
-              }
-            }
-          }
-          return false;
-        })
-        .map(|o| {
-          total += 1;
-          o
-        })
-        .skip(skip)
-        .take(limit)
-        .collect::<_>();
+    let (total, mut list): (isize, Vec<JsonValue>) = if search.is_string() || faceted {
+      let search = search.as_str().unwrap_or_default();
+      let cursor = self.params(&params)["cursor"].as_str();
+      let (total, result, cursor) = {
+        let engine = self.app.search.read().unwrap();
+        if !facets.is_empty() {
+          let counts = engine.facet_counts(&wsid, &ctx, search, &drilldown, &facets);
+          facet_counts = facets_to_json(&ws, counts);
+        }
+        engine.search(&wsid, &ctx, search, &drilldown, limit, skip, cursor)?
+      };
+      next = cursor;
 
-      if list.is_empty() {
-        (total, list)
-      } else {
-        (-1, list)
-      }
+      let list = result.into_iter().map(|id| id.resolve_to_json_object(&ws)).collect();
+
+      (total as isize, list)
     } else if filters.is_object() {
+      let list = memories.list(Some(reverse))?;
       let mut total = 0;
       let list: Vec<JsonValue> = list
         .into_iter()
@@ -161,6 +346,7 @@ impl Service for MemoriesInFiles {
         (-1, list)
       }
     } else {
+      let list = memories.list(Some(reverse))?;
       (
         list.len() as isize,
         list
@@ -174,17 +360,6 @@ impl Service for MemoriesInFiles {
       )
     };
 
-    fn show_deleted(ctx: &Vec<String>) -> bool {
-      let ctx: Vec<&str> = ctx.iter().map(|s| s.as_str()).collect();
-
-      match ctx[..] {
-        ["warehouse", "receive"] => true,
-        ["warehouse", "transfer"] => true,
-        ["warehouse", "dispatch"] => true,
-        _ => false,
-      }
-    }
-
     // workaround: count produced
     if &ctx == &vec!["production", "order"] {
       let produced = self
@@ -243,11 +418,18 @@ impl Service for MemoriesInFiles {
       }
     }
 
-    Ok(json::object! {
+    let mut result = json::object! {
       data: JsonValue::Array(list),
       total: total,
       "$skip": skip,
-    })
+    };
+    if let Some(next) = next {
+      result["next"] = next.into();
+    }
+    if !facet_counts.is_null() {
+      result["facets"] = facet_counts;
+    }
+    Ok(result)
   }
 
   fn get(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
@@ -272,24 +454,27 @@ impl Service for MemoriesInFiles {
     }
   }
 
-  fn create(&self, _ctx: Context, data: Data, params: Params) -> crate::services::Result {
+  fn create(&self, ctx: Context, data: Data, params: Params) -> crate::services::Result {
+    let account = ctx.account.read().unwrap().clone();
+
     let oid = crate::services::oid(&params)?;
     let ctx = self.ctx(&params);
 
     let ws = self.app.wss.get(&oid);
 
-    let data = ws.memories(ctx).create(&self.app, data)?;
+    if data.is_array() {
+      let key = self.params(&params)["upsert"].clone();
+      return self.bulk(&account, &ws, ctx, data, &key);
+    }
+
+    let data = ws.memories(ctx).create(&self.app, &account, data)?;
 
     Ok(data.enrich(&ws))
   }
 
-  fn update(
-    &self,
-    _ctx: Context,
-    id: String,
-    data: Data,
-    params: Params,
-  ) -> crate::services::Result {
+  fn update(&self, ctx: Context, id: String, data: Data, params: Params) -> crate::services::Result {
+    let account = ctx.account.read().unwrap().clone();
+
     if !data.is_object() {
       Err(Error::GeneralError("only object allowed".into()))
     } else {
@@ -303,13 +488,15 @@ impl Service for MemoriesInFiles {
       let ws = self.app.wss.get(&oid);
       let memories = ws.memories(ctx);
 
-      let data = memories.update(&self.app, id, data)?;
+      let data = memories.update(&self.app, &account, id, data)?;
 
       Ok(data.enrich(&ws))
     }
   }
 
-  fn patch(&self, _ctx: Context, id: String, data: Data, params: Params) -> crate::services::Result {
+  fn patch(&self, ctx: Context, id: String, data: Data, params: Params) -> crate::services::Result {
+    let account = ctx.account.read().unwrap().clone();
+
     let oid = crate::services::oid(&params)?;
     let ctx = self.ctx(&params);
 
@@ -339,7 +526,7 @@ impl Service for MemoriesInFiles {
       //   }
       // }
 
-      let data = memories.update(&self.app, id, obj)?;
+      let data = memories.update(&self.app, &account, id, obj)?;
 
       Ok(data.enrich(&ws))
     }
@@ -349,3 +536,47 @@ impl Service for MemoriesInFiles {
     Err(Error::NotImplemented)
   }
 }
+
+/// warehouse documents are listed with deleted ones
+pub(crate) fn show_deleted(ctx: &[String]) -> bool {
+  let ctx: Vec<&str> = ctx.iter().map(|s| s.as_str()).collect();
+
+  match ctx[..] {
+    ["warehouse", "receive"] => true,
+    ["warehouse", "transfer"] => true,
+    ["warehouse", "dispatch"] => true,
+    _ => false,
+  }
+}
+
+fn strings(value: &JsonValue) -> Vec<String> {
+  value.members().filter_map(|v| v.as_str()).map(|v| v.to_string()).collect()
+}
+
+// { <requested facet>: [{ facet: path, value, count }] }
+fn facets_to_json(ws: &Workspace, counts: Vec<(String, Vec<(String, u64)>)>) -> JsonValue {
+  let mut result = JsonValue::new_object();
+  for (name, counts) in counts {
+    let list: Vec<JsonValue> = counts
+      .into_iter()
+      .map(|(facet, count)| {
+        json::object! { value: facet_value(ws, &facet), facet: facet, count: count }
+      })
+      .collect();
+    result[name] = list.into();
+  }
+  result
+}
+
+// last segment of facet path: document by uuid or plain value
+fn facet_value(ws: &Workspace, facet: &str) -> JsonValue {
+  let last = facet.rsplit('/').next().unwrap_or_default();
+  match Uuid::parse_str(last) {
+    Ok(id) => id.resolve_to_json_object(ws),
+    Err(_) => match last {
+      "true" => true.into(),
+      "false" => false.into(),
+      _ => last.into(),
+    },
+  }
+}
@@ -1,9 +1,14 @@
+mod changes;
 mod memories_in_files;
+mod references;
 pub(crate) mod stock;
 
 use crate::storage::organizations::Workspace;
 use json::JsonValue;
+pub use changes::Changes;
 pub use memories_in_files::MemoriesInFiles;
+pub(crate) use memories_in_files::show_deleted;
+pub use references::References;
 use uuid::{Error, Uuid};
 
 pub trait Enrich {
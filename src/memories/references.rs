@@ -0,0 +1,101 @@
+use json::JsonValue;
+use service::error::Error;
+use service::{Context, Service};
+use std::sync::Arc;
+
+use crate::commutator::Application;
+use crate::memories::Resolve;
+use crate::services::{Data, Params};
+
+// params: { oid, target: "_id or _uuid" } - where used
+//         { oid, dangling: true } - report of broken references
+pub struct References {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl References {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(References { app, path: Arc::new("references".to_string()) })
+  }
+}
+
+impl Service for References {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn commands(&self) -> &[&str] {
+    &["find"]
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let wsid = crate::services::oid(&params)?;
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let ws = self.app.wss.get(&wsid);
+    let references = ws.references();
+
+    let params = self.params(&params);
+
+    let list = if params["dangling"].as_bool().unwrap_or(false) {
+      references.dangling()?
+    } else if let Some(target) = params["target"].as_str() {
+      references.used_by(target)?
+    } else {
+      return Err(Error::GeneralError("`target` or `dangling` required".into()));
+    };
+
+    let total = list.len();
+    let list: Vec<JsonValue> = list
+      .into_iter()
+      .skip(skip)
+      .take(limit)
+      .map(|reference| {
+        let mut data = reference.to_json();
+        data["document"] = reference.source.resolve_to_json_object(&ws);
+        data
+      })
+      .collect();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, _ctx: Context, _id: String, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn create(&self, _ctx: Context, _data: Data, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn patch(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn remove(&self, _ctx: Context, _id: String, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+}
@@ -0,0 +1,258 @@
+use actix_web::http::header;
+use actix_web::{get, web, HttpResponse};
+use json::{array, object, JsonValue};
+
+use crate::commutator::Application;
+use service::{Schema, Service, Services};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const SOCKET_IO: &str = "Every service is also available over socket.io at `/socket.io` as event \
+  `[command, service, ...arguments]` with acknowledgement `[error, result]`: \
+  `find` [params], `get` [id, params], `create` [data, params], `update` [id, data, params], \
+  `patch` [id, data, params], `remove` [id, params]. Mutations are broadcast to rooms of \
+  workspace as `[service + \" created\" | \" updated\" | \" patched\" | \" removed\", result]`.";
+
+/// OpenAPI description of registered services
+#[get("/openapi.json")]
+pub(crate) async fn openapi(app: web::Data<Application>) -> HttpResponse {
+  HttpResponse::Ok()
+    .append_header(header::ContentType(mime::APPLICATION_JSON))
+    .body(document(&app).dump())
+}
+
+/// OpenAPI 3 document of REST routes `/v1/{service}` and `/v1/{service}/{id}`
+pub(crate) fn document(app: &Application) -> JsonValue {
+  let mut paths = JsonValue::new_object();
+  let mut tags = JsonValue::new_array();
+
+  for service in app.registered() {
+    let path = service.path().to_string();
+    let schema =
+      service
+        .schema()
+        .unwrap_or(Schema::new(JsonValue::Null, JsonValue::Null, JsonValue::Null));
+
+    tags.push(object! { name: path.as_str() }).ok();
+
+    let mut parameters = common_parameters();
+    for parameter in params_of(&schema.params).members() {
+      parameters.push(parameter.clone()).ok();
+    }
+
+    let data = or_any(&schema.data);
+    let item = or_any(&schema.response);
+    let page = object! {
+      type: "object",
+      properties: {
+        data: { type: "array", items: item.clone() },
+        total: { type: "integer" },
+        "$skip": { type: "integer" },
+      },
+    };
+
+    // commands responding `NotImplemented` are left out
+    let implemented = |command: &str| service.commands().contains(&command);
+
+    let mut collection = JsonValue::new_object();
+    if implemented("find") {
+      collection["get"] = operation(&path, "find", &parameters, None, page);
+    }
+    if implemented("create") {
+      collection["post"] = operation(&path, "create", &parameters, Some(&data), item.clone());
+    }
+    if !collection.is_empty() {
+      paths[format!("/v1/{path}")] = collection;
+    }
+
+    let mut parameters = parameters;
+    parameters
+      .push(object! { name: "id", in: "path", required: true, schema: { type: "string" } })
+      .ok();
+
+    let mut single = JsonValue::new_object();
+    if implemented("get") {
+      single["get"] = operation(&path, "get", &parameters, None, item.clone());
+    }
+    if implemented("update") {
+      single["put"] = operation(&path, "update", &parameters, Some(&data), item.clone());
+    }
+    if implemented("patch") {
+      single["patch"] = operation(&path, "patch", &parameters, Some(&data), item.clone());
+    }
+    if implemented("remove") {
+      single["delete"] = operation(&path, "remove", &parameters, None, item);
+    }
+    if !single.is_empty() {
+      paths[format!("/v1/{path}/{{id}}")] = single;
+    }
+  }
+
+  object! {
+    openapi: "3.0.3",
+    info: {
+      title: env!("CARGO_PKG_NAME"),
+      version: VERSION,
+      description: SOCKET_IO,
+    },
+    tags: tags,
+    paths: paths,
+    components: {
+      schemas: {
+        Error: {
+          type: "object",
+          properties: {
+            className: { type: "string" },
+            code: { type: "integer" },
+            message: { type: "string" },
+            name: { type: "string" },
+          },
+        },
+      },
+      securitySchemes: {
+        bearer: { type: "http", scheme: "bearer", bearerFormat: "JWT" },
+      },
+    },
+    security: [{ bearer: [] }],
+  }
+}
+
+fn operation(
+  path: &str,
+  command: &str,
+  parameters: &JsonValue,
+  data: Option<&JsonValue>,
+  response: JsonValue,
+) -> JsonValue {
+  let status = if command == "create" { "201" } else { "200" };
+
+  let mut operation = object! {
+    tags: [path],
+    operationId: format!("{path}.{command}"),
+    parameters: parameters.clone(),
+    "x-socket.io": { command: command, service: path },
+    responses: {
+      default: {
+        description: "error",
+        content: { "application/json": { schema: { "$ref": "#/components/schemas/Error" } } },
+      },
+    },
+  };
+  operation["responses"][status] = object! {
+    description: command,
+    content: { "application/json": { schema: response } },
+  };
+  if let Some(data) = data {
+    operation["requestBody"] = object! {
+      required: true,
+      content: { "application/json": { schema: data.clone() } },
+    };
+  }
+  operation
+}
+
+// params every service understand
+fn common_parameters() -> JsonValue {
+  array![
+    { name: "oid", in: "query", description: "workspace", schema: { type: "string" } },
+    {
+      name: "ctx",
+      in: "query",
+      description: "context, comma separated",
+      style: "form",
+      explode: false,
+      schema: { type: "array", items: { type: "string" } },
+    },
+    { name: "$limit", in: "query", schema: { type: "integer", minimum: 0, maximum: 100 } },
+    { name: "$skip", in: "query", schema: { type: "integer", minimum: 0 } },
+  ]
+}
+
+// properties of params schema as query parameters, objects are `a[b]=c`
+fn params_of(schema: &JsonValue) -> JsonValue {
+  let mut parameters = JsonValue::new_array();
+  for (name, property) in schema["properties"].entries() {
+    if matches!(name, "oid" | "ctx" | "$limit" | "$skip") {
+      continue;
+    }
+    let mut parameter = object! { name: name, in: "query", schema: property.clone() };
+    if property["type"] == "object" {
+      parameter["style"] = "deepObject".into();
+      parameter["explode"] = true.into();
+    }
+    if let Some(description) = property["description"].as_str() {
+      parameter["description"] = description.into();
+    }
+    parameters.push(parameter).ok();
+  }
+  parameters
+}
+
+fn or_any(schema: &JsonValue) -> JsonValue {
+  if schema.is_null() {
+    object! { type: "object" }
+  } else {
+    schema.clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::services::{Audit, Members, Sessions};
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use actix_web::{test, App};
+  use std::sync::Arc;
+
+  #[actix_web::test]
+  async fn test_document() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(Sessions::new(app.clone()));
+    app.register(Members::new(app.clone()));
+    app.register(Audit::new(app.clone()));
+
+    let server = test::init_service(
+      App::new()
+        .app_data(web::Data::new(app))
+        .service(web::scope("/v1").service(openapi)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/v1/openapi.json").to_request();
+    let response = test::call_and_read_body(&server, req).await;
+    let doc = json::parse(std::str::from_utf8(&response).unwrap()).unwrap();
+
+    assert_eq!(doc["openapi"], "3.0.3");
+    assert_eq!(doc["tags"], array![{ name: "audit" }, { name: "members" }, { name: "sessions" }]);
+
+    let find = &doc["paths"]["/v1/members"]["get"];
+    assert_eq!(find["operationId"], "members.find");
+    assert_eq!(find["x-socket.io"]["command"], "find");
+    let item = &find["responses"]["200"]["content"]["application/json"]["schema"];
+    assert_eq!(item["properties"]["data"]["items"]["properties"]["roles"]["type"], "array");
+
+    let create = &doc["paths"]["/v1/members"]["post"];
+    let data = &create["requestBody"]["content"]["application/json"]["schema"];
+    assert_eq!(data["required"], array!["email"]);
+    assert!(create["responses"]["201"].is_object());
+
+    let remove = &doc["paths"]["/v1/sessions/{id}"]["delete"];
+    assert_eq!(remove["operationId"], "sessions.remove");
+    assert!(remove["parameters"].members().any(|p| p["name"] == "id" && p["in"] == "path"));
+
+    // not implemented commands aren't documented
+    assert!(doc["paths"]["/v1/sessions"]["post"].is_null());
+    assert!(doc["paths"]["/v1/sessions/{id}"]["put"].is_null());
+    assert!(doc["paths"]["/v1/sessions/{id}"]["patch"].is_null());
+    assert!(doc["paths"]["/v1/members/{id}"]["put"].is_null());
+    assert!(doc["paths"]["/v1/members/{id}"]["patch"].is_object());
+    assert!(doc["paths"]["/v1/audit"]["get"].is_object());
+    assert!(doc["paths"]["/v1/audit/{id}"].is_null());
+
+    tmp_dir.close().unwrap();
+  }
+}
@@ -0,0 +1,440 @@
+pub mod service;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use chrono::{NaiveDate, Utc};
+use json::JsonValue;
+use uuid::Uuid;
+
+use crate::commutator::Application;
+use crate::storage::memories::{index_uuid, Document, LOCK};
+use crate::storage::organizations::Workspace;
+use crate::storage::{load, save};
+use service::error::Error;
+use service::utils::json::JsonParams;
+use service::utils::time::time_to_string;
+use store::GetWarehouse;
+use values::ID;
+
+// save progress after every N documents
+const CHECKPOINT_RATE: usize = 100;
+// keep report of reasonable size, `failed` counts everything
+const ERRORS_LIMIT: usize = 1000;
+
+/// What to reindex: workspace, context prefix and range of document creation dates.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Scope {
+  pub(crate) oid: Option<ID>,
+  pub(crate) ctx: Vec<String>,
+  // inclusive, YYYY-MM-DD
+  pub(crate) from: Option<String>,
+  pub(crate) till: Option<String>,
+}
+
+impl Scope {
+  pub(crate) fn from_json(data: &JsonValue) -> Result<Self, Error> {
+    let oid = match data["oid"].as_str() {
+      Some(oid) => Some(crate::services::string_to_id(oid.to_string())?),
+      None => None,
+    };
+
+    let ctx = if let Some(ctx) = data["ctx"].as_str() {
+      ctx.split("/").filter(|s| !s.is_empty()).map(|s| s.to_string()).collect()
+    } else {
+      data["ctx"].members().filter_map(|v| v.string_or_none()).collect()
+    };
+
+    let date = |name: &str| -> Result<Option<String>, Error> {
+      match data["dates"][name].as_str() {
+        Some(date) => {
+          NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| Error::GeneralError(format!("invalid date '{date}'")))?;
+          Ok(Some(date.to_string()))
+        },
+        None => Ok(None),
+      }
+    };
+
+    Ok(Scope { oid, ctx, from: date("from")?, till: date("till")? })
+  }
+
+  pub(crate) fn to_json(&self) -> JsonValue {
+    let mut data = json::object! {
+      ctx: self.ctx.clone(),
+      dates: {},
+    };
+    if let Some(oid) = &self.oid {
+      data["oid"] = oid.to_base64().into();
+    }
+    if let Some(from) = &self.from {
+      data["dates"]["from"] = from.clone().into();
+    }
+    if let Some(till) = &self.till {
+      data["dates"]["till"] = till.clone().into();
+    }
+    data
+  }
+
+  fn contains(&self, ctx: &Vec<String>, id: &str) -> bool {
+    if !ctx.starts_with(&self.ctx) {
+      return false;
+    }
+
+    // id of document is time of creation: 2023-01-06T12:43:15Z
+    let date = id.get(0..10).unwrap_or_default();
+    if let Some(from) = &self.from {
+      if date < from.as_str() {
+        return false;
+      }
+    }
+    if let Some(till) = &self.till {
+      if date > till.as_str() {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+/// Reindex jobs, state of every job persisted at `<folder>/<id>.json`
+#[derive(Clone)]
+pub(crate) struct Jobs {
+  folder: PathBuf,
+  running: Arc<RwLock<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl Jobs {
+  pub(crate) fn new(folder: PathBuf) -> Result<Self, Error> {
+    std::fs::create_dir_all(&folder).map_err(|e| {
+      Error::IOError(format!("can't create folder {}: {}", folder.to_string_lossy(), e))
+    })?;
+
+    Ok(Jobs { folder, running: Arc::new(RwLock::new(HashMap::new())) })
+  }
+
+  fn path(&self, id: &str) -> PathBuf {
+    let mut path = self.folder.clone();
+    path.push(format!("{}.json", sanitize_filename::sanitize(id)));
+    path
+  }
+
+  pub(crate) fn create(&self, scope: Scope) -> crate::services::Result {
+    let now = time_to_string(Utc::now());
+
+    let state = json::object! {
+      _id: Uuid::new_v4().to_string(),
+      scope: scope.to_json(),
+      status: "pending",
+      processed: 0,
+      failed: 0,
+      checkpoint: null,
+      errors: [],
+      started: now.clone(),
+      updated: now,
+    };
+
+    self.save(&state)?;
+
+    Ok(state)
+  }
+
+  pub(crate) fn load(&self, id: &str) -> crate::services::Result {
+    let path = self.path(id);
+    if !path.exists() {
+      return Err(Error::NotFound(format!("reindex job {id}")));
+    }
+    load(&path)
+  }
+
+  pub(crate) fn list(&self) -> Result<Vec<JsonValue>, Error> {
+    let mut result = Vec::new();
+    for entry in std::fs::read_dir(&self.folder)? {
+      let path = entry?.path();
+      if path.is_file() && path.to_string_lossy().ends_with(".json") {
+        result.push(load(&path)?);
+      }
+    }
+
+    result.sort_by(|a, b| b["started"].string().cmp(&a["started"].string()));
+
+    Ok(result)
+  }
+
+  fn save(&self, state: &JsonValue) -> Result<(), Error> {
+    save(&self.path(&state["_id"].string()), state.dump())
+  }
+
+  pub(crate) fn is_running(&self, id: &str) -> bool {
+    self.running.read().unwrap().contains_key(id)
+  }
+
+  pub(crate) fn stop(&self, id: &str) {
+    if let Some(flag) = self.running.read().unwrap().get(id) {
+      flag.store(true, Ordering::SeqCst);
+    }
+  }
+
+  /// run job in background thread
+  pub(crate) fn spawn(&self, app: Application, id: String) -> Result<(), Error> {
+    if self.is_running(&id) {
+      return Err(Error::Conflict(format!("reindex job {id} is running")));
+    }
+
+    let jobs = self.clone();
+    thread::spawn(move || {
+      if let Err(e) = jobs.run(&app, &id) {
+        log::error!("reindex job {id} failed: {e}");
+      }
+    });
+
+    Ok(())
+  }
+
+  /// replay documents of job scope starting after last checkpoint
+  pub(crate) fn run(&self, app: &Application, id: &str) -> crate::services::Result {
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+      let mut running = self.running.write().unwrap();
+      if running.contains_key(id) {
+        return Err(Error::Conflict(format!("reindex job {id} is running")));
+      }
+      running.insert(id.to_string(), stop.clone());
+    }
+
+    let result = self.process(app, id, stop);
+
+    self.running.write().unwrap().remove(id);
+
+    result
+  }
+
+  fn process(&self, app: &Application, id: &str, stop: Arc<AtomicBool>) -> crate::services::Result {
+    let mut state = self.load(id)?;
+    if state["status"] == "done" {
+      return Ok(state);
+    }
+
+    let scope = Scope::from_json(&state["scope"])?;
+
+    let checkpoint_ws = state["checkpoint"]["workspace"].string_or_none();
+    let checkpoint_doc = state["checkpoint"]["document"].string_or_none();
+
+    state["status"] = "running".into();
+    state["updated"] = time_to_string(Utc::now()).into();
+    self.save(&state)?;
+
+    let mut workspaces: Vec<Workspace> = app
+      .wss
+      .list()?
+      .into_iter()
+      .filter(|ws| scope.oid.map(|oid| oid == ws.id).unwrap_or(true))
+      .collect();
+    workspaces.sort_by(|a, b| a.id.to_base64().cmp(&b.id.to_base64()));
+
+    let mut count = 0;
+    for ws in workspaces {
+      let wid = ws.id.to_base64();
+      if let Some(checkpoint) = &checkpoint_ws {
+        if &wid < checkpoint {
+          continue;
+        }
+      }
+
+      let mut docs: Vec<(String, Document)> = ws
+        .clone()
+        .into_iter()
+        .filter(|doc| scope.contains(&doc.mem.ctx, &doc.id))
+        .map(|doc| (format!("{}/{}", doc.mem.ctx.join("/"), doc.id), doc))
+        .collect();
+      docs.sort_by(|a, b| a.0.cmp(&b.0));
+
+      for (key, doc) in docs {
+        if checkpoint_ws.as_ref() == Some(&wid) {
+          if let Some(checkpoint) = &checkpoint_doc {
+            if &key <= checkpoint {
+              continue;
+            }
+          }
+        }
+
+        if stop.load(Ordering::SeqCst) {
+          state["status"] = "stopped".into();
+          state["updated"] = time_to_string(Utc::now()).into();
+          self.save(&state)?;
+          return Ok(state);
+        }
+
+        match reindex_document(app, &ws, &doc) {
+          Ok(_) => state["processed"] = (state["processed"].as_usize().unwrap_or(0) + 1).into(),
+          Err(e) => {
+            state["failed"] = (state["failed"].as_usize().unwrap_or(0) + 1).into();
+            if state["errors"].len() < ERRORS_LIMIT {
+              state["errors"]
+                .push(json::object! {
+                  workspace: wid.clone(),
+                  document: key.clone(),
+                  error: e.to_string(),
+                })
+                .map_err(|e| Error::GeneralError(e.to_string()))?;
+            }
+          },
+        }
+
+        state["checkpoint"] = json::object! { workspace: wid.clone(), document: key };
+
+        count += 1;
+        if count % CHECKPOINT_RATE == 0 {
+          state["updated"] = time_to_string(Utc::now()).into();
+          self.save(&state)?;
+        }
+      }
+    }
+
+    {
+      let mut search = app.search.write().unwrap();
+      search
+        .commit()
+        .map_err(|e| Error::GeneralError(format!("search commit: {e:?}")))?;
+    }
+
+    state["status"] = "done".into();
+    state["updated"] = time_to_string(Utc::now()).into();
+    self.save(&state)?;
+
+    Ok(state)
+  }
+}
+
+/// replay document through search index, warehouse, references and barcodes
+pub(crate) fn reindex_document(
+  app: &Application,
+  ws: &Workspace,
+  doc: &Document,
+) -> Result<(), Error> {
+  // online job must not interleave with saving of same documents
+  let _lock = LOCK.lock().unwrap();
+
+  let ctx = &doc.mem.ctx;
+
+  let before = JsonValue::Null;
+  let mut after = doc.json()?;
+
+  if !after.is_object() {
+    return Err(Error::GeneralError("document is not an object".into()));
+  }
+
+  // inject uuid if missing
+  if after["_uuid"].is_null() {
+    let uuid = Uuid::new_v4().to_string();
+    after["_uuid"] = uuid.clone().into();
+
+    let folder = doc
+      .path
+      .parent()
+      .ok_or_else(|| Error::IOError(format!("no folder for {}", doc.path.to_string_lossy())))?;
+
+    index_uuid(&doc.mem.top_folder, &folder.to_path_buf(), uuid.as_str())?;
+  }
+
+  // delete batch from document if it exists
+  after.remove("batch");
+
+  crate::text_search::handle_mutation(app, ws, ctx, &before, &after)
+    .map_err(|e| Error::GeneralError(format!("search: {e:?}")))?;
+
+  let ops = store::elements::data_to_ops(app, ws.id.to_string().as_str(), &after, ctx, &before)
+    .map_err(|e| Error::GeneralError(e.message()))?;
+  if !ops.is_empty() {
+    app.warehouse().mutate(&ops).map_err(|e| Error::GeneralError(e.message()))?;
+
+    crate::text_search::handle_stock(app, ws, &ops)
+      .map_err(|e| Error::GeneralError(format!("search: {e:?}")))?;
+  }
+
+  ws.references().update(&JsonValue::Null, &after)?;
+
+  let barcodes = ws.barcodes();
+  barcodes.update_batches(&ops)?;
+  if ctx == &vec!["goods".to_string()] {
+    barcodes.update_goods(&JsonValue::Null, &after)?;
+  }
+
+  save(&doc.path, after.dump())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::memories::MemoriesInFiles;
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use service::{Context, Services};
+
+  #[test]
+  fn test_scope() {
+    let scope = Scope::from_json(&json::object! {
+      ctx: "warehouse/receive",
+      dates: { from: "2023-01-01", till: "2023-01-31" },
+    })
+    .unwrap();
+
+    let receive = vec!["warehouse".to_string(), "receive".to_string()];
+    let goods = vec!["warehouse".to_string(), "goods".to_string()];
+
+    assert!(scope.contains(&receive, "2023-01-06T12:43:15Z"));
+    assert!(scope.contains(&receive, "2023-01-31T23:59:59Z"));
+    assert!(!scope.contains(&receive, "2023-02-01T00:00:00Z"));
+    assert!(!scope.contains(&goods, "2023-01-06T12:43:15Z"));
+
+    assert!(Scope::from_json(&json::object! { dates: { from: "2023-13-01" } }).is_err());
+  }
+
+  #[actix_web::test]
+  async fn test_errors_and_resume() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(MemoriesInFiles::new(app.clone(), "memories"));
+
+    let wid = ID::random();
+    for name in ["g1", "g2", "g3"] {
+      app
+        .service("memories")
+        .create(
+          Context::local(),
+          json::object! { name: name },
+          json::object! { oid: wid.to_base64(), ctx: vec!["goods"] },
+        )
+        .unwrap();
+    }
+
+    // corrupt one document
+    let ws = app.wss.get(&wid);
+    let docs = ws.memories(vec!["goods".into()]).list(Some(true)).unwrap();
+    save(&docs[1].path, "{ broken".into()).unwrap();
+
+    let jobs = Jobs::new(tmp_dir.path().join("reindex")).unwrap();
+    let state = jobs.create(Scope { oid: Some(wid), ..Scope::default() }).unwrap();
+    let id = state["_id"].string();
+
+    let state = jobs.run(&app, &id).unwrap();
+    assert_eq!(state["status"], "done");
+    assert_eq!(state["processed"], 2);
+    assert_eq!(state["failed"], 1);
+    assert_eq!(state["errors"].len(), 1);
+
+    // resume from checkpoint: nothing left to process
+    let mut state = jobs.load(&id).unwrap();
+    state["status"] = "stopped".into();
+    jobs.save(&state).unwrap();
+
+    let state = jobs.run(&app, &id).unwrap();
+    assert_eq!(state["status"], "done");
+    assert_eq!(state["processed"], 2);
+  }
+}
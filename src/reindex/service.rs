@@ -0,0 +1,202 @@
+use json::JsonValue;
+use service::error::Error;
+use service::{Context, Service};
+use std::sync::Arc;
+
+use crate::commutator::Application;
+use crate::reindex::{Jobs, Scope};
+use crate::services::{Data, Params};
+use crate::storage::roles::OWNER;
+use values::ID;
+
+// create: { oid, ctx: "warehouse/receive", dates: { from, till } } - start background job,
+// `oid` is required and only owner of workspace can manage its jobs, global scope is offline only
+// patch: { status: "running" } - resume from checkpoint, { status: "stopped" } - stop
+// find: { oid } - jobs of workspace, get: job of workspace where account is owner
+pub struct Reindex {
+  app: Application,
+  path: Arc<String>,
+  jobs: Jobs,
+}
+
+impl Reindex {
+  pub(crate) fn new(app: Application, jobs: Jobs) -> Arc<dyn Service> {
+    Arc::new(Reindex { app, path: Arc::new("reindex".to_string()), jobs })
+  }
+
+  fn status(&self, mut state: JsonValue) -> JsonValue {
+    // job that was interrupted by restart
+    if state["status"] == "running" && !self.jobs.is_running(&state["_id"].to_string()) {
+      state["status"] = "interrupted".into();
+    }
+    state
+  }
+
+  fn owner(&self, ctx: &Context, oid: Option<ID>) -> Result<ID, Error> {
+    let oid = oid.ok_or_else(|| {
+      Error::Forbidden("reindex of all workspaces is available offline only".into())
+    })?;
+
+    let account = ctx.account.read().unwrap().clone();
+    match self.app.wss.get(&oid).members().roles_of(&account.id)? {
+      Some(roles) if roles.iter().any(|role| role == OWNER) => Ok(oid),
+      _ => {
+        Err(Error::Forbidden(format!("reindex of `{}` requires `{OWNER}` role", oid.to_base64())))
+      },
+    }
+  }
+}
+
+impl Service for Reindex {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "create", "patch", "remove"]
+  }
+
+  fn find(&self, ctx: Context, params: Params) -> crate::services::Result {
+    let oid = self.owner(&ctx, Some(crate::services::oid(&params)?))?.to_base64();
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let list: Vec<JsonValue> = self
+      .jobs
+      .list()?
+      .into_iter()
+      .filter(|state| state["scope"]["oid"] == oid)
+      .collect();
+    let total = list.len();
+
+    let list: Vec<JsonValue> = list
+      .into_iter()
+      .skip(skip)
+      .take(limit)
+      .map(|state| self.status(state))
+      .collect();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, ctx: Context, id: String, _params: Params) -> crate::services::Result {
+    let state = self.jobs.load(&id)?;
+    self.owner(&ctx, Scope::from_json(&state["scope"])?.oid)?;
+    Ok(self.status(state))
+  }
+
+  fn create(&self, ctx: Context, data: Data, _params: Params) -> crate::services::Result {
+    if data["oid"].is_null() {
+      return Err(Error::GeneralError("`oid` required".into()));
+    }
+    let scope = Scope::from_json(&data)?;
+    self.owner(&ctx, scope.oid)?;
+
+    let state = self.jobs.create(scope)?;
+    self.jobs.spawn(self.app.clone(), state["_id"].to_string())?;
+
+    Ok(state)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn patch(&self, ctx: Context, id: String, data: Data, _params: Params) -> crate::services::Result {
+    let state = self.jobs.load(&id)?;
+    self.owner(&ctx, Scope::from_json(&state["scope"])?.oid)?;
+
+    match data["status"].as_str() {
+      Some("running") => self.jobs.spawn(self.app.clone(), id)?,
+      Some("stopped") => self.jobs.stop(&id),
+      _ => return Err(Error::GeneralError("`status` must be `running` or `stopped`".into())),
+    }
+
+    Ok(state)
+  }
+
+  fn remove(&self, ctx: Context, id: String, _params: Params) -> crate::services::Result {
+    let state = self.jobs.load(&id)?;
+    self.owner(&ctx, Scope::from_json(&state["scope"])?.oid)?;
+    self.jobs.stop(&id);
+    Ok(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::roles::VIEWER;
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use service::{Account, Services};
+
+  #[actix_web::test]
+  async fn test_create_requires_owner() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    let jobs = Jobs::new(tmp_dir.path().join("reindex")).unwrap();
+    app.register(Reindex::new(app.clone(), jobs));
+
+    let wid = ID::random();
+    let ws = app.wss.create(wid).unwrap();
+
+    let owner = Account { id: ID::from("owner@nae.org"), email: "owner@nae.org".into() };
+    let viewer = Account { id: ID::from("viewer@nae.org"), email: "viewer@nae.org".into() };
+    ws.members().add(&owner, &[OWNER.to_string()]).unwrap();
+    ws.members().add(&viewer, &[VIEWER.to_string()]).unwrap();
+
+    let context = |account: &Account| {
+      let ctx = Context::local();
+      *ctx.account.write().unwrap() = account.clone();
+      ctx
+    };
+
+    // global scope
+    let data = json::object! { ctx: "goods" };
+    assert!(app.service("reindex").create(context(&owner), data, JsonValue::Null).is_err());
+
+    let data = json::object! { oid: wid.to_base64(), ctx: "goods" };
+    match app.service("reindex").create(context(&viewer), data.clone(), JsonValue::Null) {
+      Err(Error::Forbidden(_)) => {},
+      r => panic!("expected forbidden, got {r:?}"),
+    }
+
+    let state = app.service("reindex").create(context(&owner), data, JsonValue::Null).unwrap();
+    assert_eq!(state["scope"]["oid"], wid.to_base64());
+
+    let id = state["_id"].string();
+    match app.service("reindex").remove(context(&viewer), id.clone(), JsonValue::Null) {
+      Err(Error::Forbidden(_)) => {},
+      r => panic!("expected forbidden, got {r:?}"),
+    }
+
+    // jobs are visible to owner of workspace only
+    let params = json::object! { oid: wid.to_base64() };
+    let list = app.service("reindex").find(context(&owner), params.clone()).unwrap();
+    assert_eq!(list["total"], 1);
+    assert!(app.service("reindex").find(context(&viewer), params).is_err());
+
+    let other = json::object! { oid: ID::random().to_base64() };
+    assert!(app.service("reindex").find(context(&owner), other).is_err());
+
+    assert!(app.service("reindex").get(context(&owner), id.clone(), JsonValue::Null).is_ok());
+    match app.service("reindex").get(context(&viewer), id, JsonValue::Null) {
+      Err(Error::Forbidden(_)) => {},
+      r => panic!("expected forbidden, got {r:?}"),
+    }
+  }
+}
@@ -0,0 +1,114 @@
+use json::JsonValue;
+use std::sync::Arc;
+
+use crate::commutator::Application;
+use crate::services::{Data, Params};
+use crate::storage::audit::{AuditFilter, SAudit};
+use service::error::Error;
+use service::{Context, Schema, Service};
+
+// who changed what at workspace, newest first
+// params: { oid, account: id or email, document: id, from: "2023-01-01", till: "2023-01-31" }
+pub struct Audit {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl Audit {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(Audit { app, path: Arc::new("audit".to_string()) })
+  }
+}
+
+impl Service for Audit {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn commands(&self) -> &[&str] {
+    &["find"]
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    Some(Schema::new(
+      json::object! {
+        type: "object",
+        properties: {
+          account: { type: "string", description: "account id or email" },
+          document: { type: "string" },
+          from: { type: "string", format: "date" },
+          till: { type: "string", format: "date" },
+        },
+      },
+      JsonValue::Null,
+      json::object! {
+        type: "object",
+        properties: {
+          time: { type: "string", format: "date-time" },
+          account: { type: "object" },
+          service: { type: "string" },
+          command: { type: "string" },
+          ctx: { type: "array", items: { type: "string" } },
+          document: { type: "string" },
+          summary: { type: "object" },
+        },
+      },
+    ))
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let params = self.params(&params);
+
+    let filter = AuditFilter {
+      account: params["account"].as_str().map(|s| s.to_string()),
+      document: params["document"].as_str().map(|s| s.to_string()),
+      from: SAudit::date(&params["from"])?,
+      till: SAudit::date(&params["till"])?,
+    };
+
+    let (list, total) = self.app.wss.get(&oid).audit().find(&filter, skip, limit)?;
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, _ctx: Context, _id: String, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn create(&self, _ctx: Context, _data: Data, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn patch(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn remove(&self, _ctx: Context, _id: String, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+}
@@ -1,10 +1,10 @@
-use crate::auth::JWTAuth;
 use crate::commutator::Application;
 use crate::services::{Data, Params};
 use json::JsonValue;
 use service::error::Error;
-use service::{Account, Context, Service, Services};
+use service::{Account, Context, Schema, Service, Services};
 use std::sync::Arc;
+use values::ID;
 
 pub struct Authentication {
   app: Application,
@@ -17,11 +17,57 @@ impl Authentication {
   }
 }
 
+impl Authentication {
+  fn login(&self, ctx: &Context, account: Account, session: String) {
+    *ctx.account.write().unwrap() = account;
+    *ctx.session.write().unwrap() = Some(session);
+  }
+}
+
 impl Service for Authentication {
   fn path(&self) -> &str {
     &self.path
   }
 
+  fn commands(&self) -> &[&str] {
+    &["create", "remove"]
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    Some(Schema::new(
+      JsonValue::Null,
+      json::object! {
+        type: "object",
+        properties: {
+          strategy: {
+            type: "string",
+            enum: [
+              "local", "jwt", "refresh", "verification-request", "verification",
+              "password-reset-request", "password-reset"
+            ],
+          },
+          email: { type: "string", format: "email" },
+          password: { type: "string" },
+          device: { type: "string" },
+          accessToken: { type: "string" },
+          refreshToken: { type: "string" },
+          token: { type: "string", description: "of verification or password reset" },
+        },
+      },
+      json::object! {
+        type: "object",
+        properties: {
+          accessToken: { type: "string" },
+          refreshToken: { type: "string" },
+          user: { type: "object" },
+          sent: { type: "boolean" },
+          _id: { type: "string" },
+          emailVerified: { type: "boolean" },
+        },
+      },
+    ))
+  }
+
   fn find(&self, _ctx: Context, _params: Params) -> crate::services::Result {
     Err(Error::NotImplemented)
   }
@@ -35,8 +81,9 @@ impl Service for Authentication {
     match strategy.as_str() {
       "jwt" => {
         let token = data["accessToken"].as_str().unwrap_or("");
-        let account =
-          Account::jwt(&self.app, token).map_err(|e| Error::GeneralError(e.to_string()))?;
+        let (email, session) = crate::auth::decode_token(&self.app, token)
+          .map_err(|e| Error::NotAuthenticated(e.to_string()))?;
+        let account = Account { id: ID::from(email.as_str()), email };
 
         let user = self.app.service("users").get(
           Context::local(),
@@ -49,39 +96,97 @@ impl Service for Authentication {
           user: user
         };
 
-        let mut ctx_account = ctx.account.write().unwrap();
-        *ctx_account = account;
+        self.login(&ctx, account, session);
 
         Ok(data)
       },
       "local" => {
         let email = data["email"].as_str().unwrap_or("").trim().to_lowercase();
         let password = data["password"].as_str().unwrap_or("").to_string();
+        let device = data["device"].as_str().unwrap_or("").to_string();
 
         let request =
-          crate::auth::LoginRequest { password, email: email.clone(), remember_me: false };
+          crate::auth::LoginRequest { password, email: email.clone(), remember_me: false, device };
 
         match crate::auth::login_procedure(&self.app, request) {
-          Ok((account, token)) => {
+          Ok((account, tokens)) => {
+            let user = self.app.service("users").get(
+              Context::local(),
+              account.to_base64(),
+              JsonValue::Null,
+            )?;
+
+            let data = json::object! {
+              accessToken: tokens.access,
+              refreshToken: tokens.refresh,
+              user: user
+            };
+
+            self.login(&ctx, Account { id: account, email }, tokens.session);
+
+            Ok(data)
+          },
+          Err(msg) => Err(Error::NotAuthenticated(msg)),
+        }
+      },
+      "refresh" => {
+        let token = data["refreshToken"].as_str().unwrap_or("");
+
+        match crate::auth::refresh_procedure(&self.app, token) {
+          Ok((account, tokens)) => {
             let user = self.app.service("users").get(
               Context::local(),
               account.to_base64(),
               JsonValue::Null,
             )?;
 
+            let email = user["email"].as_str().unwrap_or_default().to_string();
+
             let data = json::object! {
-              accessToken: token,
+              accessToken: tokens.access,
+              refreshToken: tokens.refresh,
               user: user
             };
 
-            let mut ctx_account = ctx.account.write().unwrap();
-            *ctx_account = Account { id: account, email: "-".to_string() };
+            self.login(&ctx, Account { id: account, email }, tokens.session);
 
             Ok(data)
           },
-          Err(msg) => Err(Error::GeneralError(msg)),
+          Err(msg) => Err(Error::NotAuthenticated(msg)),
         }
       },
+      "verification-request" => {
+        let email = data["email"].as_str().unwrap_or("").trim().to_lowercase();
+
+        crate::auth::verification_procedure(&self.app, &email).map_err(Error::GeneralError)?;
+
+        Ok(json::object! { sent: true })
+      },
+      "verification" => {
+        let token = data["token"].as_str().unwrap_or("");
+
+        let account =
+          crate::auth::verify_procedure(&self.app, token).map_err(Error::NotAuthenticated)?;
+
+        Ok(json::object! { _id: account.to_base64(), emailVerified: true })
+      },
+      "password-reset-request" => {
+        let email = data["email"].as_str().unwrap_or("").trim().to_lowercase();
+
+        crate::auth::password_reset_request_procedure(&self.app, &email)
+          .map_err(Error::GeneralError)?;
+
+        Ok(json::object! { sent: true })
+      },
+      "password-reset" => {
+        let token = data["token"].as_str().unwrap_or("");
+        let password = data["password"].as_str().unwrap_or("");
+
+        let account = crate::auth::password_reset_procedure(&self.app, token, password)
+          .map_err(Error::NotAuthenticated)?;
+
+        Ok(json::object! { _id: account.to_base64() })
+      },
       _ => Err(Error::GeneralError(format!("unknown strategy '{strategy}'"))),
     }
   }
@@ -108,7 +213,8 @@ impl Service for Authentication {
 
   fn remove(&self, ctx: Context, _id: String, _params: Params) -> crate::services::Result {
     let account = { ctx.account.read().unwrap().clone() };
-    match crate::auth::logout_procedure(&self.app, account) {
+    let session = { ctx.session.read().unwrap().clone() };
+    match crate::auth::logout_procedure(&self.app, account, session) {
       Ok(_) => Ok(JsonValue::Null),
       Err(msg) => Err(Error::GeneralError(msg)),
     }
@@ -0,0 +1,287 @@
+use chrono::Utc;
+use json::JsonValue;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::commutator::Application;
+use crate::memories::Resolve;
+use crate::services::{Data, Params};
+use crate::storage::barcodes::batch_of;
+use service::error::Error;
+use service::utils::json::JsonParams;
+use service::{Context, Schema, Service};
+use store::barcode;
+use store::elements::ToJson;
+use store::GetWarehouse;
+
+// resolve scanned code to goods or batch with current stock by storages
+// get: id is code, params: { oid }
+// find: params: { oid, code }
+pub struct Barcodes {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl Barcodes {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(Barcodes { app, path: Arc::new("barcodes".to_string()) })
+  }
+
+  fn lookup(&self, code: &str, params: &Params) -> Result<Vec<JsonValue>, Error> {
+    let oid = crate::services::oid(params)?;
+    let ws = self.app.wss.get(&oid);
+
+    let code = code.trim();
+
+    // legacy batch codes without check digit are resolved if such batch is registered
+    let entries = ws.barcodes().resolve(code)?;
+    if entries.is_empty() {
+      if !barcode::is_valid(code) {
+        return Err(Error::BadRequest(format!("invalid barcode `{code}`, check digit mismatch")));
+      }
+      return Ok(vec![]);
+    }
+
+    let storages: Vec<Uuid> = ws
+      .memories(vec!["warehouse".into(), "storage".into()])
+      .list(None)?
+      .into_iter()
+      .filter_map(|doc| doc.json().ok().and_then(|data| data["_uuid"].uuid_or_none()))
+      .collect();
+
+    let database = self.app.warehouse().database;
+    let now = Utc::now();
+
+    let mut result = Vec::with_capacity(entries.len());
+    for entry in entries {
+      let goods = match entry["goods"].uuid_or_none() {
+        Some(goods) => goods,
+        None => continue,
+      };
+      let batch = batch_of(&entry);
+
+      let mut stock: Vec<(Uuid, JsonValue)> = Vec::new();
+      for store in &storages {
+        let balances = database
+          .get_balance_for_store_goods(now, *store, goods)
+          .map_err(|e| Error::GeneralError(e.message()))?;
+
+        for (b, balance) in &balances {
+          if balance.is_zero() || batch.as_ref().map(|batch| batch != b).unwrap_or(false) {
+            continue;
+          }
+          stock.push((
+            *store,
+            json::object! {
+              storage: store.resolve_to_json_object(&ws),
+              batch: b.to_json(),
+              qty: balance.qty.to_json(),
+              cost: balance.cost.to_json(),
+            },
+          ));
+        }
+      }
+      stock.sort_by(|(a, x), (b, y)| {
+        (a, x["batch"]["date"].string()).cmp(&(b, y["batch"]["date"].string()))
+      });
+
+      result.push(json::object! {
+        code: entry["code"].clone(),
+        kind: if batch.is_some() { "batch" } else { "goods" },
+        goods: goods.resolve_to_json_object(&ws),
+        batch: batch.map(|b| b.to_json()).unwrap_or(JsonValue::Null),
+        stock: stock.into_iter().map(|(_, s)| s).collect::<Vec<_>>(),
+      });
+    }
+
+    Ok(result)
+  }
+}
+
+impl Service for Barcodes {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn commands(&self) -> &[&str] {
+    &["find", "get"]
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    Some(Schema::new(
+      json::object! {
+        type: "object",
+        properties: {
+          code: { type: "string", description: "scanned barcode, GTIN or batch code" },
+        },
+      },
+      JsonValue::Null,
+      json::object! {
+        type: "object",
+        properties: {
+          code: { type: "string" },
+          kind: { type: "string", enum: ["goods", "batch"] },
+          goods: { type: "object" },
+          batch: { type: "object" },
+          stock: { type: "array", items: { type: "object" } },
+        },
+      },
+    ))
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let code = self.params(&params)["code"].string();
+
+    let list = self.lookup(&code, &params)?;
+    let total = list.len();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": 0,
+    })
+  }
+
+  fn get(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    self
+      .lookup(&id, &params)?
+      .into_iter()
+      .next()
+      .ok_or_else(|| Error::NotFound(format!("barcode `{id}`")))
+  }
+
+  fn create(&self, _ctx: Context, _data: Data, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn patch(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn remove(&self, _ctx: Context, _id: String, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::memories::MemoriesInFiles;
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use service::Services;
+
+  #[actix_web::test]
+  async fn test_barcodes() {
+    let (tmp_dir, settings, db) = init();
+
+    let (mut app, _) = Application::new(
+      Arc::new(settings),
+      Arc::new(db),
+      Workspaces::new(tmp_dir.path().join("companies")),
+    )
+    .await
+    .unwrap();
+
+    app.register(MemoriesInFiles::new(app.clone(), "memories"));
+    app.register(Barcodes::new(app.clone()));
+
+    let oid = values::ID::random();
+    let memories = app.service("memories");
+
+    let storage = memories
+      .create(
+        Context::local(),
+        json::object! { name: "store" },
+        json::object! { oid: oid.to_base64(), ctx: ["warehouse", "storage"] },
+      )
+      .unwrap();
+
+    let goods = memories
+      .create(
+        Context::local(),
+        json::object! { name: "milk", barcodes: ["4601234567893", "MILK-1"] },
+        json::object! { oid: oid.to_base64(), ctx: ["goods"] },
+      )
+      .unwrap();
+
+    let document = memories
+      .create(
+        Context::local(),
+        json::object! { date: "2023-01-05", storage: storage["_uuid"].string() },
+        json::object! { oid: oid.to_base64(), ctx: ["warehouse", "receive", "document"] },
+      )
+      .unwrap();
+
+    memories
+      .create(
+        Context::local(),
+        json::object! {
+          document: document["_uuid"].string(),
+          goods: goods["_uuid"].string(),
+          qty: { number: 3 },
+          cost: { number: 30 },
+        },
+        json::object! { oid: oid.to_base64(), ctx: ["warehouse", "receive"] },
+      )
+      .unwrap();
+
+    let barcodes = app.service("barcodes");
+    let params = json::object! { oid: oid.to_base64() };
+
+    let result = barcodes.get(Context::local(), "4601234567893".into(), params.clone()).unwrap();
+    assert_eq!(result["kind"], "goods");
+    assert_eq!(result["goods"]["name"], "milk");
+    assert_eq!(result["stock"].len(), 1);
+    assert_eq!(result["stock"][0]["storage"]["name"], "store");
+    assert_eq!(result["stock"][0]["qty"], "3");
+
+    let batch_code = result["stock"][0]["batch"]["barcode"].string();
+    let result = barcodes.get(Context::local(), batch_code.clone(), params.clone()).unwrap();
+    assert_eq!(result["kind"], "batch");
+    assert_eq!(result["goods"]["_uuid"], goods["_uuid"]);
+    assert_eq!(result["stock"].len(), 1);
+
+    // printed before check digit was added
+    let result = barcodes.get(Context::local(), batch_code[..12].into(), params.clone()).unwrap();
+    assert_eq!(result["kind"], "batch");
+    match barcodes.get(Context::local(), "200000000000".into(), params.clone()) {
+      Err(Error::BadRequest(_)) => {},
+      result => panic!("unexpected {result:?}"),
+    }
+
+    let result = barcodes
+      .find(Context::local(), json::object! { oid: oid.to_base64(), code: "MILK-1" })
+      .unwrap();
+    assert_eq!(result["total"], 1);
+
+    // wrong check digit
+    match barcodes.get(Context::local(), "4601234567890".into(), params.clone()) {
+      Err(Error::BadRequest(_)) => {},
+      result => panic!("unexpected {result:?}"),
+    }
+
+    match barcodes.get(Context::local(), "96385074".into(), params) {
+      Err(Error::NotFound(_)) => {},
+      result => panic!("unexpected {result:?}"),
+    }
+
+    tmp_dir.close().unwrap();
+  }
+}
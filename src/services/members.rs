@@ -0,0 +1,184 @@
+use json::JsonValue;
+use std::sync::Arc;
+
+use crate::commutator::Application;
+use crate::services::{Data, Params};
+use crate::storage::organizations::Workspace;
+use crate::storage::roles::{OWNER, VIEWER};
+use service::error::Error;
+use service::{Account, Context, Schema, Service};
+use values::ID;
+
+// accounts with access to workspace
+// create: { email, roles: ["viewer"] } with params { oid }
+// patch: { roles: ["storekeeper"] } with params { oid }
+// remove: account id with params { oid }
+pub struct Members {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl Members {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(Members { app, path: Arc::new("members".to_string()) })
+  }
+}
+
+impl Service for Members {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "create", "patch", "remove"]
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    Some(Schema::new(
+      JsonValue::Null,
+      json::object! {
+        type: "object",
+        required: ["email"],
+        properties: {
+          email: { type: "string", format: "email" },
+          roles: { type: "array", items: { type: "string" } },
+        },
+      },
+      json::object! {
+        type: "object",
+        properties: {
+          _id: { type: "string" },
+          email: { type: "string" },
+          roles: { type: "array", items: { type: "string" } },
+          added: { type: "string", format: "date-time" },
+        },
+      },
+    ))
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let list = self.app.wss.get(&oid).members().list()?;
+    let total = list.len();
+
+    let list: Vec<JsonValue> = list.into_iter().skip(skip).take(limit).collect();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    let id = crate::services::string_to_id(id)?;
+
+    match self.app.wss.get(&oid).members().get(&id)? {
+      Some(mut member) => {
+        member["_id"] = id.to_base64().into();
+        Ok(member)
+      },
+      None => Err(Error::NotFound(format!("member {}", id.to_base64()))),
+    }
+  }
+
+  fn create(&self, _ctx: Context, data: Data, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let email = data["email"].as_str().unwrap_or_default().trim().to_lowercase();
+    if email.is_empty() {
+      return Err(Error::GeneralError("email can't be empty".into()));
+    }
+
+    let account = Account { id: ID::from(email.as_str()), email };
+
+    let ws = self.app.wss.get(&oid);
+
+    let roles =
+      if data["roles"].is_null() { vec![VIEWER.to_string()] } else { roles(&ws, &data["roles"])? };
+
+    ws.members().add(&account, &roles)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn patch(&self, _ctx: Context, id: String, data: Data, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    let id = crate::services::string_to_id(id)?;
+
+    let ws = self.app.wss.get(&oid);
+    let roles = roles(&ws, &data["roles"])?;
+
+    if !roles.iter().any(|r| r == OWNER) && is_last_owner(&ws, &id)? {
+      return Err(Error::Conflict("workspace must have an owner".into()));
+    }
+
+    ws.members().set_roles(&id, &roles)
+  }
+
+  fn remove(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    let id = crate::services::string_to_id(id)?;
+
+    let ws = self.app.wss.get(&oid);
+    if is_last_owner(&ws, &id)? {
+      return Err(Error::Conflict("can't remove last owner of workspace".into()));
+    }
+
+    ws.members().remove(&id)
+  }
+}
+
+fn roles(ws: &Workspace, data: &JsonValue) -> Result<Vec<String>, Error> {
+  if !data.is_array() {
+    return Err(Error::GeneralError("`roles` must be an array".into()));
+  }
+
+  let mut roles = Vec::new();
+  for role in data.members() {
+    let role = role.as_str().unwrap_or_default();
+    if !ws.roles().exists(role)? {
+      return Err(Error::NotFound(format!("role `{role}`")));
+    }
+    roles.push(role.to_string());
+  }
+  Ok(roles)
+}
+
+fn is_last_owner(ws: &Workspace, id: &ID) -> Result<bool, Error> {
+  let members = ws.members();
+
+  let is_owner = |id: &ID| -> Result<bool, Error> {
+    Ok(
+      members
+        .roles_of(id)?
+        .map(|roles| roles.iter().any(|r| r == OWNER))
+        .unwrap_or(false),
+    )
+  };
+
+  if !is_owner(id)? {
+    return Ok(false);
+  }
+
+  for member in members.list()? {
+    let other = crate::services::string_to_id(member["_id"].to_string())?;
+    if &other != id && is_owner(&other)? {
+      return Ok(false);
+    }
+  }
+  Ok(true)
+}
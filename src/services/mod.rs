@@ -1,12 +1,24 @@
+mod audit;
 mod authentication;
+mod barcodes;
+mod members;
 mod people;
 pub(crate) mod persistent;
+mod roles;
+mod sessions;
+mod synonyms;
 mod users;
 
+pub use audit::Audit;
 pub use authentication::Authentication;
+pub use barcodes::Barcodes;
 use json::JsonValue;
+pub use members::Members;
 pub use people::People;
+pub use roles::Roles;
 use service::error::Error;
+pub use sessions::Sessions;
+pub use synonyms::Synonyms;
 pub use users::Users;
 use values::ID;
 
@@ -25,11 +37,11 @@ pub enum Mutation {
 
 #[derive(Debug)]
 pub enum Event {
-  // service name, data
-  Created(String, Data),
-  Updated(String, Data),
-  Patched(String, Data),
-  Removed(String, Data),
+  // service name, data, params of mutation (workspace and context of event)
+  Created(String, Data, Params),
+  Updated(String, Data, Params),
+  Patched(String, Data, Params),
+  Removed(String, Data, Params),
 }
 
 pub fn id(name: &str, params: &Params) -> std::result::Result<ID, Error> {
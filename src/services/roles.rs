@@ -0,0 +1,97 @@
+use json::JsonValue;
+use std::sync::Arc;
+
+use crate::commutator::Application;
+use crate::services::{Data, Params};
+use service::error::Error;
+use service::{Context, Service};
+
+// roles of workspace, role name is `_id`
+// create: { _id: "storekeeper", rules: [{ service: "memories", ctx: "warehouse/receive", commands: ["create"], effect: "allow" }] }
+// params: { oid }
+pub struct Roles {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl Roles {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(Roles { app, path: Arc::new("roles".to_string()) })
+  }
+}
+
+impl Service for Roles {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let list = self.app.wss.get(&oid).roles().list()?;
+    let total = list.len();
+
+    let list: Vec<JsonValue> = list.into_iter().skip(skip).take(limit).collect();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    match self.app.wss.get(&oid).roles().get(&id)? {
+      Some(role) => Ok(role),
+      None => Err(Error::NotFound(format!("role `{id}`"))),
+    }
+  }
+
+  fn create(&self, _ctx: Context, data: Data, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let name = data["_id"].as_str().unwrap_or_default();
+
+    let roles = self.app.wss.get(&oid).roles();
+    if roles.exists(name)? {
+      return Err(Error::Conflict(format!("role `{name}` already exist")));
+    }
+
+    roles.save(name, &data["rules"])
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    id: String,
+    data: Data,
+    params: Params,
+  ) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    self.app.wss.get(&oid).roles().save(&id, &data["rules"])
+  }
+
+  fn patch(&self, ctx: Context, id: String, data: Data, params: Params) -> crate::services::Result {
+    self.update(ctx, id, data, params)
+  }
+
+  fn remove(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let ws = self.app.wss.get(&oid);
+
+    for member in ws.members().list()? {
+      if member["roles"].members().any(|r| r == id.as_str()) {
+        return Err(Error::Conflict(format!("role `{id}` is assigned to {}", member["email"])));
+      }
+    }
+
+    ws.roles().remove(&id)
+  }
+}
@@ -0,0 +1,125 @@
+use json::JsonValue;
+use std::sync::Arc;
+
+use crate::commutator::Application;
+use crate::services::{Data, Params};
+use service::error::Error;
+use service::{Context, Schema, Service};
+
+// login sessions of current account
+// find: active sessions, `current` mark session of context
+// remove: revoke session by id
+pub struct Sessions {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl Sessions {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(Sessions { app, path: Arc::new("sessions".to_string()) })
+  }
+}
+
+impl Service for Sessions {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "remove"]
+  }
+
+  fn schema(&self) -> Option<Schema> {
+    Some(Schema::new(
+      JsonValue::Null,
+      JsonValue::Null,
+      json::object! {
+        type: "object",
+        properties: {
+          _id: { type: "string", format: "uuid" },
+          device: { type: "string" },
+          issued: { type: "string", format: "date-time" },
+          last_used: { type: "string", format: "date-time" },
+          expires: { type: "string", format: "date-time" },
+          current: { type: "boolean" },
+        },
+      },
+    ))
+  }
+
+  fn find(&self, ctx: Context, params: Params) -> crate::services::Result {
+    let account = crate::access::authenticate(&self.app, &ctx)?;
+    let current = { ctx.session.read().unwrap().clone() };
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let list = self.app.sessions.list(&account.id)?;
+    let total = list.len();
+
+    let list: Vec<JsonValue> = list
+      .into_iter()
+      .skip(skip)
+      .take(limit)
+      .map(|mut session| {
+        session["current"] = (session["_id"].as_str() == current.as_deref()).into();
+        session
+      })
+      .collect();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, ctx: Context, id: String, _params: Params) -> crate::services::Result {
+    let account = crate::access::authenticate(&self.app, &ctx)?;
+
+    match self
+      .app
+      .sessions
+      .list(&account.id)?
+      .into_iter()
+      .find(|s| s["_id"] == id.as_str())
+    {
+      Some(session) => Ok(session),
+      None => Err(Error::NotFound(format!("session {id}"))),
+    }
+  }
+
+  fn create(&self, _ctx: Context, _data: Data, _params: Params) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn patch(
+    &self,
+    _ctx: Context,
+    _id: String,
+    _data: Data,
+    _params: Params,
+  ) -> crate::services::Result {
+    Err(Error::NotImplemented)
+  }
+
+  fn remove(&self, ctx: Context, id: String, _params: Params) -> crate::services::Result {
+    let account = crate::access::authenticate(&self.app, &ctx)?;
+
+    if uuid::Uuid::parse_str(&id).is_err() {
+      return Err(Error::NotFound(format!("session {id}")));
+    }
+
+    self.app.sessions.revoke(&account.id, &id)
+  }
+}
@@ -0,0 +1,107 @@
+use json::JsonValue;
+use std::sync::Arc;
+
+use crate::commutator::Application;
+use crate::services::{Data, Params};
+use crate::storage::organizations::Workspace;
+use service::error::Error;
+use service::{Context, Service};
+
+// synonym groups of workspace search, words or phrases of group find each other
+// create: { words: ["аспирин", "ацетилсалициловая кислота"] }
+// params: { oid }
+pub struct Synonyms {
+  app: Application,
+  path: Arc<String>,
+}
+
+impl Synonyms {
+  pub fn new(app: Application) -> Arc<dyn Service> {
+    Arc::new(Synonyms { app, path: Arc::new("synonyms".to_string()) })
+  }
+
+  fn reload(&self, ws: &Workspace) -> Result<(), Error> {
+    let mut search = self.app.search.write().unwrap();
+    search
+      .reload_synonyms(ws)
+      .map_err(|e| Error::GeneralError(format!("search index: {e:?}")))
+  }
+}
+
+impl Service for Synonyms {
+  fn path(&self) -> &str {
+    &self.path
+  }
+
+  fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    let limit = self.limit(&params);
+    let skip = self.skip(&params);
+
+    let list = self.app.wss.get(&oid).synonyms().list()?;
+    let total = list.len();
+
+    let list: Vec<JsonValue> = list.into_iter().skip(skip).take(limit).collect();
+
+    Ok(json::object! {
+      data: JsonValue::Array(list),
+      total: total,
+      "$skip": skip,
+    })
+  }
+
+  fn get(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+
+    self
+      .app
+      .wss
+      .get(&oid)
+      .synonyms()
+      .list()?
+      .into_iter()
+      .find(|group| group["_id"] == id.as_str())
+      .ok_or_else(|| Error::NotFound(format!("synonyms `{id}`")))
+  }
+
+  fn create(&self, _ctx: Context, data: Data, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    let ws = self.app.wss.get(&oid);
+
+    let group = ws.synonyms().save(None, &data["words"])?;
+    self.reload(&ws)?;
+
+    Ok(group)
+  }
+
+  fn update(
+    &self,
+    _ctx: Context,
+    id: String,
+    data: Data,
+    params: Params,
+  ) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    let ws = self.app.wss.get(&oid);
+
+    let group = ws.synonyms().save(Some(&id), &data["words"])?;
+    self.reload(&ws)?;
+
+    Ok(group)
+  }
+
+  fn patch(&self, ctx: Context, id: String, data: Data, params: Params) -> crate::services::Result {
+    self.update(ctx, id, data, params)
+  }
+
+  fn remove(&self, _ctx: Context, id: String, params: Params) -> crate::services::Result {
+    let oid = crate::services::oid(&params)?;
+    let ws = self.app.wss.get(&oid);
+
+    let group = ws.synonyms().remove(&id)?;
+    self.reload(&ws)?;
+
+    Ok(group)
+  }
+}
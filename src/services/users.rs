@@ -84,6 +84,10 @@ impl Service for Users {
     &self.path
   }
 
+  fn commands(&self) -> &[&str] {
+    &["find", "get", "create", "update", "patch"]
+  }
+
   fn find(&self, _ctx: Context, params: Params) -> crate::services::Result {
     let limit = self.limit(&params);
     let skip = self.skip(&params);
@@ -114,6 +118,9 @@ impl Service for Users {
       }
     };
 
+    let mut obj = obj;
+    obj["emailVerified"] = auth::is_verified(&self.app, &id).unwrap_or(false).into();
+
     Ok(obj)
 
     // let names = ["label", "email", "avatar"];
@@ -164,9 +171,10 @@ impl Service for Users {
     let signup = crate::auth::SignUpRequest { email: email.clone(), password };
 
     match auth::signup_procedure(&self.app, signup) {
-      Ok((account, token)) => Ok(json::object! {
+      Ok((account, tokens)) => Ok(json::object! {
         _id: account.to_base64(),
-        accessToken: token,
+        accessToken: tokens.access,
+        refreshToken: tokens.refresh,
         email: email,
       }),
       Err(msg) => Err(Error::IOError(msg)),
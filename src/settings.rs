@@ -1,5 +1,6 @@
 use config::{Config, ConfigError, File};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
 
@@ -7,6 +8,23 @@ use std::path::PathBuf;
 pub(crate) struct Database {
   pub memory: PathBuf,
   pub inventory: PathBuf,
+  #[serde(default = "default_sessions")]
+  pub sessions: PathBuf,
+}
+
+impl Database {
+  /// databases inside of data folder
+  pub(crate) fn at(folder: &std::path::Path) -> Self {
+    Database {
+      memory: folder.join("memory"),
+      inventory: folder.join("inventory"),
+      sessions: folder.join("sessions"),
+    }
+  }
+}
+
+fn default_sessions() -> PathBuf {
+  PathBuf::from("./data/sessions")
 }
 
 #[derive(Debug, Deserialize)]
@@ -16,11 +34,127 @@ pub struct JWTConfig {
   pub(crate) secret: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MailConfig {
+  // `smtp`, `file` or `log`
+  pub(crate) transport: String,
+  pub(crate) from: String,
+  // link base for verification and reset mails
+  #[serde(default)]
+  pub(crate) base_url: String,
+  // `file` transport
+  #[serde(default)]
+  pub(crate) folder: PathBuf,
+  // `smtp` transport
+  #[serde(default)]
+  pub(crate) host: String,
+  #[serde(default = "default_smtp_port")]
+  pub(crate) port: u16,
+  #[serde(default)]
+  pub(crate) username: String,
+  #[serde(default)]
+  pub(crate) password: String,
+}
+
+fn default_smtp_port() -> u16 {
+  587
+}
+
+impl Default for MailConfig {
+  fn default() -> Self {
+    MailConfig {
+      transport: "log".into(),
+      from: "Nae <noreply@localhost>".into(),
+      base_url: String::new(),
+      folder: PathBuf::from("./data/mails"),
+      host: String::new(),
+      port: default_smtp_port(),
+      username: String::new(),
+      password: String::new(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+  // `authentication` create per minute from one ip and for one email
+  pub(crate) login_per_minute: u32,
+  // service commands per minute from one ip and for one account
+  pub(crate) commands_per_minute: u32,
+  // bytes of uploaded picture
+  pub(crate) picture_size: usize,
+  // bytes of websocket frame or polling request
+  pub(crate) message_size: usize,
+}
+
+impl Default for LimitsConfig {
+  fn default() -> Self {
+    LimitsConfig {
+      login_per_minute: 10,
+      commands_per_minute: 600,
+      picture_size: 10 * 1024 * 1024,
+      message_size: 1_000_000,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+  // indexed fields of contexts without own list,
+  // `a.b` is field `b` of object or referred by uuid document at `a`
+  pub(crate) fields: Vec<String>,
+  // by context path, for example "warehouse/receive"
+  pub(crate) contexts: HashMap<String, Vec<String>>,
+}
+
+impl SearchConfig {
+  pub(crate) fn fields(&self, ctx: &[String]) -> &[String] {
+    self.contexts.get(&ctx.join("/")).unwrap_or(&self.fields)
+  }
+}
+
+impl Default for SearchConfig {
+  fn default() -> Self {
+    SearchConfig {
+      fields: ["name", "code", "barcode", "counterparty.name", "notes"]
+        .iter()
+        .map(|f| f.to_string())
+        .collect(),
+      contexts: HashMap::new(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HikConfig {
+  // register cameras, events and attendance services
+  pub(crate) enabled: bool,
+  // key of camera credentials encryption
+  pub(crate) secret: String,
+}
+
+impl HikConfig {
+  pub(crate) fn secret(&self) -> crate::hik::Secret {
+    crate::hik::Secret::new(&self.secret)
+  }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Settings {
   pub(crate) debug: bool,
   pub(crate) jwt_config: JWTConfig,
   pub(crate) database: Database,
+  #[serde(default)]
+  pub(crate) mail: MailConfig,
+  #[serde(default)]
+  pub(crate) limits: LimitsConfig,
+  #[serde(default)]
+  pub(crate) search: SearchConfig,
+  #[serde(default)]
+  pub(crate) hik: HikConfig,
 }
 
 impl Settings {
@@ -32,7 +166,15 @@ impl Settings {
         issuer: "Nae".into(),
         secret: "1234567890".into(),
       },
-      database: Database { memory: folder.join("memory"), inventory: folder.join("inventory") },
+      database: Database::at(&folder),
+      mail: MailConfig {
+        transport: "file".into(),
+        folder: folder.join("mails"),
+        ..MailConfig::default()
+      },
+      limits: LimitsConfig::default(),
+      search: SearchConfig::default(),
+      hik: HikConfig { enabled: true, secret: "0987654321".into() },
     }
   }
 
@@ -0,0 +1,232 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use json::JsonValue;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::storage::organizations::Workspace;
+use service::error::Error;
+use service::utils::time::time_to_string;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+// fields that never go to audit
+const SECRETS: [&str; 3] = ["password", "password_hash", "token"];
+
+// length of non-scalar value in summary
+const SUMMARY_LENGTH: usize = 120;
+
+/// Who changed what in workspace.
+///
+/// layout: audit/<yyyy-mm-dd>.jsonl - one record per line
+#[derive(Clone)]
+pub(crate) struct SAudit {
+  pub(crate) ws: Workspace,
+
+  pub(crate) folder: PathBuf,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct AuditFilter {
+  pub(crate) account: Option<String>,
+  pub(crate) document: Option<String>,
+  pub(crate) from: Option<NaiveDate>,
+  pub(crate) till: Option<NaiveDate>,
+}
+
+impl AuditFilter {
+  fn matches(&self, record: &JsonValue) -> bool {
+    let account = match &self.account {
+      Some(account) => {
+        record["account"]["_id"] == account.as_str()
+          || record["account"]["email"] == account.as_str()
+      },
+      None => true,
+    };
+    let document = match &self.document {
+      Some(document) => record["document"] == document.as_str(),
+      None => true,
+    };
+    account && document
+  }
+}
+
+impl SAudit {
+  fn path(&self, date: NaiveDate) -> PathBuf {
+    let mut path = self.folder.clone();
+    path.push(format!("{}.jsonl", date.format("%Y-%m-%d")));
+    path
+  }
+
+  pub(crate) fn append(&self, mut record: JsonValue) -> Result<(), Error> {
+    let now = Utc::now();
+    record["time"] = time_to_string(now).into();
+
+    let _lock = LOCK.lock().unwrap();
+
+    std::fs::create_dir_all(&self.folder).map_err(|e| {
+      Error::IOError(format!("can't create folder {}: {}", self.folder.to_string_lossy(), e))
+    })?;
+
+    let mut file = std::fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(self.path(now.date_naive()))
+      .map_err(|e| Error::IOError(format!("fail to open for append file: {}", e)))?;
+
+    file
+      .write_all(format!("{}\n", record.dump()).as_bytes())
+      .map_err(|e| Error::IOError(format!("fail to write file: {}", e)))
+  }
+
+  /// records matching filter, newest first
+  pub(crate) fn find(
+    &self,
+    filter: &AuditFilter,
+    skip: usize,
+    limit: usize,
+  ) -> Result<(Vec<JsonValue>, usize), Error> {
+    let mut days: Vec<NaiveDate> = match std::fs::read_dir(&self.folder) {
+      Ok(entries) => entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+          let name = entry.file_name().to_string_lossy().to_string();
+          NaiveDate::parse_from_str(name.strip_suffix(".jsonl")?, "%Y-%m-%d").ok()
+        })
+        .filter(|day| filter.from.map(|from| *day >= from).unwrap_or(true))
+        .filter(|day| filter.till.map(|till| *day <= till).unwrap_or(true))
+        .collect(),
+      Err(_) => return Ok((vec![], 0)),
+    };
+    days.sort_by(|a, b| b.cmp(a));
+
+    let mut total = 0;
+    let mut result = Vec::new();
+    for day in days {
+      let file = std::fs::File::open(self.path(day))
+        .map_err(|e| Error::IOError(format!("fail to open file: {}", e)))?;
+
+      let mut records = Vec::new();
+      for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| Error::IOError(e.to_string()))?;
+        if line.is_empty() {
+          continue;
+        }
+        let record = json::parse(&line).map_err(|e| Error::IOError(e.to_string()))?;
+        if filter.matches(&record) {
+          records.push(record);
+        }
+      }
+
+      for record in records.into_iter().rev() {
+        if total >= skip && result.len() < limit {
+          result.push(record);
+        }
+        total += 1;
+      }
+    }
+
+    Ok((result, total))
+  }
+
+  /// changed top-level fields as { field: { before, after } }
+  pub(crate) fn summary(before: &JsonValue, after: &JsonValue) -> JsonValue {
+    let mut summary = JsonValue::new_object();
+
+    let mut fields: Vec<&str> = before.entries().map(|(k, _)| k).collect();
+    for (k, _) in after.entries() {
+      if !fields.contains(&k) {
+        fields.push(k);
+      }
+    }
+
+    for field in fields {
+      if before[field] == after[field] {
+        continue;
+      }
+      if SECRETS.contains(&field) {
+        summary[field] = json::object! { before: "***", after: "***" };
+      } else {
+        summary[field] = json::object! { before: SAudit::short(&before[field]), after: SAudit::short(&after[field]) };
+      }
+    }
+
+    summary
+  }
+
+  fn short(value: &JsonValue) -> JsonValue {
+    if value.is_object() || value.is_array() {
+      let dump = value.dump();
+      if dump.len() > SUMMARY_LENGTH {
+        let mut end = SUMMARY_LENGTH;
+        while !dump.is_char_boundary(end) {
+          end -= 1;
+        }
+        return format!("{}…", &dump[..end]).into();
+      }
+    }
+    value.clone()
+  }
+
+  pub(crate) fn date(value: &JsonValue) -> Result<Option<NaiveDate>, Error> {
+    match value.as_str() {
+      None | Some("") => Ok(None),
+      Some(str) => match NaiveDate::parse_from_str(str, "%Y-%m-%d") {
+        Ok(date) => Ok(Some(date)),
+        Err(_) => DateTime::parse_from_rfc3339(str)
+          .map(|dt| Some(dt.with_timezone(&Utc).date_naive()))
+          .map_err(|e| Error::GeneralError(format!("invalid date {str}: {e}"))),
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::Workspaces;
+  use tempfile::tempdir;
+  use values::ID;
+
+  #[test]
+  fn test_append_and_find() {
+    let tmp_dir = tempdir().unwrap();
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let audit = wss.get(&ID::random()).audit();
+
+    let before = json::object! { _id: "doc1", name: "a", qty: 1, password: "x" };
+    let after = json::object! { _id: "doc1", name: "b", qty: 1, password: "y" };
+
+    let summary = SAudit::summary(&before, &after);
+    assert_eq!(summary["name"], json::object! { before: "a", after: "b" });
+    assert!(summary["qty"].is_null());
+    assert_eq!(summary["password"]["after"], "***");
+
+    for (email, document) in [("a@nae.org", "doc1"), ("b@nae.org", "doc1"), ("a@nae.org", "doc2")] {
+      let record = json::object! {
+        account: { _id: ID::from(email).to_base64(), email: email },
+        service: "memories",
+        command: "patch",
+        document: document,
+      };
+      audit.append(record).unwrap();
+    }
+
+    let all = AuditFilter::default();
+    let (records, total) = audit.find(&all, 0, 10).unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(records[0]["document"], "doc2");
+
+    let by_account = AuditFilter { account: Some("a@nae.org".into()), ..AuditFilter::default() };
+    assert_eq!(audit.find(&by_account, 0, 10).unwrap().1, 2);
+
+    let by_document = AuditFilter { document: Some("doc1".into()), ..AuditFilter::default() };
+    let (records, total) = audit.find(&by_document, 1, 10).unwrap();
+    assert_eq!(total, 2);
+    assert_eq!(records[0]["account"]["email"], "a@nae.org");
+
+    let tomorrow = Utc::now().date_naive().succ_opt();
+    let future = AuditFilter { from: tomorrow, ..AuditFilter::default() };
+    assert_eq!(audit.find(&future, 0, 10).unwrap().1, 0);
+  }
+}
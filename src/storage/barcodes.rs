@@ -0,0 +1,219 @@
+use json::JsonValue;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::storage::organizations::Workspace;
+use crate::storage::{load, save};
+use service::error::Error;
+use service::utils::json::JsonParams;
+use store::barcode;
+use store::batch::Batch;
+use store::elements::ToJson;
+use store::operations::{InternalOperation, OpMutation};
+use values::ID;
+
+/// Registry of barcodes: codes of goods (`barcode` and `barcodes` fields)
+/// and codes of batches issued at receive.
+///
+/// layout: barcodes/<code hash>/<owner hash>.json
+#[derive(Clone)]
+pub(crate) struct SBarcodes {
+  pub(crate) ws: Workspace,
+
+  pub(crate) folder: PathBuf,
+}
+
+impl SBarcodes {
+  /// codes of goods document; deleted goods have none
+  pub(crate) fn codes_of(data: &JsonValue) -> BTreeSet<String> {
+    let mut codes = BTreeSet::new();
+
+    if !data.is_object() || data["status"].string() == "deleted" {
+      return codes;
+    }
+
+    let values = data["barcodes"].members().chain(std::iter::once(&data["barcode"]));
+    for code in values.filter_map(|v| v.as_str()).map(|v| v.trim()) {
+      if barcode::is_valid(code) {
+        codes.insert(code.to_string());
+      } else if !code.is_empty() {
+        log::warn!("goods {}: invalid barcode `{code}`", data["_uuid"].string());
+      }
+    }
+
+    codes
+  }
+
+  fn path(&self, code: &str, owner: &str) -> PathBuf {
+    let mut path = self.folder.clone();
+    path.push(ID::from(code).to_base64());
+    path.push(format!("{}.json", ID::from(owner).to_base64()));
+    path
+  }
+
+  fn add(&self, code: &str, owner: &str, entry: JsonValue) -> Result<(), Error> {
+    let mut entry = entry;
+    entry["code"] = code.into();
+    save(&self.path(code, owner), entry.dump())
+  }
+
+  fn remove(&self, code: &str, owner: &str) -> Result<(), Error> {
+    let path = self.path(code, owner);
+    if path.exists() {
+      std::fs::remove_file(&path).map_err(|e| {
+        Error::IOError(format!("can't remove barcode {}: {}", path.to_string_lossy(), e))
+      })?;
+    }
+    Ok(())
+  }
+
+  /// record changes of goods codes between two revisions of document
+  pub(crate) fn update_goods(&self, before: &JsonValue, after: &JsonValue) -> Result<(), Error> {
+    let goods = match after["_uuid"].string_or_none().or_else(|| before["_uuid"].string_or_none()) {
+      Some(goods) => goods,
+      None => return Ok(()),
+    };
+
+    let before = SBarcodes::codes_of(before);
+    let after = SBarcodes::codes_of(after);
+
+    for code in before.difference(&after) {
+      self.remove(code, &goods)?;
+    }
+    for code in after.difference(&before) {
+      self.add(code, &goods, json::object! { goods: goods.clone() })?;
+    }
+
+    Ok(())
+  }
+
+  /// index batch codes of receive operations
+  pub(crate) fn update_batches(&self, ops: &[OpMutation]) -> Result<(), Error> {
+    let is_receive =
+      |op: &Option<InternalOperation>| matches!(op, Some(InternalOperation::Receive(..)));
+
+    for op in ops {
+      if op.batch.is_empty() || is_receive(&op.before) == is_receive(&op.after) {
+        continue;
+      }
+
+      let code = op.batch.to_barcode();
+      let owner = format!("{}/{}", op.goods, op.batch.id);
+
+      if is_receive(&op.after) {
+        let entry = json::object! { goods: op.goods.to_string(), batch: op.batch.to_json() };
+        self.add(&code, &owner, entry)?;
+      } else {
+        self.remove(&code, &owner)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// entries of scanned code, batch codes without check digit are accepted
+  pub(crate) fn resolve(&self, code: &str) -> Result<Vec<JsonValue>, Error> {
+    let mut result = self.entries(code)?;
+
+    if result.is_empty() && barcode::is_legacy_batch(code) {
+      if let Some(digit) = barcode::check_digit(code) {
+        result = self.entries(&format!("{code}{digit}"))?;
+      }
+    }
+
+    Ok(result)
+  }
+
+  fn entries(&self, code: &str) -> Result<Vec<JsonValue>, Error> {
+    let mut folder = self.folder.clone();
+    folder.push(ID::from(code).to_base64());
+
+    let entries = match std::fs::read_dir(&folder) {
+      Ok(entries) => entries,
+      Err(_) => return Ok(vec![]),
+    };
+
+    let mut result = Vec::new();
+    for entry in entries {
+      let path = entry.map_err(|e| Error::IOError(e.to_string()))?.path();
+      if path.is_file() {
+        result.push(load(&path)?);
+      }
+    }
+
+    // goods codes first, batches by date
+    result.sort_by(|a, b| {
+      let key =
+        |e: &JsonValue| (e["batch"].is_object(), e["batch"]["date"].string(), e["goods"].string());
+      key(a).cmp(&key(b))
+    });
+
+    Ok(result)
+  }
+}
+
+/// batch of registry entry
+pub(crate) fn batch_of(entry: &JsonValue) -> Option<Batch> {
+  if !entry["batch"].is_object() {
+    return None;
+  }
+  let id = entry["batch"]["id"].uuid_or_none()?;
+  let date = entry["batch"]["date"].date_with_check().ok()?;
+  Some(Batch { id, date })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::Workspaces;
+  use chrono::{TimeZone, Utc};
+  use tempfile::tempdir;
+  use uuid::Uuid;
+
+  #[test]
+  fn test_goods_and_batches() {
+    let tmp_dir = tempdir().unwrap();
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let barcodes = wss.get(&ID::random()).barcodes();
+
+    let goods = Uuid::new_v4();
+    let before = json::object! {
+      _uuid: goods.to_string(),
+      barcode: "4601234567893",
+      barcodes: ["INT-7", "4601234567890"],
+    };
+    barcodes.update_goods(&JsonValue::Null, &before).unwrap();
+
+    assert_eq!(barcodes.resolve("4601234567893").unwrap()[0]["goods"], goods.to_string());
+    assert_eq!(barcodes.resolve("INT-7").unwrap().len(), 1);
+    // wrong check digit isn't registered
+    assert!(barcodes.resolve("4601234567890").unwrap().is_empty());
+
+    let mut after = before.clone();
+    after["barcodes"] = json::array!["96385074"];
+    barcodes.update_goods(&before, &after).unwrap();
+    assert!(barcodes.resolve("INT-7").unwrap().is_empty());
+    assert_eq!(barcodes.resolve("96385074").unwrap().len(), 1);
+
+    let batch =
+      Batch { id: Uuid::new_v4(), date: Utc.with_ymd_and_hms(2023, 2, 17, 0, 0, 0).unwrap() };
+    let mut op = OpMutation::default();
+    op.goods = goods;
+    op.batch = batch.clone();
+    op.after = Some(InternalOperation::Receive(1.into(), 10.into()));
+    barcodes.update_batches(&[op.clone()]).unwrap();
+
+    let code = batch.to_barcode();
+    let entries = barcodes.resolve(&code).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(batch_of(&entries[0]), Some(batch.clone()));
+    // printed before check digit was added
+    assert_eq!(barcodes.resolve(&code[..12]).unwrap().len(), 1);
+
+    op.before = op.after.take();
+    barcodes.update_batches(&[op]).unwrap();
+    assert!(barcodes.resolve(&code).unwrap().is_empty());
+
+    tmp_dir.close().unwrap();
+  }
+}
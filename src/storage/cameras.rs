@@ -23,10 +23,13 @@ impl SCamera {
     Ok(self)
   }
 
-  pub(crate) fn config(&self) -> Result<crate::hik::ConfigCamera, Error> {
+  pub(crate) fn config(
+    &self,
+    secret: &crate::hik::Secret,
+  ) -> Result<crate::hik::ConfigCamera, Error> {
     let contents = self.data()?;
 
-    serde_json::from_str(contents.as_str()).map_err(|e| Error::IOError(e.to_string()))
+    crate::hik::ConfigCamera::from_data(&contents, secret).map_err(|e| Error::IOError(e.to_string()))
   }
 
   // pub(crate) fn load(&self) -> crate::services::Result {
@@ -0,0 +1,179 @@
+use chrono::Utc;
+use json::JsonValue;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use crate::storage::organizations::Workspace;
+use service::error::Error;
+use service::utils::time::time_to_string;
+use service::Account;
+use values::ID;
+
+// records per segment file
+const SEGMENT: u64 = 10_000;
+
+/// Append-only log of memories mutations.
+///
+/// layout: changes/sequence - last sequence number
+///         changes/<seq / SEGMENT>.jsonl - one change per line
+#[derive(Clone)]
+pub(crate) struct SChanges {
+  pub(crate) ws: Workspace,
+
+  pub(crate) folder: PathBuf,
+}
+
+impl SChanges {
+  pub(crate) fn hash(data: &JsonValue) -> JsonValue {
+    if data.is_null() {
+      JsonValue::Null
+    } else {
+      ID::from(data.dump()).to_base64().into()
+    }
+  }
+
+  fn sequence_path(&self) -> PathBuf {
+    let mut path = self.folder.clone();
+    path.push("sequence");
+    path
+  }
+
+  fn segment_path(&self, segment: u64) -> PathBuf {
+    let mut path = self.folder.clone();
+    path.push(format!("{segment:0>10}.jsonl"));
+    path
+  }
+
+  /// last written sequence number, 0 if log is empty
+  pub(crate) fn last(&self) -> Result<u64, Error> {
+    match std::fs::read_to_string(self.sequence_path()) {
+      Ok(data) => data
+        .trim()
+        .parse::<u64>()
+        .map_err(|e| Error::IOError(format!("corrupted changes sequence: {e}"))),
+      Err(_) => Ok(0),
+    }
+  }
+
+  /// caller must hold memories lock
+  pub(crate) fn append(
+    &self,
+    ctx: &Vec<String>,
+    before: &JsonValue,
+    after: &JsonValue,
+    account: &Account,
+  ) -> Result<u64, Error> {
+    std::fs::create_dir_all(&self.folder).map_err(|e| {
+      Error::IOError(format!("can't create folder {}: {}", self.folder.to_string_lossy(), e))
+    })?;
+
+    let seq = self.last()? + 1;
+
+    let record = json::object! {
+      seq: seq,
+      ctx: ctx.clone(),
+      _id: after["_id"].clone(),
+      _uuid: after["_uuid"].clone(),
+      before: SChanges::hash(before),
+      after: SChanges::hash(after),
+      account: {
+        _id: account.id.to_base64(),
+        email: account.email.clone(),
+      },
+      time: time_to_string(Utc::now()),
+    };
+
+    let path = self.segment_path(seq / SEGMENT);
+    let mut file = std::fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&path)
+      .map_err(|e| Error::IOError(format!("fail to open for append file: {}", e)))?;
+
+    file
+      .write_all(format!("{}\n", record.dump()).as_bytes())
+      .map_err(|e| Error::IOError(format!("fail to write file: {}", e)))?;
+
+    crate::storage::save(&self.sequence_path(), seq.to_string())?;
+
+    Ok(seq)
+  }
+
+  /// changes with sequence number greater than `since`
+  pub(crate) fn since(&self, since: u64, limit: usize) -> Result<Vec<JsonValue>, Error> {
+    let last = self.last()?;
+
+    let mut result = Vec::with_capacity(limit);
+
+    let mut segment = (since + 1) / SEGMENT;
+    while segment <= last / SEGMENT && result.len() < limit {
+      let file = match std::fs::File::open(self.segment_path(segment)) {
+        Ok(file) => file,
+        Err(_) => {
+          segment += 1;
+          continue;
+        },
+      };
+
+      for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| Error::IOError(e.to_string()))?;
+        if line.is_empty() {
+          continue;
+        }
+        let record = json::parse(&line).map_err(|e| Error::IOError(e.to_string()))?;
+        if record["seq"].as_u64().unwrap_or(0) > since {
+          result.push(record);
+          if result.len() >= limit {
+            break;
+          }
+        }
+      }
+
+      segment += 1;
+    }
+
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::Workspaces;
+  use tempfile::tempdir;
+  use values::ID_MIN;
+
+  #[test]
+  fn test_append_and_since() {
+    let tmp_dir = tempdir().unwrap();
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let changes = wss.get(&ID::random()).changes();
+
+    let account = Account { id: ID_MIN, email: "tester@nae.org".into() };
+    let ctx = vec!["goods".to_string()];
+
+    assert_eq!(changes.last().unwrap(), 0);
+
+    let mut before = JsonValue::Null;
+    for n in 0..5 {
+      let after = json::object! { _id: "goods/2023-01-06T12:43:15Z", name: format!("g{n}") };
+      assert_eq!(changes.append(&ctx, &before, &after, &account).unwrap(), n + 1);
+      before = after;
+    }
+
+    assert_eq!(changes.last().unwrap(), 5);
+
+    let list = changes.since(2, 10).unwrap();
+    assert_eq!(list.len(), 3);
+    assert_eq!(list[0]["seq"], 3);
+    let g1 = json::object! { _id: "goods/2023-01-06T12:43:15Z", name: "g1" };
+    assert_eq!(list[0]["before"], SChanges::hash(&g1));
+    assert_eq!(list[0]["account"]["email"], "tester@nae.org");
+
+    let list = changes.since(0, 2).unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[1]["seq"], 2);
+
+    assert!(changes.since(5, 10).unwrap().is_empty());
+  }
+}
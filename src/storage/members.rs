@@ -0,0 +1,125 @@
+use chrono::Utc;
+use json::JsonValue;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::storage::organizations::Workspace;
+use crate::storage::roles::OWNER;
+use crate::storage::{load, save};
+use service::error::Error;
+use service::utils::time::time_to_string;
+use service::Account;
+use values::ID;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Accounts that have access to workspace.
+///
+/// layout: members.json - { <account id>: { email, roles, added } }
+#[derive(Clone)]
+pub(crate) struct SMembers {
+  pub(crate) ws: Workspace,
+
+  pub(crate) path: PathBuf,
+}
+
+impl SMembers {
+  fn load(&self) -> Result<JsonValue, Error> {
+    if self.path.exists() {
+      load(&self.path)
+    } else {
+      Ok(JsonValue::new_object())
+    }
+  }
+
+  pub(crate) fn list(&self) -> Result<Vec<JsonValue>, Error> {
+    Ok(
+      self
+        .load()?
+        .entries()
+        .map(|(id, member)| {
+          let mut member = member.clone();
+          member["_id"] = id.into();
+          member
+        })
+        .collect(),
+    )
+  }
+
+  pub(crate) fn get(&self, id: &ID) -> Result<Option<JsonValue>, Error> {
+    let member = self.load()?[id.to_base64()].clone();
+    Ok(if member.is_null() { None } else { Some(member) })
+  }
+
+  pub(crate) fn is_member(&self, id: &ID) -> bool {
+    matches!(self.get(id), Ok(Some(_)))
+  }
+
+  /// roles of member; members that were added before roles have full access
+  pub(crate) fn roles_of(&self, id: &ID) -> Result<Option<Vec<String>>, Error> {
+    Ok(self.get(id)?.map(|member| {
+      if member["roles"].is_array() {
+        member["roles"]
+          .members()
+          .filter_map(|r| r.as_str())
+          .map(|r| r.to_string())
+          .collect()
+      } else {
+        vec![OWNER.to_string()]
+      }
+    }))
+  }
+
+  pub(crate) fn add(&self, account: &Account, roles: &[String]) -> Result<JsonValue, Error> {
+    let _lock = LOCK.lock().unwrap();
+
+    let mut members = self.load()?;
+
+    let key = account.id.to_base64();
+    if members[&key].is_null() {
+      members[&key] = json::object! {
+        email: account.email.clone(),
+        roles: roles.to_vec(),
+        added: time_to_string(Utc::now()),
+      };
+      save(&self.path, members.dump())?;
+    }
+
+    let mut member = members[&key].clone();
+    member["_id"] = key.into();
+    Ok(member)
+  }
+
+  pub(crate) fn set_roles(&self, id: &ID, roles: &[String]) -> Result<JsonValue, Error> {
+    let _lock = LOCK.lock().unwrap();
+
+    let mut members = self.load()?;
+
+    let key = id.to_base64();
+    if members[&key].is_null() {
+      return Err(Error::NotFound(format!("member {key}")));
+    }
+    members[&key]["roles"] = roles.to_vec().into();
+    save(&self.path, members.dump())?;
+
+    let mut member = members[&key].clone();
+    member["_id"] = key.into();
+    Ok(member)
+  }
+
+  pub(crate) fn remove(&self, id: &ID) -> Result<JsonValue, Error> {
+    let _lock = LOCK.lock().unwrap();
+
+    let mut members = self.load()?;
+
+    let key = id.to_base64();
+    let mut member = members.remove(&key);
+    if member.is_null() {
+      return Err(Error::NotFound(format!("member {key}")));
+    }
+    save(&self.path, members.dump())?;
+
+    member["_id"] = key.into();
+    Ok(member)
+  }
+}
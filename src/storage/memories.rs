@@ -7,16 +7,21 @@ use json::JsonValue;
 
 use service::error::Error;
 use service::utils::time::time_to_string;
+use service::Account;
 
 use std::path::PathBuf;
 
 use crate::memories::Enrich;
 use crate::utils::substring::StringUtils;
+use service::utils::json::JsonMerge;
 use std::sync::Mutex;
-use store::elements::receive_data;
+use store::elements::data_to_ops;
+use store::operations::OpMutation;
+use store::GetWarehouse;
 use uuid::Uuid;
 
-static LOCK: Mutex<Vec<u8>> = Mutex::new(vec![]);
+// serialize changes of documents, warehouse and references
+pub(crate) static LOCK: Mutex<Vec<u8>> = Mutex::new(vec![]);
 
 #[derive(Clone)]
 pub struct Memories {
@@ -32,29 +37,30 @@ pub struct Memories {
   pub folder: PathBuf,
 }
 
-fn save_data(
+/// Checked change of document with its warehouse operations, nothing is written yet.
+pub(crate) struct Pending {
+  ws: Workspace,
+  top_folder: PathBuf,
+  folder: PathBuf,
+  ctx: Vec<String>,
+  time: DateTime<Utc>,
+  // folder was allocated for new document
+  created: bool,
+  before: JsonValue,
+  pub(crate) data: JsonValue,
+  pub(crate) ops: Vec<OpMutation>,
+}
+
+fn prepare(
   app: &Application,
   ws: &Workspace,
   top_folder: &PathBuf,
   folder: &PathBuf,
   ctx: &Vec<String>,
-  _id: &String,
-  _uuid: Option<Uuid>,
   time: DateTime<Utc>,
+  created: bool,
   mut data: JsonValue,
-) -> Result<JsonValue, Error> {
-  let _lock = LOCK.lock().unwrap();
-
-  // if data["_id"] != id {
-  //   return Err(Error::IOError(format!("incorrect id {id} vs {}", data["_id"])));
-  // }
-
-  let time_str = time_to_string(time);
-
-  let file_name = format!("{time_str}.json");
-  let mut path_current = folder.clone();
-  path_current.push(&file_name);
-
+) -> Result<Pending, Error> {
   // 2023/01/2023-01-06T12:43:15Z/latest.json
   let mut path_latest = folder.clone();
   path_latest.push("latest.json");
@@ -66,8 +72,6 @@ fn save_data(
   // data = { _id: "", date: "2023-01-11", storage: "uuid", goods: [{goods: "", uom: "", qty: 0, price: 0, cost: 0, _tid: ""}, ...]}
   // cost = qty * price
 
-  // println!("loading before {path_latest:?}");
-
   let before = match load(&path_latest) {
     Ok(b) => {
       //WORKAROUND: make sure that id & uuid stay same
@@ -82,27 +86,113 @@ fn save_data(
     Err(_) => JsonValue::Null,
   };
 
-  // println!("loaded before {before:?}");
+  ws.references().check_delete(ctx, &before, &data)?;
 
-  crate::text_search::handle_mutation(app, ctx, &before, &data);
-  // TODO .map_err(|e| IOError(e.to_string()))?;
-
-  let data = receive_data(app, ws.id.to_string().as_str(), data, ctx, before)
+  let ops = data_to_ops(app, ws.id.to_string().as_str(), &data, ctx, &before)
     .map_err(|e| Error::GeneralError(e.message()))?;
 
-  let uuid = data["_uuid"].as_str();
+  Ok(Pending {
+    ws: ws.clone(),
+    top_folder: top_folder.clone(),
+    folder: folder.clone(),
+    ctx: ctx.clone(),
+    time,
+    created,
+    before,
+    data,
+    ops,
+  })
+}
+
+impl Pending {
+  /// document is changed again before it's saved, `false` if patch changes nothing
+  pub(crate) fn merge(&mut self, app: &Application, patch: &JsonValue) -> Result<bool, Error> {
+    let data = self.data.merge(patch);
+    if data == self.data {
+      return Ok(false);
+    }
+
+    self.ws.references().check_delete(&self.ctx, &self.before, &data)?;
+
+    self.ops = data_to_ops(app, self.ws.id.to_string().as_str(), &data, &self.ctx, &self.before)
+      .map_err(|e| Error::GeneralError(e.message()))?;
+    self.data = data;
+
+    Ok(true)
+  }
+
+  /// write document with its search index, references, barcodes and changes. Warehouse
+  /// operations are mutated here unless they are `applied` already by caller.
+  pub(crate) fn save(
+    self,
+    app: &Application,
+    account: &Account,
+    applied: bool,
+  ) -> Result<JsonValue, Error> {
+    let Pending { ws, top_folder, folder, ctx, time, before, data, ops, .. } = self;
+
+    let time_str = time_to_string(time);
+
+    let file_name = format!("{time_str}.json");
+    let mut path_current = folder.clone();
+    path_current.push(&file_name);
+
+    let mut path_latest = folder.clone();
+    path_latest.push("latest.json");
 
-  save(&path_current, data.dump())?;
+    // index can be rebuilt by reindex, document is saved anyway
+    if let Err(e) = crate::text_search::handle_mutation(app, &ws, &ctx, &before, &data) {
+      log::warn!("search index: {e:?}");
+    }
+
+    if !applied && !ops.is_empty() {
+      app.warehouse().mutate(&ops).map_err(|e| Error::GeneralError(e.message()))?;
+
+      if let Err(e) = crate::text_search::handle_stock(app, &ws, &ops) {
+        log::warn!("search index: {e:?}");
+      }
+    }
+
+    let barcodes = ws.barcodes();
+    barcodes.update_batches(&ops)?;
+    if ctx == vec!["goods".to_string()] {
+      barcodes.update_goods(&before, &data)?;
+    }
+
+    let uuid = data["_uuid"].as_str();
+
+    save(&path_current, data.dump())?;
 
-  // ignore error if file do not exist
-  symlink::remove_symlink_file(&path_latest);
-  symlink::symlink_file(&file_name, &path_latest)?;
+    ws.references().update(&before, &data)?;
 
-  if let Some(uuid) = uuid {
-    index_uuid(top_folder, folder, uuid)?;
+    ws.changes().append(&ctx, &before, &data, account)?;
+
+    // ignore error if file do not exist
+    symlink::remove_symlink_file(&path_latest);
+    symlink::symlink_file(&file_name, &path_latest)?;
+
+    if let Some(uuid) = uuid {
+      index_uuid(&top_folder, &folder, uuid)?;
+    }
+
+    Ok(data)
+  }
+
+  /// changed fields of document as audit records them
+  pub(crate) fn summary(&self) -> JsonValue {
+    let before = if self.before.is_object() { self.before.clone() } else { JsonValue::new_object() };
+    crate::storage::audit::SAudit::summary(&before, &self.data)
   }
 
-  Ok(data)
+  /// drop change, folder that was allocated for new document is removed
+  pub(crate) fn discard(self) -> Result<(), Error> {
+    if self.created {
+      std::fs::remove_dir_all(&self.folder).map_err(|e| {
+        Error::IOError(format!("can't remove folder {}: {}", self.folder.to_string_lossy(), e))
+      })?;
+    }
+    Ok(())
+  }
 }
 
 pub(crate) fn index_uuid(top_folder: &PathBuf, folder: &PathBuf, uuid: &str) -> Result<(), Error> {
@@ -161,10 +251,26 @@ pub(crate) fn build_folder_path(id: &String, folder: &PathBuf) -> Option<PathBuf
 }
 
 impl Memories {
-  pub(crate) fn create(&self, app: &Application, mut data: JsonValue) -> Result<JsonValue, Error> {
-    let (id, time, folder) = {
-      let _lock = LOCK.lock().unwrap();
+  pub(crate) fn create(
+    &self,
+    app: &Application,
+    account: &Account,
+    data: JsonValue,
+  ) -> Result<JsonValue, Error> {
+    let _lock = LOCK.lock().unwrap();
 
+    let data = self.prepare_create(app, data)?.save(app, account, false)?;
+
+    Ok(data.enrich(&self.ws))
+  }
+
+  /// allocate id for new document and check it, caller must hold `LOCK` until it's saved
+  pub(crate) fn prepare_create(
+    &self,
+    app: &Application,
+    mut data: JsonValue,
+  ) -> Result<Pending, Error> {
+    let (id, time, folder) = {
       let mut count = 0;
       let mut time = Utc::now();
       loop {
@@ -183,23 +289,14 @@ impl Memories {
 
         // println!("creating folder {folder:?}");
 
-        std::fs::create_dir_all(&folder).map_err(|e| {
-          Error::IOError(format!("can't create folder {}: {}", folder.to_string_lossy(), e))
-        })?;
-
-        let time_str = time_to_string(time);
-
-        let file_name = format!("{time_str}.json");
-        let mut path_current = folder.clone();
-        path_current.push(&file_name);
-
-        if path_current.exists() {
+        if folder.exists() {
           time = time + chrono::Duration::milliseconds(1);
           continue;
         }
 
-        // create because of lock releasing
-        save(&path_current, "".into())?;
+        std::fs::create_dir_all(&folder).map_err(|e| {
+          Error::IOError(format!("can't create folder {}: {}", folder.to_string_lossy(), e))
+        })?;
 
         break (id, time, folder);
       }
@@ -210,41 +307,73 @@ impl Memories {
     data["_id"] = id.clone().into();
     data["_uuid"] = uuid.to_string().into();
 
-    let data =
-      save_data(app, &self.ws, &self.top_folder, &folder, &self.ctx, &id, Some(uuid), time, data)?;
-
-    Ok(data.enrich(&self.ws))
+    match prepare(app, &self.ws, &self.top_folder, &folder, &self.ctx, time, true, data) {
+      Ok(pending) => Ok(pending),
+      Err(e) => {
+        // nothing to keep at allocated folder
+        if let Err(e) = std::fs::remove_dir_all(&folder) {
+          log::warn!("can't remove folder {}: {e}", folder.to_string_lossy());
+        }
+        Err(e)
+      },
+    }
   }
 
   pub(crate) fn update(
     &self,
     app: &Application,
+    account: &Account,
     id: String,
     data: Data,
   ) -> Result<JsonValue, Error> {
+    let _lock = LOCK.lock().unwrap();
+
+    let data = self.prepare_update(app, id, data)?.save(app, account, false)?;
+
+    Ok(data.enrich(&self.ws))
+  }
+
+  /// check change of document, caller must hold `LOCK` until it's saved
+  pub(crate) fn prepare_update(
+    &self,
+    app: &Application,
+    id: String,
+    data: Data,
+  ) -> Result<Pending, Error> {
     let time = Utc::now();
 
+    // document of other context can't be updated through this one
+    if let Some((ctx, _)) = id.rsplit_once('/') {
+      if ctx != self.ctx.join("/") {
+        return Err(Error::NotFound(format!("id `{id}` not found at {:?}", self.ctx)));
+      }
+    }
+
     let folder = match build_folder_path(&id, &self.folder) {
       Some(f) => f,
       None => return Err(Error::IOError(format!("fail on folder path for id: {}", id))),
     };
 
-    let data =
-      save_data(app, &self.ws, &self.top_folder, &folder, &self.ctx, &id, None, time, data)?;
-
-    Ok(data.enrich(&self.ws))
+    prepare(app, &self.ws, &self.top_folder, &folder, &self.ctx, time, false, data)
   }
 
   // TODO move to ???
+  /// document by `_id` or `_uuid`, only if it belongs to context of memories
   pub(crate) fn get(&self, id: &String) -> Option<Document> {
-    if id.contains("/") {
+    let doc = if id.contains("/") {
       self.ws.resolve_id(id)
     } else {
       match Uuid::parse_str(id) {
         Ok(id) => self.ws.resolve_uuid(&id),
         Err(_) => None,
       }
-    }
+    };
+    doc.filter(|doc| self.contains(doc))
+  }
+
+  // context/2023/01/2023-01-06T12:43:15Z/latest.json
+  fn contains(&self, doc: &Document) -> bool {
+    doc.path.ancestors().nth(4) == Some(self.folder.as_path())
   }
 
   pub(crate) fn list(&self, reverse: Option<bool>) -> std::io::Result<Vec<Document>> {
@@ -343,6 +472,37 @@ mod tests {
     println!("{path:?}")
   }
 
+  #[actix_web::test]
+  async fn test_get_within_context() {
+    let (tmp_dir, settings, db) = crate::warehouse::test_util::init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, _events) =
+      Application::new(std::sync::Arc::new(settings), std::sync::Arc::new(db), wss)
+        .await
+        .unwrap();
+
+    let ws = app.wss.get(&ID::random());
+    let account = Account { id: ID::from("user@nae.org"), email: "user@nae.org".into() };
+
+    let receive = ws.memories(vec!["warehouse".into(), "receive".into()]);
+    let goods = ws.memories(vec!["goods".into()]);
+
+    let doc = receive.create(&app, &account, json::object! { date: "2023-01-06" }).unwrap();
+    let (id, uuid) = (doc["_id"].string(), doc["_uuid"].string());
+
+    assert!(receive.get(&id).is_some());
+    assert!(receive.get(&uuid).is_some());
+
+    assert!(goods.get(&id).is_none());
+    assert!(goods.get(&uuid).is_none());
+    match goods.update(&app, &account, id.clone(), json::object! { name: "g1" }) {
+      Err(Error::NotFound(_)) => {},
+      r => panic!("expected not found, got {r:?}"),
+    }
+    assert_eq!(receive.get(&id).unwrap().json().unwrap()["date"], "2023-01-06");
+  }
+
   // #[test]
   // fn test_() {
   //   let tmp_dir = tempdir().unwrap();
@@ -1,7 +1,15 @@
+pub(crate) mod audit;
+pub(crate) mod barcodes;
 mod cameras;
+pub(crate) mod changes;
+pub(crate) mod members;
 pub mod memories;
 mod old_references;
 pub mod organizations;
+pub(crate) mod references;
+pub(crate) mod roles;
+pub(crate) mod sessions;
+pub(crate) mod synonyms;
 
 use crate::services::JsonData;
 pub(crate) use cameras::{SCamera, SEvent};
@@ -15,7 +23,7 @@ fn data(path: &PathBuf) -> Result<String, Error> {
   std::fs::read_to_string(path).map_err(|e| Error::IOError(e.to_string()))
 }
 
-fn load(path: &PathBuf) -> crate::services::Result {
+pub(crate) fn load(path: &PathBuf) -> crate::services::Result {
   data(path)?.json()
 }
 
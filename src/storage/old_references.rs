@@ -60,6 +60,35 @@ impl SShift {
   }
 }
 
+pub(crate) struct SAdjustment {
+  pub(crate) id: ID,
+  pub(crate) oid: ID,
+
+  pub(crate) path: PathBuf,
+}
+
+impl SAdjustment {
+  pub(crate) fn json(&self) -> JsonValue {
+    json(self.id.to_base64(), &self.path)
+  }
+
+  pub(crate) fn load(&self) -> crate::services::Result {
+    load(&self.path)
+  }
+
+  pub(crate) fn save(&self, data: String) -> Result<(), Error> {
+    save(&self.path, data)
+  }
+
+  pub(crate) fn delete(&self) -> Result<JsonValue, Error> {
+    let data = self.load()?;
+    std::fs::remove_file(&self.path).map_err(|e| {
+      Error::IOError(format!("can't remove {}: {}", self.path.to_string_lossy(), e))
+    })?;
+    Ok(data)
+  }
+}
+
 pub(crate) struct SLocation {
   pub(crate) id: ID,
   pub(crate) oid: ID,
@@ -4,8 +4,15 @@ use std::path::{Path, PathBuf};
 use uuid::Uuid;
 use walkdir::{DirEntry, WalkDir};
 
+use crate::storage::audit::SAudit;
+use crate::storage::barcodes::SBarcodes;
+use crate::storage::changes::SChanges;
+use crate::storage::members::SMembers;
 use crate::storage::memories::{Document, Memories};
-use crate::storage::old_references::{SDepartment, SLocation, SPerson, SShift};
+use crate::storage::old_references::{SAdjustment, SDepartment, SLocation, SPerson, SShift};
+use crate::storage::references::SReferences;
+use crate::storage::roles::SRoles;
+use crate::storage::synonyms::SSynonyms;
 use crate::storage::{json, load, save, SCamera};
 use service::error::Error;
 use values::ID;
@@ -83,6 +90,10 @@ pub struct Workspace {
 }
 
 impl Workspace {
+  pub(crate) fn folder(&self) -> &PathBuf {
+    &self.folder
+  }
+
   pub(crate) fn json(&self) -> JsonValue {
     json(self.id.to_base64(), &self.path)
   }
@@ -113,6 +124,55 @@ impl Workspace {
     Memories { ws: self.clone(), ctx, top_folder, folder }
   }
 
+  pub(crate) fn audit(&self) -> SAudit {
+    let mut folder = self.folder.clone();
+    folder.push("audit");
+
+    SAudit { ws: self.clone(), folder }
+  }
+
+  pub(crate) fn barcodes(&self) -> SBarcodes {
+    let mut folder = self.folder.clone();
+    folder.push("barcodes");
+
+    SBarcodes { ws: self.clone(), folder }
+  }
+
+  pub(crate) fn changes(&self) -> SChanges {
+    let mut folder = self.folder.clone();
+    folder.push("changes");
+
+    SChanges { ws: self.clone(), folder }
+  }
+
+  pub(crate) fn members(&self) -> SMembers {
+    let mut path = self.folder.clone();
+    path.push("members.json");
+
+    SMembers { ws: self.clone(), path }
+  }
+
+  pub(crate) fn references(&self) -> SReferences {
+    let mut folder = self.folder.clone();
+    folder.push("references");
+
+    SReferences { ws: self.clone(), folder }
+  }
+
+  pub(crate) fn roles(&self) -> SRoles {
+    let mut path = self.folder.clone();
+    path.push("roles.json");
+
+    SRoles { ws: self.clone(), path }
+  }
+
+  pub(crate) fn synonyms(&self) -> SSynonyms {
+    let mut path = self.folder.clone();
+    path.push("synonyms.json");
+
+    SSynonyms { ws: self.clone(), path }
+  }
+
   pub(crate) fn resolve_uuid(&self, id: &Uuid) -> Option<Document> {
     // println!("resolve_uuid {id}");
     let mut top_folder = self.folder.clone();
@@ -195,7 +255,7 @@ impl Workspace {
   pub(crate) fn departments(&self) -> Result<Vec<SDepartment>, Error> {
     let mut result = Vec::new();
 
-    let mut folder = self.path.clone();
+    let mut folder = self.folder.clone();
     folder.push("departments");
 
     let entries = match std::fs::read_dir(&folder) {
@@ -225,7 +285,7 @@ impl Workspace {
 
   pub(crate) fn shift(&self, id: ID) -> SShift {
     let mut path = self.folder.clone();
-    path.push("departments");
+    path.push("shifts");
     path.push(format!("{}.json", id.to_base64()));
 
     SShift { id: id.clone(), oid: self.id.clone(), path }
@@ -234,7 +294,7 @@ impl Workspace {
   pub(crate) fn shifts(&self) -> Vec<SShift> {
     let mut result = Vec::new();
 
-    let mut folder = self.path.clone();
+    let mut folder = self.folder.clone();
     folder.push("shifts");
 
     let entries = match std::fs::read_dir(&folder) {
@@ -262,6 +322,39 @@ impl Workspace {
     result
   }
 
+  pub(crate) fn adjustment(&self, id: ID) -> SAdjustment {
+    let mut path = self.folder.clone();
+    path.push("adjustments");
+    path.push(format!("{}.json", id.to_base64()));
+
+    SAdjustment { id, oid: self.id, path }
+  }
+
+  pub(crate) fn adjustments(&self) -> Vec<SAdjustment> {
+    let mut result = Vec::new();
+
+    let mut folder = self.folder.clone();
+    folder.push("adjustments");
+
+    let entries = match std::fs::read_dir(&folder) {
+      Ok(entries) => entries,
+      // no adjustments yet
+      Err(_) => return vec![],
+    };
+
+    for entry in entries.flatten() {
+      let path = entry.path();
+      if path.is_file() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(Ok(id)) = name.strip_suffix(".json").map(|id| ID::from_base64(id.as_bytes())) {
+          result.push(SAdjustment { id, oid: self.id, path });
+        }
+      }
+    }
+
+    result
+  }
+
   pub(crate) fn location(&self, id: ID) -> SLocation {
     let mut path = self.folder.clone();
     path.push("locations");
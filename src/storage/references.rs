@@ -0,0 +1,279 @@
+use json::JsonValue;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::storage::organizations::Workspace;
+use crate::storage::{load, save};
+use service::error::Error;
+use service::utils::json::JsonParams;
+use values::ID;
+
+// fields of memories document that hold `_id` or `_uuid` of other document
+pub(crate) const REFERENCE_FIELDS: [&str; 5] =
+  ["goods", "storage", "document", "counterparty", "order"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Reference {
+  pub(crate) source: String,
+  pub(crate) field: String,
+  pub(crate) target: String,
+}
+
+impl Reference {
+  pub(crate) fn to_json(&self) -> JsonValue {
+    json::object! {
+      source: self.source.clone(),
+      field: self.field.clone(),
+      target: self.target.clone(),
+    }
+  }
+}
+
+/// Registry of references between memories documents.
+///
+/// layout: references/<target hash>/<source hash>.<field>.json
+#[derive(Clone)]
+pub(crate) struct SReferences {
+  pub(crate) ws: Workspace,
+
+  pub(crate) folder: PathBuf,
+}
+
+impl SReferences {
+  /// key of document: `_uuid` if present, otherwise `_id`
+  pub(crate) fn key_of(data: &JsonValue) -> Option<String> {
+    data["_uuid"].string_or_none().or_else(|| data["_id"].string_or_none())
+  }
+
+  /// outgoing references of document; deleted document refer to nothing
+  pub(crate) fn outgoing(data: &JsonValue) -> BTreeMap<String, String> {
+    let mut result = BTreeMap::new();
+
+    if !data.is_object() || data["status"].string() == "deleted" {
+      return result;
+    }
+
+    for field in REFERENCE_FIELDS {
+      if let Some(target) = data[field].as_str() {
+        if !target.is_empty() {
+          result.insert(field.to_string(), target.to_string());
+        }
+      }
+    }
+
+    result
+  }
+
+  // same document can be referred by `_id` or by `_uuid`, store everything under `_uuid`
+  fn normalize(&self, target: &str) -> String {
+    if let Ok(uuid) = Uuid::parse_str(target) {
+      return uuid.to_string();
+    }
+
+    self
+      .ws
+      .resolve_id(target)
+      .and_then(|doc| doc.json().ok())
+      .and_then(|data| data["_uuid"].string_or_none())
+      .unwrap_or_else(|| target.to_string())
+  }
+
+  fn target_folder(&self, target: &str) -> PathBuf {
+    let mut folder = self.folder.clone();
+    folder.push(ID::from(target).to_base64());
+    folder
+  }
+
+  fn path(&self, target: &str, source: &str, field: &str) -> PathBuf {
+    let mut path = self.target_folder(target);
+    path.push(format!("{}.{field}.json", ID::from(source).to_base64()));
+    path
+  }
+
+  /// record changes of outgoing references between two revisions of document
+  pub(crate) fn update(&self, before: &JsonValue, after: &JsonValue) -> Result<(), Error> {
+    let source = match SReferences::key_of(after).or_else(|| SReferences::key_of(before)) {
+      Some(key) => key,
+      None => return Ok(()),
+    };
+
+    let before = SReferences::outgoing(before);
+    let after = SReferences::outgoing(after);
+
+    for (field, target) in &before {
+      if after.get(field) != Some(target) {
+        let path = self.path(&self.normalize(target), &source, field);
+        if path.exists() {
+          std::fs::remove_file(&path).map_err(|e| {
+            Error::IOError(format!("can't remove reference {}: {}", path.to_string_lossy(), e))
+          })?;
+        }
+      }
+    }
+
+    for (field, target) in &after {
+      let target = self.normalize(target);
+      let data = json::object! {
+        source: source.clone(),
+        field: field.clone(),
+        target: target.clone(),
+      };
+      save(&self.path(&target, &source, field), data.dump())?;
+    }
+
+    Ok(())
+  }
+
+  /// documents that refer to given `_id` or `_uuid`
+  pub(crate) fn used_by(&self, target: &str) -> Result<Vec<Reference>, Error> {
+    let folder = self.target_folder(&self.normalize(target));
+
+    let entries = match std::fs::read_dir(&folder) {
+      Ok(entries) => entries,
+      Err(_) => return Ok(vec![]),
+    };
+
+    let mut result = Vec::new();
+    for entry in entries {
+      let path = entry.map_err(|e| Error::IOError(e.to_string()))?.path();
+      if path.is_file() {
+        let data = load(&path)?;
+        result.push(Reference {
+          source: data["source"].string(),
+          field: data["field"].string(),
+          target: data["target"].string(),
+        });
+      }
+    }
+
+    result.sort_by(|a, b| (&a.source, &a.field).cmp(&(&b.source, &b.field)));
+
+    Ok(result)
+  }
+
+  /// refuse to delete document that is still referred by others
+  pub(crate) fn check_delete(
+    &self,
+    ctx: &Vec<String>,
+    before: &JsonValue,
+    after: &JsonValue,
+  ) -> Result<(), Error> {
+    let protected = match ctx.last().map(|s| s.as_str()) {
+      Some("goods") | Some("storage") => true,
+      _ => false,
+    };
+
+    if !protected || before["status"].string() == "deleted" || after["status"].string() != "deleted"
+    {
+      return Ok(());
+    }
+
+    let key = match SReferences::key_of(before) {
+      Some(key) => key,
+      None => return Ok(()),
+    };
+
+    let used_by = self.used_by(&key)?;
+    if used_by.is_empty() {
+      Ok(())
+    } else {
+      let sources: Vec<String> = used_by.iter().take(5).map(|r| r.source.clone()).collect();
+      Err(Error::Conflict(format!(
+        "`{key}` is used by {} document(s): {}",
+        used_by.len(),
+        sources.join(", ")
+      )))
+    }
+  }
+
+  /// references of workspace that can't be resolved
+  pub(crate) fn dangling(&self) -> Result<Vec<Reference>, Error> {
+    let mut result = Vec::new();
+
+    for doc in self.ws.clone().into_iter() {
+      let data = match doc.json() {
+        Ok(data) => data,
+        Err(_) => continue,
+      };
+
+      let source = SReferences::key_of(&data).unwrap_or_else(|| doc.id.clone());
+
+      for (field, target) in SReferences::outgoing(&data) {
+        if !self.exists(&target) {
+          result.push(Reference { source: source.clone(), field, target });
+        }
+      }
+    }
+
+    result.sort_by(|a, b| a.source.cmp(&b.source));
+
+    Ok(result)
+  }
+
+  fn exists(&self, target: &str) -> bool {
+    let doc = match Uuid::parse_str(target) {
+      Ok(uuid) => self.ws.resolve_uuid(&uuid),
+      Err(_) => self.ws.resolve_id(target),
+    };
+    doc.map(|doc| doc.path.exists()).unwrap_or(false)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::Workspaces;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_outgoing() {
+    let data = json::object! {
+      _id: "warehouse/receive/2023-01-06T12:43:15Z",
+      _uuid: "b1a4d6c3-6a0c-4c1f-9a64-2a6f5b2bbf57",
+      goods: "7fcb1e4e-7d8d-4d5b-8c53-8e1c3f0e0d5a",
+      storage: "warehouse/storage/2023-01-01T00:00:00Z",
+      document: "",
+      qty: { number: 1 },
+    };
+
+    let refs = SReferences::outgoing(&data);
+    assert_eq!(refs.len(), 2);
+    assert_eq!(refs.get("goods").unwrap(), "7fcb1e4e-7d8d-4d5b-8c53-8e1c3f0e0d5a");
+    assert_eq!(refs.get("storage").unwrap(), "warehouse/storage/2023-01-01T00:00:00Z");
+
+    let mut deleted = data.clone();
+    deleted["status"] = "deleted".into();
+    assert!(SReferences::outgoing(&deleted).is_empty());
+  }
+
+  #[test]
+  fn test_used_by_field() {
+    let tmp_dir = tempdir().unwrap();
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let references = wss.get(&ID::random()).references();
+
+    let target = "b1a4d6c3-6a0c-4c1f-9a64-2a6f5b2bbf57";
+
+    let before = json::object! {
+      _id: "warehouse/transfer/2023-01-06T12:43:15Z",
+      document: target,
+      order: target,
+    };
+    references.update(&JsonValue::Null, &before).unwrap();
+
+    let used_by = references.used_by(target).unwrap();
+    assert_eq!(used_by.len(), 2);
+    assert_eq!(used_by[0].field, "document");
+    assert_eq!(used_by[1].field, "order");
+
+    let mut after = before.clone();
+    after.remove("document");
+    references.update(&before, &after).unwrap();
+
+    let used_by = references.used_by(target).unwrap();
+    assert_eq!(used_by.len(), 1);
+    assert_eq!(used_by[0].source, "warehouse/transfer/2023-01-06T12:43:15Z");
+    assert_eq!(used_by[0].field, "order");
+  }
+}
@@ -0,0 +1,231 @@
+use json::JsonValue;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::storage::organizations::Workspace;
+use crate::storage::{load, save};
+use service::error::Error;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) const OWNER: &str = "owner";
+pub(crate) const VIEWER: &str = "viewer";
+
+const COMMANDS: [&str; 6] = ["find", "get", "create", "update", "patch", "remove"];
+
+/// Roles of workspace. Rule match by service, context prefix and command,
+/// `deny` win over `allow` and nothing is allowed by default.
+///
+/// layout: roles.json - { <name>: { rules: [{ service, ctx, commands, effect }] } }
+#[derive(Clone)]
+pub(crate) struct SRoles {
+  pub(crate) ws: Workspace,
+
+  pub(crate) path: PathBuf,
+}
+
+impl SRoles {
+  fn builtin() -> JsonValue {
+    json::object! {
+      "owner": {
+        rules: [{ service: "*", ctx: "*", commands: ["*"], effect: "allow" }],
+      },
+      "viewer": {
+        rules: [{ service: "*", ctx: "*", commands: ["find", "get"], effect: "allow" }],
+      },
+    }
+  }
+
+  fn load(&self) -> Result<JsonValue, Error> {
+    let mut roles = SRoles::builtin();
+    if self.path.exists() {
+      for (name, role) in load(&self.path)?.entries() {
+        roles[name] = role.clone();
+      }
+    }
+    Ok(roles)
+  }
+
+  pub(crate) fn list(&self) -> Result<Vec<JsonValue>, Error> {
+    Ok(self.load()?.entries().map(|(name, role)| SRoles::to_json(name, role)).collect())
+  }
+
+  pub(crate) fn get(&self, name: &str) -> Result<Option<JsonValue>, Error> {
+    let roles = self.load()?;
+    let role = &roles[name];
+    Ok(if role.is_null() { None } else { Some(SRoles::to_json(name, role)) })
+  }
+
+  pub(crate) fn exists(&self, name: &str) -> Result<bool, Error> {
+    Ok(self.get(name)?.is_some())
+  }
+
+  pub(crate) fn save(&self, name: &str, rules: &JsonValue) -> Result<JsonValue, Error> {
+    if name.is_empty() {
+      return Err(Error::GeneralError("role name can't be empty".into()));
+    }
+    if name == OWNER {
+      return Err(Error::Conflict(format!("role `{OWNER}` can't be changed")));
+    }
+    SRoles::validate(rules)?;
+
+    let _lock = LOCK.lock().unwrap();
+
+    let mut roles = self.custom()?;
+    roles[name] = json::object! { rules: rules.clone() };
+    save(&self.path, roles.dump())?;
+
+    Ok(SRoles::to_json(name, &roles[name]))
+  }
+
+  pub(crate) fn remove(&self, name: &str) -> Result<JsonValue, Error> {
+    if name == OWNER {
+      return Err(Error::Conflict(format!("role `{OWNER}` can't be removed")));
+    }
+
+    let _lock = LOCK.lock().unwrap();
+
+    let mut roles = self.custom()?;
+    let role = roles.remove(name);
+    if role.is_null() {
+      return Err(Error::NotFound(format!("role `{name}`")));
+    }
+    save(&self.path, roles.dump())?;
+
+    Ok(SRoles::to_json(name, &role))
+  }
+
+  /// check that any of roles allow command at service for context
+  pub(crate) fn allowed(
+    &self,
+    roles: &[String],
+    service: &str,
+    ctx: &[String],
+    command: &str,
+  ) -> Result<bool, Error> {
+    let all = self.load()?;
+
+    let mut allowed = false;
+    for name in roles {
+      for rule in all[name.as_str()]["rules"].members() {
+        if !SRoles::matches(rule, service, ctx, command) {
+          continue;
+        }
+        match rule["effect"].as_str() {
+          Some("deny") => return Ok(false),
+          Some("allow") => allowed = true,
+          _ => {},
+        }
+      }
+    }
+
+    Ok(allowed)
+  }
+
+  fn matches(rule: &JsonValue, service: &str, ctx: &[String], command: &str) -> bool {
+    let service_match = match rule["service"].as_str() {
+      Some("*") | None => true,
+      Some(name) => name == service,
+    };
+
+    let ctx_match = match rule["ctx"].as_str() {
+      Some("*") | Some("") | None => true,
+      Some(prefix) => {
+        let prefix: Vec<&str> = prefix.split('/').collect();
+        prefix.len() <= ctx.len() && prefix.iter().zip(ctx.iter()).all(|(p, c)| p == c)
+      },
+    };
+
+    let command_match = rule["commands"]
+      .members()
+      .any(|c| c.as_str() == Some("*") || c.as_str() == Some(command));
+
+    service_match && ctx_match && command_match
+  }
+
+  fn validate(rules: &JsonValue) -> Result<(), Error> {
+    if !rules.is_array() {
+      return Err(Error::GeneralError("`rules` must be an array".into()));
+    }
+    for rule in rules.members() {
+      match rule["effect"].as_str() {
+        Some("allow") | Some("deny") => {},
+        _ => return Err(Error::GeneralError("rule `effect` must be `allow` or `deny`".into())),
+      }
+      if !rule["commands"].is_array() {
+        return Err(Error::GeneralError("rule `commands` must be an array".into()));
+      }
+      for command in rule["commands"].members() {
+        match command.as_str() {
+          Some("*") => {},
+          Some(c) if COMMANDS.contains(&c) => {},
+          _ => return Err(Error::GeneralError(format!("unknown command {command}"))),
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn custom(&self) -> Result<JsonValue, Error> {
+    if self.path.exists() {
+      load(&self.path)
+    } else {
+      Ok(JsonValue::new_object())
+    }
+  }
+
+  fn to_json(name: &str, role: &JsonValue) -> JsonValue {
+    let mut role = role.clone();
+    role["_id"] = name.into();
+    role
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::storage::Workspaces;
+  use tempfile::tempdir;
+  use values::ID;
+
+  #[test]
+  fn test_allowed() {
+    let tmp_dir = tempdir().unwrap();
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let roles = wss.get(&ID::random()).roles();
+
+    let rules = json::array![
+      { service: "memories", ctx: "*", commands: ["find", "get"], effect: "allow" },
+      { service: "memories", ctx: "warehouse/receive", commands: ["create", "update", "patch"], effect: "allow" },
+      { service: "memories", ctx: "goods", commands: ["update", "patch"], effect: "deny" },
+    ];
+    roles.save("storekeeper", &rules).unwrap();
+
+    let storekeeper = vec!["storekeeper".to_string()];
+    let receive = vec!["warehouse".to_string(), "receive".to_string()];
+    let goods = vec!["goods".to_string()];
+
+    assert!(roles.allowed(&storekeeper, "memories", &receive, "create").unwrap());
+    assert!(roles.allowed(&storekeeper, "memories", &goods, "find").unwrap());
+    assert!(!roles.allowed(&storekeeper, "memories", &goods, "patch").unwrap());
+    assert!(!roles.allowed(&storekeeper, "memories", &receive, "remove").unwrap());
+    assert!(!roles.allowed(&storekeeper, "roles", &[], "create").unwrap());
+
+    // deny win over allow of other role
+    let both = vec!["storekeeper".to_string(), OWNER.to_string()];
+    assert!(!roles.allowed(&both, "memories", &goods, "update").unwrap());
+    assert!(roles.allowed(&both, "roles", &[], "create").unwrap());
+
+    let viewer = vec![VIEWER.to_string()];
+    assert!(roles.allowed(&viewer, "inventory", &[], "find").unwrap());
+    assert!(!roles.allowed(&viewer, "memories", &receive, "create").unwrap());
+
+    assert!(roles.save(OWNER, &rules).is_err());
+    assert!(roles
+      .save("broken", &json::array![{ commands: ["find"], effect: "maybe" }])
+      .is_err());
+
+    roles.remove("storekeeper").unwrap();
+    assert!(!roles.exists("storekeeper").unwrap());
+  }
+}
@@ -0,0 +1,247 @@
+use chrono::{DateTime, Duration, Utc};
+use json::JsonValue;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::storage::{load, save};
+use service::error::Error;
+use service::utils::time::{string_to_time, time_to_string};
+use values::ID;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+// `last_used` is written not more often than that
+const TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+/// Login sessions of accounts, refresh tokens are kept as hashes.
+///
+/// layout: <account id>/<session id>.json - { _id, device, issued, last_used, expires, refresh, revoked }
+#[derive(Debug, Clone)]
+pub(crate) struct SSessions {
+  pub(crate) folder: PathBuf,
+}
+
+impl SSessions {
+  pub(crate) fn new(folder: PathBuf) -> Self {
+    SSessions { folder }
+  }
+
+  fn path(&self, account: &ID, sid: &str) -> PathBuf {
+    let mut path = self.folder.clone();
+    path.push(account.to_base64());
+    path.push(format!("{sid}.json"));
+    path
+  }
+
+  fn hash(secret: &str) -> String {
+    ID::from(secret).to_base64()
+  }
+
+  // refresh token: <account id>.<session id>.<secret>
+  fn refresh_token(account: &ID, sid: &str, secret: &str) -> String {
+    format!("{}.{sid}.{secret}", account.to_base64())
+  }
+
+  /// new session and it's refresh token
+  pub(crate) fn create(
+    &self,
+    account: &ID,
+    device: &str,
+    ttl: Duration,
+  ) -> Result<(JsonValue, String), Error> {
+    let sid = Uuid::new_v4().to_string();
+    let secret = ID::random().to_base64();
+
+    let now = Utc::now();
+    let session = json::object! {
+      _id: sid.clone(),
+      device: device,
+      issued: time_to_string(now),
+      last_used: time_to_string(now),
+      expires: time_to_string(now + ttl),
+      refresh: SSessions::hash(&secret),
+    };
+
+    save(&self.path(account, &sid), session.dump())?;
+
+    Ok((SSessions::to_json(&session), SSessions::refresh_token(account, &sid, &secret)))
+  }
+
+  pub(crate) fn get(&self, account: &ID, sid: &str) -> Result<Option<JsonValue>, Error> {
+    let path = self.path(account, sid);
+    if path.exists() {
+      Ok(Some(load(&path)?))
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// session is known, not revoked and not expired; `last_used` is updated
+  pub(crate) fn check(&self, account: &ID, sid: &str) -> Result<bool, Error> {
+    let mut session = match self.get(account, sid)? {
+      Some(session) => session,
+      None => return Ok(false),
+    };
+
+    let now = Utc::now();
+    if !SSessions::is_active(&session, now) {
+      return Ok(false);
+    }
+
+    let last_used = string_to_time(session["last_used"].as_str().unwrap_or_default())?;
+    if now - last_used > Duration::seconds(TOUCH_INTERVAL_SECONDS) {
+      let _lock = LOCK.lock().unwrap();
+      session["last_used"] = time_to_string(now).into();
+      save(&self.path(account, sid), session.dump())?;
+    }
+
+    Ok(true)
+  }
+
+  /// exchange refresh token for new one, reuse of old token revoke session
+  pub(crate) fn refresh(&self, token: &str) -> Result<(ID, JsonValue, String), Error> {
+    let invalid = || Error::NotAuthenticated("invalid refresh token".into());
+
+    let mut parts = token.splitn(3, '.');
+    let (account, sid, secret) = match (parts.next(), parts.next(), parts.next()) {
+      (Some(account), Some(sid), Some(secret)) => (account, sid, secret),
+      _ => return Err(invalid()),
+    };
+    let account = ID::from_base64(account.as_bytes()).map_err(|_| invalid())?;
+    if Uuid::parse_str(sid).is_err() {
+      return Err(invalid());
+    }
+
+    let _lock = LOCK.lock().unwrap();
+
+    let mut session = self.get(&account, sid)?.ok_or_else(invalid)?;
+
+    let now = Utc::now();
+    if !SSessions::is_active(&session, now) {
+      return Err(invalid());
+    }
+
+    if session["refresh"].as_str() != Some(SSessions::hash(secret).as_str()) {
+      log::warn!("reuse of refresh token, revoking session {sid}");
+      session["revoked"] = time_to_string(now).into();
+      save(&self.path(&account, sid), session.dump())?;
+      return Err(invalid());
+    }
+
+    let secret = ID::random().to_base64();
+    session["refresh"] = SSessions::hash(&secret).into();
+    session["last_used"] = time_to_string(now).into();
+    save(&self.path(&account, sid), session.dump())?;
+
+    Ok((account, SSessions::to_json(&session), SSessions::refresh_token(&account, sid, &secret)))
+  }
+
+  /// active sessions of account
+  pub(crate) fn list(&self, account: &ID) -> Result<Vec<JsonValue>, Error> {
+    let mut folder = self.folder.clone();
+    folder.push(account.to_base64());
+
+    let mut result = Vec::new();
+    if !folder.exists() {
+      return Ok(result);
+    }
+
+    let now = Utc::now();
+    let entries = std::fs::read_dir(&folder).map_err(|e| Error::IOError(e.to_string()))?;
+    for entry in entries {
+      let path = entry.map_err(|e| Error::IOError(e.to_string()))?.path();
+      if path.extension().map(|e| e == "json").unwrap_or(false) {
+        let session = load(&path)?;
+        if SSessions::is_active(&session, now) {
+          result.push(SSessions::to_json(&session));
+        }
+      }
+    }
+
+    result.sort_by(|a, b| b["issued"].as_str().cmp(&a["issued"].as_str()));
+
+    Ok(result)
+  }
+
+  pub(crate) fn revoke(&self, account: &ID, sid: &str) -> Result<JsonValue, Error> {
+    let _lock = LOCK.lock().unwrap();
+
+    let mut session = self
+      .get(account, sid)?
+      .ok_or_else(|| Error::NotFound(format!("session {sid}")))?;
+
+    if session["revoked"].is_null() {
+      session["revoked"] = time_to_string(Utc::now()).into();
+      save(&self.path(account, sid), session.dump())?;
+    }
+
+    Ok(SSessions::to_json(&session))
+  }
+
+  pub(crate) fn revoke_all(&self, account: &ID) -> Result<(), Error> {
+    for session in self.list(account)? {
+      self.revoke(account, session["_id"].as_str().unwrap_or_default())?;
+    }
+    Ok(())
+  }
+
+  fn is_active(session: &JsonValue, now: DateTime<Utc>) -> bool {
+    if !session["revoked"].is_null() {
+      return false;
+    }
+    match session["expires"].as_str().map(string_to_time) {
+      Some(Ok(expires)) => expires > now,
+      _ => false,
+    }
+  }
+
+  // without hash of refresh token
+  fn to_json(session: &JsonValue) -> JsonValue {
+    let mut session = session.clone();
+    session.remove("refresh");
+    session
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  #[test]
+  fn test_refresh_and_revoke() {
+    let tmp_dir = tempdir().unwrap();
+    let sessions = SSessions::new(tmp_dir.path().join("sessions"));
+
+    let account = ID::from("tester@nae.org");
+
+    let (session, token1) = sessions.create(&account, "browser", Duration::days(1)).unwrap();
+    let sid = session["_id"].as_str().unwrap().to_string();
+    assert!(session["refresh"].is_null());
+    assert!(sessions.check(&account, &sid).unwrap());
+
+    let (id, _, token2) = sessions.refresh(&token1).unwrap();
+    assert_eq!(id, account);
+    assert_ne!(token1, token2);
+
+    // old refresh token can't be used twice, session is revoked
+    assert!(sessions.refresh(&token1).is_err());
+    assert!(!sessions.check(&account, &sid).unwrap());
+    assert!(sessions.refresh(&token2).is_err());
+
+    let (s1, _) = sessions.create(&account, "phone", Duration::days(1)).unwrap();
+    let (_, _) = sessions.create(&account, "laptop", Duration::days(1)).unwrap();
+    assert_eq!(sessions.list(&account).unwrap().len(), 2);
+
+    sessions.revoke(&account, s1["_id"].as_str().unwrap()).unwrap();
+    assert_eq!(sessions.list(&account).unwrap().len(), 1);
+
+    sessions.revoke_all(&account).unwrap();
+    assert!(sessions.list(&account).unwrap().is_empty());
+
+    // expired
+    let (s2, _) = sessions.create(&account, "old", Duration::seconds(-1)).unwrap();
+    assert!(!sessions.check(&account, s2["_id"].as_str().unwrap()).unwrap());
+  }
+}
@@ -0,0 +1,97 @@
+use json::JsonValue;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::storage::organizations::Workspace;
+use crate::storage::{load, save};
+use service::error::Error;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Synonyms of workspace search, every word or phrase of group is found by others.
+///
+/// layout: synonyms.json - [{ _id, words: ["аспирин", "ацетилсалициловая кислота"] }]
+#[derive(Clone)]
+pub(crate) struct SSynonyms {
+  pub(crate) ws: Workspace,
+
+  pub(crate) path: PathBuf,
+}
+
+impl SSynonyms {
+  pub(crate) fn list(&self) -> Result<Vec<JsonValue>, Error> {
+    if self.path.exists() {
+      Ok(load(&self.path)?.members().cloned().collect())
+    } else {
+      Ok(vec![])
+    }
+  }
+
+  /// words of every group
+  pub(crate) fn groups(&self) -> Result<Vec<Vec<String>>, Error> {
+    Ok(
+      self
+        .list()?
+        .iter()
+        .map(|group| {
+          group["words"]
+            .members()
+            .filter_map(|w| w.as_str())
+            .map(|w| w.to_string())
+            .collect()
+        })
+        .collect(),
+    )
+  }
+
+  /// create group if `id` is none, otherwise replace words of group
+  pub(crate) fn save(&self, id: Option<&str>, words: &JsonValue) -> Result<JsonValue, Error> {
+    let words: Vec<JsonValue> = words
+      .members()
+      .filter_map(|w| w.as_str())
+      .map(|w| w.trim())
+      .filter(|w| !w.is_empty())
+      .map(|w| w.into())
+      .collect();
+    if words.len() < 2 {
+      return Err(Error::GeneralError("synonyms need at least two words".into()));
+    }
+
+    let _lock = LOCK.lock().unwrap();
+
+    let mut list = self.list()?;
+    let group = match id {
+      Some(id) => {
+        let group = list
+          .iter_mut()
+          .find(|group| group["_id"] == id)
+          .ok_or_else(|| Error::NotFound(format!("synonyms `{id}`")))?;
+        group["words"] = words.into();
+        group.clone()
+      },
+      None => {
+        let group = json::object! { _id: Uuid::new_v4().to_string(), words: words };
+        list.push(group.clone());
+        group
+      },
+    };
+
+    save(&self.path, JsonValue::Array(list).dump())?;
+    Ok(group)
+  }
+
+  pub(crate) fn remove(&self, id: &str) -> Result<JsonValue, Error> {
+    let _lock = LOCK.lock().unwrap();
+
+    let mut list = self.list()?;
+    let position = list
+      .iter()
+      .position(|group| group["_id"] == id)
+      .ok_or_else(|| Error::NotFound(format!("synonyms `{id}`")))?;
+    let group = list.remove(position);
+
+    save(&self.path, JsonValue::Array(list).dump())?;
+    Ok(group)
+  }
+}
@@ -0,0 +1,264 @@
+use tantivy::tokenizer::{
+  BoxTokenStream, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, TextAnalyzer,
+  Token, TokenFilter, TokenStream,
+};
+
+/// name of tokenizer registered at index for `text` field
+pub const TOKENIZER: &str = "nae";
+
+/// Words are lowercased, `ё` is replaced by `е`, each word is followed by its Cyrillic↔Latin
+/// transliteration at the same position and both are stemmed (Russian, then English stemmer,
+/// each of them keep words of other alphabet unchanged).
+pub fn analyzer() -> TextAnalyzer {
+  TextAnalyzer::from(SimpleTokenizer)
+    .filter(RemoveLongFilter::limit(40))
+    .filter(LowerCaser)
+    .filter(Transliteration)
+    .filter(Stemmer::new(Language::Russian))
+    .filter(Stemmer::new(Language::English))
+}
+
+/// analyzed words of text: variants of every position, original word first
+pub fn tokens(analyzer: &TextAnalyzer, text: &str) -> Vec<Vec<String>> {
+  let mut positions: Vec<(usize, Vec<String>)> = Vec::new();
+
+  let mut stream = analyzer.token_stream(text);
+  while stream.advance() {
+    let token = stream.token();
+    match positions.last_mut() {
+      Some((position, variants)) if *position == token.position => {
+        if !variants.contains(&token.text) {
+          variants.push(token.text.clone());
+        }
+      },
+      _ => positions.push((token.position, vec![token.text.clone()])),
+    }
+  }
+
+  positions.into_iter().map(|(_, variants)| variants).collect()
+}
+
+/// transliteration of words, those without alternative stay as is
+pub fn transliterate_text(text: &str) -> String {
+  text
+    .split_whitespace()
+    .map(|word| transliterate(word).unwrap_or_else(|| word.to_string()))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Cyrillic word in Latin letters and Latin word in Cyrillic ones, lowercase input expected
+pub fn transliterate(word: &str) -> Option<String> {
+  let result = if word.chars().any(is_cyrillic) {
+    word.chars().map(to_latin).collect()
+  } else if word.chars().any(|c| c.is_ascii_lowercase()) {
+    to_cyrillic(word)
+  } else {
+    return None;
+  };
+
+  if result == word {
+    None
+  } else {
+    Some(result)
+  }
+}
+
+fn is_cyrillic(c: char) -> bool {
+  ('а'..='я').contains(&c) || c == 'ё'
+}
+
+fn to_latin(c: char) -> String {
+  let latin = match c {
+    'а' => "a",
+    'б' => "b",
+    'в' => "v",
+    'г' => "g",
+    'д' => "d",
+    'е' | 'ё' | 'э' => "e",
+    'ж' => "zh",
+    'з' => "z",
+    'и' => "i",
+    'й' | 'ы' => "y",
+    'к' => "k",
+    'л' => "l",
+    'м' => "m",
+    'н' => "n",
+    'о' => "o",
+    'п' => "p",
+    'р' => "r",
+    'с' => "s",
+    'т' => "t",
+    'у' => "u",
+    'ф' => "f",
+    'х' => "kh",
+    'ц' => "ts",
+    'ч' => "ch",
+    'ш' => "sh",
+    'щ' => "shch",
+    'ъ' | 'ь' => "",
+    'ю' => "yu",
+    'я' => "ya",
+    _ => return c.to_string(),
+  };
+  latin.to_string()
+}
+
+// longest combinations first
+const LATIN: [(&str, &str); 16] = [
+  ("shch", "щ"),
+  ("zh", "ж"),
+  ("kh", "х"),
+  ("ts", "ц"),
+  ("ch", "ч"),
+  ("sh", "ш"),
+  ("yu", "ю"),
+  ("ya", "я"),
+  ("yo", "е"),
+  ("ph", "ф"),
+  ("th", "т"),
+  ("ck", "к"),
+  ("qu", "кв"),
+  ("ce", "це"),
+  ("ci", "ци"),
+  ("cy", "ци"),
+];
+
+fn to_cyrillic(word: &str) -> String {
+  let mut result = String::with_capacity(word.len() * 2);
+
+  let mut rest = word;
+  'outer: while let Some(c) = rest.chars().next() {
+    for (latin, cyrillic) in LATIN {
+      if rest.starts_with(latin) {
+        result.push_str(cyrillic);
+        rest = &rest[latin.len()..];
+        continue 'outer;
+      }
+    }
+
+    let cyrillic = match c {
+      'a' => "а",
+      'b' => "б",
+      'c' | 'k' | 'q' => "к",
+      'd' => "д",
+      'e' => "е",
+      'f' => "ф",
+      'g' => "г",
+      'h' => "х",
+      'i' => "и",
+      'j' => "й",
+      'l' => "л",
+      'm' => "м",
+      'n' => "н",
+      'o' => "о",
+      'p' => "п",
+      'r' => "р",
+      's' => "с",
+      't' => "т",
+      'u' => "у",
+      'v' | 'w' => "в",
+      'x' => "кс",
+      // vowel after consonant, semivowel otherwise
+      'y' => match result.chars().last() {
+        Some(prev) if !"аеиоуюя".contains(prev) => "и",
+        _ => "й",
+      },
+      'z' => "з",
+      _ => "",
+    };
+    if cyrillic.is_empty() {
+      result.push(c);
+    } else {
+      result.push_str(cyrillic);
+    }
+    rest = &rest[c.len_utf8()..];
+  }
+
+  result
+}
+
+/// `ё` to `е` and transliteration of word as next token at same position
+#[derive(Clone)]
+pub struct Transliteration;
+
+impl TokenFilter for Transliteration {
+  fn transform<'a>(&self, token_stream: BoxTokenStream<'a>) -> BoxTokenStream<'a> {
+    BoxTokenStream::from(TransliterationStream {
+      tail: token_stream,
+      variant: None,
+      token: Token::default(),
+    })
+  }
+}
+
+struct TransliterationStream<'a> {
+  tail: BoxTokenStream<'a>,
+  variant: Option<Token>,
+  token: Token,
+}
+
+impl<'a> TokenStream for TransliterationStream<'a> {
+  fn advance(&mut self) -> bool {
+    if let Some(variant) = self.variant.take() {
+      self.token = variant;
+      return true;
+    }
+
+    if !self.tail.advance() {
+      return false;
+    }
+
+    let mut token = self.tail.token().clone();
+    token.text = token.text.replace('ё', "е");
+
+    self.variant = transliterate(&token.text).map(|text| Token { text, ..token.clone() });
+    self.token = token;
+    true
+  }
+
+  fn token(&self) -> &Token {
+    &self.token
+  }
+
+  fn token_mut(&mut self) -> &mut Token {
+    &mut self.token
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_transliterate() {
+    assert_eq!(transliterate("парацетамол").unwrap(), "paratsetamol");
+    assert_eq!(transliterate("paratsetamol").unwrap(), "парацетамол");
+    assert_eq!(transliterate("paracetamol").unwrap(), "парацетамол");
+    assert_eq!(transliterate("citramon").unwrap(), "цитрамон");
+    assert_eq!(transliterate("shchi").unwrap(), "щи");
+    assert_eq!(transliterate("hydroxyzine").unwrap(), "хидроксизине");
+    assert_eq!(transliterate("100"), None);
+
+    assert_eq!(transliterate_text("аспирин 500 mg"), "aspirin 500 мг");
+  }
+
+  #[test]
+  fn test_tokens() {
+    let analyzer = analyzer();
+    let first = |text: &str| tokens(&analyzer, text)[0][0].clone();
+
+    // original word first, then transliteration, both stemmed
+    let words = tokens(&analyzer, "Ёлочные Игрушки, aspirin");
+    assert_eq!(words.len(), 3);
+    assert_eq!(words[0][0], first("елочный"));
+    assert_eq!(words[0][1], first("elochnye"));
+    assert_eq!(words[1][0], first("игрушка"));
+    assert_eq!(words[2], vec!["aspirin", "аспирин"]);
+
+    // word forms share stem
+    assert_eq!(first("таблетки"), first("таблетка"));
+    assert_eq!(first("аспирина"), first("аспирин"));
+    assert_eq!(first("tablets"), first("tablet"));
+  }
+}
@@ -1,22 +1,49 @@
 use super::*;
 
+use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use tantivy::collector::TopDocs;
+use tantivy::collector::{FacetCollector, TopDocs};
 use tantivy::directory::MmapDirectory;
-use tantivy::query::QueryParser;
-use tantivy::schema::{Field, Schema, STORED, TEXT};
-use tantivy::{doc, Directory, Index, IndexReader, IndexWriter, ReloadPolicy, Term};
+use tantivy::query::{AllQuery, BooleanQuery, Occur, PhraseQuery, Query, TermQuery};
+use tantivy::schema::{
+  Facet, FacetOptions, Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, STORED,
+  STRING,
+};
+use tantivy::tokenizer::TextAnalyzer;
+use tantivy::{
+  Directory, Document, Index, IndexReader, IndexWriter, ReloadPolicy, Searcher, TantivyError, Term,
+};
 use uuid::Uuid;
 
+use crate::text_search::analyzer::{self, TOKENIZER};
+
 const COMMIT_RATE: usize = 500;
 const COMMIT_TIME: Duration = Duration::from_secs(1);
 
+/// facet fields, path of facet starts with name of field: `/category/<uuid>/<uuid>`
+pub const FACETS: [&str; 3] = ["category", "storage", "has_balance"];
+
+/// How words of query match documents
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Match {
+  /// words in same order, word forms are equal
+  Phrase,
+  /// every word, its transliteration or synonym
+  Terms,
+}
+
+/// Index of workspace documents: `uuid`, context path at `ctx`, values of indexed fields at `text`
+/// and facets at fields of `FACETS`
 #[derive(Clone)]
 pub struct TantivyEngine {
   index: Index,
+  analyzer: TextAnalyzer,
+  // analyzed word to alternatives of it, alternative is list of positions
+  synonyms: HashMap<String, Vec<Vec<Vec<String>>>>,
   added_events: usize,
   writer: Arc<Mutex<IndexWriter>>,
   reader: Arc<Mutex<IndexReader>>,
@@ -24,29 +51,73 @@ pub struct TantivyEngine {
 }
 
 impl TantivyEngine {
-  pub fn new() -> Self {
+  pub fn open(path: &Path) -> Result<Self, tantivy::TantivyError> {
     let mut schema_builder = Schema::builder();
-    schema_builder.add_text_field("uuid", TEXT | STORED);
-    schema_builder.add_text_field("name", TEXT);
+    schema_builder.add_text_field("uuid", STRING | STORED);
+    schema_builder.add_text_field("ctx", STRING | STORED);
+    let text = TextFieldIndexing::default()
+      .set_tokenizer(TOKENIZER)
+      .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    schema_builder
+      .add_text_field("text", TextOptions::default().set_indexing_options(text).set_stored());
+    for name in FACETS {
+      schema_builder.add_facet_field(name, FacetOptions::default());
+    }
 
     let schema = schema_builder.build();
 
-    let path = "./data/tantivy";
-    fs::create_dir_all(path).unwrap();
+    let index = match TantivyEngine::index(path, schema.clone()) {
+      Err(TantivyError::SchemaError(e)) => {
+        // index of older schema can't be upgraded, it is filled again by reindex
+        log::warn!("search index {} is dropped, reindex is required: {e}", path.to_string_lossy());
+        fs::remove_dir_all(path)?;
+        TantivyEngine::index(path, schema)?
+      },
+      result => result?,
+    };
 
-    let directory = MmapDirectory::open(path).unwrap();
-    let directory: Box<dyn Directory> = Box::new(directory);
-    let index = Index::open_or_create(directory, schema).unwrap();
+    let analyzer = analyzer::analyzer();
+    index.tokenizers().register(TOKENIZER, analyzer.clone());
 
-    let writer = index.writer(3_000_000).unwrap();
-    let reader = index.reader_builder().reload_policy(ReloadPolicy::OnCommit).try_into().unwrap();
+    let writer = index.writer(3_000_000)?;
+    let reader = index.reader_builder().reload_policy(ReloadPolicy::OnCommit).try_into()?;
 
-    Self {
+    Ok(Self {
       index,
+      analyzer,
+      synonyms: HashMap::new(),
       added_events: 0,
       commit_timestamp: std::time::Instant::now(),
       writer: Arc::new(Mutex::new(writer)),
       reader: Arc::new(Mutex::new(reader)),
+    })
+  }
+
+  fn index(path: &Path, schema: Schema) -> Result<Index, tantivy::TantivyError> {
+    fs::create_dir_all(path)?;
+
+    let directory = MmapDirectory::open(path)?;
+    let directory: Box<dyn Directory> = Box::new(directory);
+    Index::open_or_create(directory, schema)
+  }
+
+  /// groups of words or phrases with same meaning, only single words are expanded at query
+  pub fn set_synonyms(&mut self, groups: &[Vec<String>]) {
+    self.synonyms.clear();
+    for group in groups {
+      let entries: Vec<Vec<Vec<String>>> =
+        group.iter().map(|entry| analyzer::tokens(&self.analyzer, entry)).collect();
+
+      for (i, entry) in entries.iter().enumerate() {
+        if let [word] = entry.as_slice() {
+          let alternatives = self.synonyms.entry(word[0].clone()).or_default();
+          for (j, other) in entries.iter().enumerate() {
+            if i != j && !other.is_empty() && !alternatives.contains(other) {
+              alternatives.push(other.clone());
+            }
+          }
+        }
+      }
     }
   }
 
@@ -63,7 +134,6 @@ impl TantivyEngine {
       || (self.added_events > 0
         && (self.added_events >= COMMIT_RATE || self.commit_timestamp.elapsed() >= COMMIT_TIME))
     {
-      // println!("TantivyEngine: commit, {} {:?}", self.added_events, self.commit_timestamp.elapsed());
       self.writer.lock().unwrap().commit()?;
       self.added_events = 0;
       self.commit_timestamp = std::time::Instant::now();
@@ -74,25 +144,39 @@ impl TantivyEngine {
     }
   }
 
-  pub fn insert(&mut self, id: Uuid, text: &str) -> Result<bool, tantivy::TantivyError> {
-    let (uuid, name) = self.schematic();
+  /// replace document of `id`
+  pub fn insert(
+    &mut self,
+    id: Uuid,
+    ctx: &str,
+    texts: &[String],
+    facets: &[String],
+  ) -> Result<bool, tantivy::TantivyError> {
+    let (uuid, context, text) = self.schematic();
 
-    {
-      let writer = self.writer.lock()?;
-      match writer.add_document(doc! {
-        uuid => id.to_string(),
-        name => text,
-      }) {
-        Ok(_) => {},
-        Err(e) => return Err(e),
+    let mut doc = Document::new();
+    doc.add_text(uuid, id.to_string());
+    doc.add_text(context, ctx);
+    for value in texts {
+      doc.add_text(text, value);
+    }
+    for path in facets {
+      if let Some((field, facet)) = self.facet(path) {
+        doc.add_facet(field, facet);
       }
     }
 
+    {
+      let writer = self.writer.lock().unwrap();
+      writer.delete_term(Term::from_field_text(uuid, &id.to_string()));
+      writer.add_document(doc)?;
+    }
+
     self.commit()
   }
 
   pub fn delete(&mut self, id: Uuid) -> Result<bool, tantivy::TantivyError> {
-    let uuid = self.index.schema().get_field("uuid").unwrap();
+    let (uuid, _, _) = self.schematic();
 
     {
       let writer = self.writer.lock().unwrap();
@@ -102,59 +186,206 @@ impl TantivyEngine {
     self.force_commit()
   }
 
-  pub fn search(&self, input: &str) -> Vec<Uuid> {
-    let (uuid, name) = self.schematic();
+  /// documents of context matching query and all of drilldown facets with their scores, best
+  /// first; empty query match every document of context
+  pub fn search(
+    &self,
+    ctx: &str,
+    input: &str,
+    mode: Match,
+    drilldown: &[String],
+  ) -> Vec<(Uuid, f64)> {
+    let (uuid, _, _) = self.schematic();
 
     let reader = self.reader.lock().unwrap();
     let searcher = reader.searcher();
 
-    let parser = QueryParser::for_index(&self.index, vec![name]);
-    let query = match parser.parse_query(input) {
-      Ok(q) => q,
-      Err(e) => {
-        eprintln!("error at parsing query: {input} {e}");
-        return vec![];
-      },
+    let query = match self.query(ctx, input, mode, drilldown) {
+      Some(query) => query,
+      None => return vec![],
     };
 
-    let top_docs = match searcher.search(
-      // &query, &TopDocs::with_limit(page_size)
-      &query,
-      &TopDocs::with_limit(100),
-    ) {
+    // all matches are needed for total and ranking with other results
+    let limit = searcher.num_docs().max(1) as usize;
+
+    let top_docs = match searcher.search(&query, &TopDocs::with_limit(limit)) {
       Ok(r) => r,
       Err(e) => {
-        eprintln!("error at query: {e}");
+        log::warn!("error at query: {e}");
         return vec![];
       },
     };
 
     top_docs
       .iter()
-      .map(|(_score, doc_address)| match searcher.doc(*doc_address) {
-        Ok(doc) => match doc.get_first(uuid) {
-          None => UUID_NIL,
-          Some(id) => match id.as_text() {
-            None => UUID_NIL,
-            Some(id) => match Uuid::parse_str(id) {
-              Ok(r) => r,
-              Err(e) => {
-                eprintln!("error at uuid parse: {id} {e}");
-                UUID_NIL
-              },
-            },
-          },
-        },
-        Err(_) => UUID_NIL,
+      .filter_map(|(score, address)| searcher.doc(*address).ok().map(|doc| (*score, doc)))
+      .filter_map(|(score, doc)| {
+        let id = doc.get_first(uuid).and_then(|id| id.as_text())?;
+        Uuid::parse_str(id).ok().map(|id| (id, score as f64))
+      })
+      .filter(|(id, _)| *id != UUID_NIL)
+      .collect()
+  }
+
+  /// counts of children of requested facets (`category` or `/category/<uuid>`) at documents
+  /// matching query and drilldown
+  pub fn facet_counts(
+    &self,
+    ctx: &str,
+    input: &str,
+    drilldown: &[String],
+    facets: &[String],
+  ) -> Vec<(String, Vec<(String, u64)>)> {
+    let reader = self.reader.lock().unwrap();
+    let searcher = reader.searcher();
+
+    let query = self.query(ctx, input, Match::Terms, drilldown);
+
+    facets
+      .iter()
+      .map(|name| {
+        let path = if name.starts_with('/') { name.clone() } else { format!("/{name}") };
+        let counts = match (&query, self.facet(&path)) {
+          (Some(query), Some((field, _))) => count(&searcher, query.as_ref(), field, &path),
+          _ => vec![],
+        };
+        (name.clone(), counts)
       })
-      .filter(|id| *id != UUID_NIL)
       .collect()
   }
 
-  fn schematic(&self) -> (Field, Field) {
+  fn query(
+    &self,
+    ctx: &str,
+    input: &str,
+    mode: Match,
+    drilldown: &[String],
+  ) -> Option<Box<dyn Query>> {
+    let (_, context, _) = self.schematic();
+
+    let query: Box<dyn Query> =
+      if input.trim().is_empty() { Box::new(AllQuery) } else { self.text_query(input, mode)? };
+
+    let in_context: Box<dyn Query> =
+      Box::new(TermQuery::new(Term::from_field_text(context, ctx), IndexRecordOption::Basic));
+
+    let mut clauses = vec![(Occur::Must, query), (Occur::Must, in_context)];
+    for path in drilldown {
+      match self.facet(path) {
+        // facet term match documents of path and of its descendants
+        Some((field, facet)) => clauses.push((
+          Occur::Must,
+          Box::new(TermQuery::new(Term::from_facet(field, &facet), IndexRecordOption::Basic)),
+        )),
+        None => log::debug!("unknown facet {path}"),
+      }
+    }
+
+    Some(Box::new(BooleanQuery::new(clauses)))
+  }
+
+  fn text_query(&self, input: &str, mode: Match) -> Option<Box<dyn Query>> {
+    let words = analyzer::tokens(&self.analyzer, input);
+    if words.is_empty() {
+      log::debug!("no words at query: {input}");
+      return None;
+    }
+
+    match mode {
+      Match::Phrase => Some(self.phrase(&words)),
+      Match::Terms => {
+        let clauses = words
+          .iter()
+          .map(|variants| {
+            let mut alternatives: Vec<(Occur, Box<dyn Query>)> =
+              variants.iter().map(|variant| (Occur::Should, self.term(variant))).collect();
+            for synonym in self.synonyms.get(&variants[0]).into_iter().flatten() {
+              alternatives.push((Occur::Should, self.phrase(synonym)));
+            }
+            let alternatives: Box<dyn Query> = Box::new(BooleanQuery::new(alternatives));
+            (Occur::Must, alternatives)
+          })
+          .collect();
+        Some(Box::new(BooleanQuery::new(clauses)))
+      },
+    }
+  }
+
+  // original words in order
+  fn phrase(&self, words: &[Vec<String>]) -> Box<dyn Query> {
+    let (_, _, text) = self.schematic();
+    match words {
+      [word] => self.term(&word[0]),
+      _ => Box::new(PhraseQuery::new(
+        words.iter().map(|variants| Term::from_field_text(text, &variants[0])).collect(),
+      )),
+    }
+  }
+
+  fn term(&self, word: &str) -> Box<dyn Query> {
+    let (_, _, text) = self.schematic();
+    Box::new(TermQuery::new(Term::from_field_text(text, word), IndexRecordOption::WithFreqs))
+  }
+
+  // field of facet path by its first segment
+  fn facet(&self, path: &str) -> Option<(Field, Facet)> {
+    let name = path.trim_start_matches('/').split('/').next()?;
+    if !FACETS.contains(&name) {
+      return None;
+    }
+    let field = self.index.schema().get_field(name)?;
+    let facet = Facet::from_text(path).ok()?;
+    Some((field, facet))
+  }
+
+  /// all indexed documents as (uuid, ctx, texts)
+  pub fn documents(&self) -> Result<Vec<(Uuid, String, Vec<String>)>, tantivy::TantivyError> {
+    let (uuid, context, text) = self.schematic();
+
+    let reader = self.reader.lock().unwrap();
+    let searcher = reader.searcher();
+
+    let total = searcher.num_docs() as usize;
+    if total == 0 {
+      return Ok(vec![]);
+    }
+
+    let mut result = Vec::with_capacity(total);
+    for (_score, address) in searcher.search(&AllQuery, &TopDocs::with_limit(total))? {
+      let doc = searcher.doc(address)?;
+
+      let id = doc
+        .get_first(uuid)
+        .and_then(|v| v.as_text())
+        .and_then(|v| Uuid::parse_str(v).ok());
+      let ctx = doc.get_first(context).and_then(|v| v.as_text()).unwrap_or_default().to_string();
+      let texts = doc.get_all(text).filter_map(|v| v.as_text()).map(|v| v.to_string()).collect();
+
+      if let Some(id) = id {
+        result.push((id, ctx, texts));
+      }
+    }
+    Ok(result)
+  }
+
+  fn schematic(&self) -> (Field, Field, Field) {
     let schema = self.index.schema();
     let uuid = schema.get_field("uuid").unwrap();
-    let name = schema.get_field("name").unwrap();
-    (uuid, name)
+    let ctx = schema.get_field("ctx").unwrap();
+    let text = schema.get_field("text").unwrap();
+    (uuid, ctx, text)
+  }
+}
+
+fn count(searcher: &Searcher, query: &dyn Query, field: Field, path: &str) -> Vec<(String, u64)> {
+  let mut collector = FacetCollector::for_field(field);
+  collector.add_facet(path);
+
+  match searcher.search(query, &collector) {
+    Ok(counts) => counts.get(path).map(|(facet, count)| (facet.to_string(), count)).collect(),
+    Err(e) => {
+      log::warn!("error at facet counts: {e}");
+      vec![]
+    },
   }
 }
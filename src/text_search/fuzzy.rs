@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
+
+// minimal similarity of words to match
+const THRESHOLD: f64 = 0.8;
+
+/// In-memory fuzzy search by Jaro-Winkler similarity of words. Words and documents are kept
+/// ordered, so same index and pattern always give same scores.
+#[derive(Clone, Debug, Default)]
+pub struct Fuzzy {
+  words: HashMap<Uuid, Vec<String>>,
+  documents: BTreeMap<String, BTreeSet<Uuid>>,
+}
+
+impl Fuzzy {
+  pub fn new() -> Self {
+    Fuzzy::default()
+  }
+
+  pub fn insert(&mut self, id: Uuid, text: &str) {
+    self.delete(&id);
+
+    let words: Vec<String> = split(text);
+    for word in &words {
+      self.documents.entry(word.clone()).or_default().insert(id);
+    }
+    self.words.insert(id, words);
+  }
+
+  pub fn delete(&mut self, id: &Uuid) {
+    for word in self.words.remove(id).unwrap_or_default() {
+      if let Some(ids) = self.documents.get_mut(&word) {
+        ids.remove(id);
+        if ids.is_empty() {
+          self.documents.remove(&word);
+        }
+      }
+    }
+  }
+
+  pub fn contains(&self, id: &Uuid) -> bool {
+    self.words.contains_key(id)
+  }
+
+  /// documents with score, sum of best similarity of every pattern word
+  pub fn search(&self, pattern: &str) -> Vec<(Uuid, f64)> {
+    let mut scores: BTreeMap<Uuid, f64> = BTreeMap::new();
+
+    for pattern in split(pattern) {
+      let mut best: BTreeMap<Uuid, f64> = BTreeMap::new();
+      for (word, ids) in &self.documents {
+        let score = jaro_winkler(&pattern, word);
+        if score < THRESHOLD {
+          continue;
+        }
+        for id in ids {
+          let current = best.entry(*id).or_insert(0.0);
+          if score > *current {
+            *current = score;
+          }
+        }
+      }
+      for (id, score) in best {
+        *scores.entry(id).or_insert(0.0) += score;
+      }
+    }
+
+    scores.into_iter().collect()
+  }
+}
+
+fn split(text: &str) -> Vec<String> {
+  text
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .map(|word| word.to_lowercase())
+    .collect()
+}
+
+fn jaro_winkler(a: &str, b: &str) -> f64 {
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+
+  let jaro = jaro(&a, &b);
+
+  // common prefix up to 4 chars
+  let prefix = a.iter().zip(b.iter()).take(4).take_while(|(x, y)| x == y).count() as f64;
+
+  jaro + prefix * 0.1 * (1.0 - jaro)
+}
+
+fn jaro(a: &[char], b: &[char]) -> f64 {
+  if a.is_empty() && b.is_empty() {
+    return 1.0;
+  }
+  if a.is_empty() || b.is_empty() {
+    return 0.0;
+  }
+
+  let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+
+  let mut a_matched = vec![false; a.len()];
+  let mut b_matched = vec![false; b.len()];
+
+  let mut matches = 0;
+  for (i, x) in a.iter().enumerate() {
+    let from = i.saturating_sub(window);
+    let till = (i + window + 1).min(b.len());
+    for j in from..till {
+      if !b_matched[j] && b[j] == *x {
+        a_matched[i] = true;
+        b_matched[j] = true;
+        matches += 1;
+        break;
+      }
+    }
+  }
+
+  if matches == 0 {
+    return 0.0;
+  }
+
+  let a_seq = a.iter().zip(a_matched).filter(|(_, m)| *m).map(|(c, _)| c);
+  let b_seq = b.iter().zip(b_matched).filter(|(_, m)| *m).map(|(c, _)| c);
+  let transpositions = a_seq.zip(b_seq).filter(|(x, y)| x != y).count() / 2;
+
+  let m = matches as f64;
+  (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64) / m) / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fuzzy() {
+    assert!((jaro_winkler("martha", "marhta") - 0.9611).abs() < 0.001);
+    assert_eq!(jaro_winkler("аспирин", "аспирин"), 1.0);
+
+    let mut fuzzy = Fuzzy::new();
+    let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    fuzzy.insert(a, "Парацетамол таблетки");
+    fuzzy.insert(b, "парацетамол детский");
+    fuzzy.insert(c, "ибупрофен");
+
+    // typo and prefix
+    let found: Vec<Uuid> = fuzzy.search("парацитамол").into_iter().map(|(id, _)| id).collect();
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&a) && found.contains(&b));
+    assert_eq!(fuzzy.search("ибупро").len(), 1);
+
+    fuzzy.delete(&a);
+    assert_eq!(fuzzy.search("парацитамол").len(), 1);
+    assert!(!fuzzy.contains(&a));
+    assert!(fuzzy.documents.get("таблетки").is_none());
+  }
+}
@@ -1,8 +1,12 @@
+mod analyzer;
 mod engine_tantivy;
+mod fuzzy;
 mod processing;
+mod ranking;
 mod tantivy_search;
 
 pub use processing::handle_mutation;
+pub use processing::handle_stock;
 pub use processing::SearchEngine;
 pub use tantivy_search::TextSearch;
 use uuid::{uuid, Uuid};
@@ -1,12 +1,24 @@
 use super::*;
 
+use chrono::Utc;
 use json::JsonValue;
-use simsearch::SimSearch;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use store::elements::{Goods, Store};
+use store::operations::OpMutation;
+use store::GetWarehouse;
 use uuid::Uuid;
-
-use crate::{
-  commutator::Application, storage::Workspaces, text_search::engine_tantivy::TantivyEngine,
-};
+use values::ID;
+
+use crate::commutator::Application;
+use crate::memories::show_deleted;
+use crate::settings::SearchConfig;
+use crate::storage::organizations::Workspace;
+use crate::storage::Workspaces;
+use crate::text_search::analyzer::transliterate_text;
+use crate::text_search::engine_tantivy::{Match, TantivyEngine};
+use crate::text_search::fuzzy::Fuzzy;
+use crate::text_search::ranking::{page, rank};
+use service::utils::json::JsonParams;
 
 #[derive(Debug)]
 pub enum Error {
@@ -33,197 +45,657 @@ impl From<std::io::Error> for Error {
   }
 }
 
+/// storages with non-zero balance of goods
+pub type Stock = HashMap<Goods, BTreeSet<Store>>;
+
+// documents of workspace: persistent index and in-memory fuzzy search by context
 #[derive(Clone)]
-pub struct SearchEngine {
-  sim: SimSearch<Uuid>,
+struct WorkspaceIndex {
   tan: TantivyEngine,
+  fuzzy: HashMap<String, Fuzzy>,
+}
+
+#[derive(Clone)]
+pub struct SearchEngine {
+  config: SearchConfig,
+  workspaces: HashMap<ID, WorkspaceIndex>,
 }
 
 impl SearchEngine {
-  pub fn new() -> Self {
-    Self { sim: SimSearch::new(), tan: TantivyEngine::new() }
+  pub fn new(config: &SearchConfig) -> Self {
+    Self { config: config.clone(), workspaces: HashMap::new() }
   }
 
+  /// open indexes of all workspaces
   pub fn load(&mut self, workspaces: Workspaces) -> Result<(), service::error::Error> {
     for ws in workspaces.list()? {
-      let memories = ws.memories(vec!["drugs".to_string()]);
+      self
+        .open(&ws)
+        .map_err(|e| service::error::Error::GeneralError(format!("search index: {e:?}")))?;
+    }
+    Ok(())
+  }
+
+  /// values of indexed fields of document at context
+  pub fn texts(&self, ws: &Workspace, ctx: &[String], data: &JsonValue) -> Vec<String> {
+    let mut texts = Vec::new();
+    for field in self.config.fields(ctx) {
+      let mut path = field.split('.');
+      let mut values = vec![data[path.next().unwrap_or_default()].clone()];
+      for name in path {
+        values = values
+          .into_iter()
+          .map(|value| match value.as_str().map(Uuid::parse_str) {
+            // reference to other document
+            Some(Ok(id)) => {
+              ws.resolve_uuid(&id).and_then(|doc| doc.json().ok()).unwrap_or(JsonValue::Null)
+            },
+            _ => value,
+          })
+          .map(|value| value[name].clone())
+          .collect();
+      }
+      for value in values {
+        collect(&value, &mut texts);
+      }
+    }
+    texts
+  }
 
-      for mem in memories.list(None)? {
-        let jdoc = mem.json()?;
-        let name = jdoc["name"].as_str().unwrap();
-        let uuid = jdoc["_uuid"].as_str().unwrap();
-        let uuid = Uuid::parse_str(uuid).unwrap();
+  /// facet paths of document: category with its parents, storages (with their locations) where
+  /// goods has balance and `has_balance` for goods
+  pub fn facets(
+    &self,
+    ws: &Workspace,
+    ctx: &[String],
+    data: &JsonValue,
+    stock: &Stock,
+  ) -> Vec<String> {
+    let mut facets = Vec::new();
+
+    if let Some(category) = data["category"].uuid_or_none() {
+      facets.push(format!("/category/{}", path_of(ws, category, "parent")));
+    }
 
-        self.sim.insert(uuid, &name);
+    if ctx == ["goods"] {
+      let storages = data["_uuid"].uuid_or_none().and_then(|id| stock.get(&id));
+      for storage in storages.into_iter().flatten() {
+        facets.push(format!("/storage/{}", path_of(ws, *storage, "location")));
       }
+      facets.push(format!("/has_balance/{}", storages.map(|s| !s.is_empty()).unwrap_or(false)));
     }
 
-    Ok(())
+    facets
   }
 
-  pub fn create(&mut self, id: Uuid, text: &str) -> Result<(), tantivy::TantivyError> {
-    let text = text.to_lowercase().replace("ё", "е");
+  fn open(&mut self, ws: &Workspace) -> Result<&mut WorkspaceIndex, Error> {
+    if !self.workspaces.contains_key(&ws.id) {
+      let mut path = ws.folder().clone();
+      path.push("search");
 
-    self.sim.insert(id, text.as_str());
-    self.tan.insert(id, text.as_str())?;
-    Ok(())
+      let mut tan = TantivyEngine::open(&path)?;
+      tan.set_synonyms(&ws.synonyms().groups()?);
+
+      // fuzzy search is rebuilt from stored values
+      let mut fuzzy: HashMap<String, Fuzzy> = HashMap::new();
+      for (id, ctx, texts) in tan.documents()? {
+        fuzzy.entry(ctx).or_default().insert(id, &fuzzy_text(&texts));
+      }
+
+      self.workspaces.insert(ws.id, WorkspaceIndex { tan, fuzzy });
+    }
+    Ok(self.workspaces.get_mut(&ws.id).unwrap())
   }
 
-  pub fn change(
+  pub fn create(
     &mut self,
+    ws: &Workspace,
+    ctx: &[String],
     id: Uuid,
-    _before: &str,
-    after: &str,
-  ) -> Result<(), tantivy::TantivyError> {
-    self.delete(&id)?;
-    self.create(id, after)?;
+    texts: &[String],
+    facets: &[String],
+  ) -> Result<(), Error> {
+    let texts: Vec<String> = texts.iter().map(|text| normalize(text)).collect();
+    let ctx = ctx.join("/");
+
+    let index = self.open(ws)?;
+    index.fuzzy.entry(ctx.clone()).or_default().insert(id, &fuzzy_text(&texts));
+    index.tan.insert(id, &ctx, &texts, facets)?;
     Ok(())
   }
 
-  pub fn delete(&mut self, id: &Uuid) -> Result<(), tantivy::TantivyError> {
-    self.sim.delete(id);
-    self.tan.delete(*id)?;
+  /// apply changed synonyms of workspace
+  pub fn reload_synonyms(&mut self, ws: &Workspace) -> Result<(), Error> {
+    let groups = ws.synonyms().groups()?;
+    self.open(ws)?.tan.set_synonyms(&groups);
     Ok(())
   }
 
-  pub fn search(&self, text: &str, page_size: usize, offset: usize) -> (usize, Vec<Uuid>) {
-    // println!("page_size = {page_size}, offset = {offset}");
-    let text = text.to_lowercase().replace("ё", "е");
+  pub fn change(
+    &mut self,
+    ws: &Workspace,
+    ctx: &[String],
+    id: Uuid,
+    _before: &[String],
+    after: &[String],
+    facets: &[String],
+  ) -> Result<(), Error> {
+    // index replace document with same uuid
+    self.create(ws, ctx, id, after, facets)
+  }
 
-    let result_full = self.tan.search(&format!("\"{}\"", text));
-    let result_tan = self.tan.search(&text);
-    let result_sim = self.sim.search(&text);
+  pub fn delete(&mut self, ws: &Workspace, ctx: &[String], id: &Uuid) -> Result<(), Error> {
+    let index = self.open(ws)?;
+    if let Some(fuzzy) = index.fuzzy.get_mut(&ctx.join("/")) {
+      fuzzy.delete(id);
+    }
+    index.tan.delete(*id)?;
+    Ok(())
+  }
 
-    let result_tan = remove_duplicates(result_tan, &result_full);
-    let result_tan: Vec<_> = [result_tan, result_full].concat();
+  /// Page of documents matching text: exact phrase first, then all words (in any form,
+  /// transliteration or synonym), then fuzzy matches; by score and uuid within each of them.
+  /// Next page start after `cursor` if it is given, otherwise after `offset` documents.
+  /// Result is total, page and cursor of next page.
+  pub fn search(
+    &self,
+    ws: &ID,
+    ctx: &[String],
+    text: &str,
+    drilldown: &[String],
+    page_size: usize,
+    offset: usize,
+    cursor: Option<&str>,
+  ) -> Result<(usize, Vec<Uuid>, Option<String>), service::error::Error> {
+    let index = match self.workspaces.get(ws) {
+      Some(index) => index,
+      None => return Ok((0, vec![], None)),
+    };
+    let ctx = ctx.join("/");
+
+    let text = normalize(text);
+
+    let hits = if text.trim().is_empty() {
+      rank(vec![], index.tan.search(&ctx, "", Match::Terms, drilldown), vec![])
+    } else {
+      let phrase = index.tan.search(&ctx, &text, Match::Phrase, drilldown);
+      let terms = index.tan.search(&ctx, &text, Match::Terms, drilldown);
+      let mut fuzzy = index.fuzzy.get(&ctx).map(|f| f.search(&text)).unwrap_or_default();
+
+      // fuzzy search know nothing about facets
+      if !drilldown.is_empty() {
+        let allowed: HashSet<Uuid> = index
+          .tan
+          .search(&ctx, "", Match::Terms, drilldown)
+          .into_iter()
+          .map(|(id, _)| id)
+          .collect();
+        fuzzy.retain(|(id, _)| allowed.contains(id));
+      }
 
-    let result_sim = remove_duplicates(result_sim, &result_tan);
+      rank(phrase, terms, fuzzy)
+    };
 
-    // PAGINATION
-    let page_number = offset / page_size;
+    let (items, next) = page(&hits, page_size, offset, cursor)?;
 
-    let total = result_tan.len() + result_sim.len();
+    Ok((hits.len(), items, next))
+  }
 
-    if page_number > total / page_size + 1 {
-      return (0, vec![]);
+  /// counts of requested facets at documents matching text (fuzzy matches are not counted)
+  pub fn facet_counts(
+    &self,
+    ws: &ID,
+    ctx: &[String],
+    text: &str,
+    drilldown: &[String],
+    facets: &[String],
+  ) -> Vec<(String, Vec<(String, u64)>)> {
+    match self.workspaces.get(ws) {
+      Some(index) => index.tan.facet_counts(&ctx.join("/"), &normalize(text), drilldown, facets),
+      None => facets.iter().map(|name| (name.clone(), vec![])).collect(),
     }
+  }
 
-    let half_page = page_size / 2;
-
-    let full_page_tan = result_tan.len() / half_page;
-    let full_page_sim = result_sim.len() / half_page;
-
-    let full_page = full_page_tan.min(full_page_sim);
+  pub fn commit(&mut self) -> Result<(), Error> {
+    for index in self.workspaces.values_mut() {
+      index.tan.force_commit()?;
+    }
+    Ok(())
+  }
+}
 
-    // println!("full_page_tan = {full_page_tan} full_page_sim = {full_page_sim}");
+// uuids from top to `id` joined by `/`, following references at `field`
+fn path_of(ws: &Workspace, id: Uuid, field: &str) -> String {
+  let mut path = vec![id.to_string()];
+  let mut current = id;
+  loop {
+    let parent = ws
+      .resolve_uuid(&current)
+      .and_then(|doc| doc.json().ok())
+      .and_then(|data| data[field].uuid_or_none());
+
+    match parent {
+      // stop at recursion
+      Some(parent) if !path.contains(&parent.to_string()) => {
+        path.insert(0, parent.to_string());
+        current = parent;
+      },
+      _ => break,
+    }
+  }
+  path.join("/")
+}
 
-    if page_number < full_page {
-      let offset = page_number * half_page;
-      let mut result: Vec<Uuid> =
-        result_tan.iter().skip(offset).take(half_page).map(|s| *s).collect();
-      result.extend(result_sim.into_iter().skip(offset).take(half_page));
+// values with their transliteration, so fuzzy search match words typed in other alphabet
+fn fuzzy_text(texts: &[String]) -> String {
+  let text = texts.join(" ");
+  format!("{text} {}", transliterate_text(&text))
+}
 
-      return (total, result);
-    } else if page_number == full_page {
-      if full_page == full_page_tan {
-        // println!("min - tan");
-        let offset = page_number * half_page;
-        let take_tan = result_tan.len() - offset;
-        let mut result: Vec<Uuid> =
-          result_tan.iter().skip(offset).take(take_tan).map(|s| *s).collect();
+fn normalize(text: &str) -> String {
+  text.to_lowercase().replace("ё", "е")
+}
 
-        let take_sim = page_size - take_tan;
-        result.extend(result_sim.into_iter().skip(offset).take(take_sim));
+// strings and numbers, arrays item by item
+fn collect(value: &JsonValue, texts: &mut Vec<String>) {
+  if value.is_array() {
+    value.members().for_each(|item| collect(item, texts));
+  } else if let Some(str) = value.as_str() {
+    if !str.is_empty() {
+      texts.push(str.to_string());
+    }
+  } else if value.is_number() {
+    texts.push(value.dump());
+  }
+}
 
-        return (total, result);
-      } else {
-        // println!("min - sim");
-        // println!("page_number = {page_number} half_page = {half_page} full_page_tan = {full_page_tan} full_page_sim = {full_page_sim} {} {}", cat_0_and_1.len(), result_sim.len());
-        //sim < tan
-        let offset = page_number * half_page;
-        let take = result_sim.len() - offset;
-        let result_sim: Vec<Uuid> = result_sim.iter().skip(offset).take(take).map(|s| *s).collect();
+/// Keep search index of workspace in sync with document change. Deleted documents are removed
+/// from index unless context shows them.
+pub fn handle_mutation(
+  app: &Application,
+  ws: &Workspace,
+  ctx: &Vec<String>,
+  before: &JsonValue,
+  data: &JsonValue,
+) -> Result<(), Error> {
+  let id = match data["_uuid"].as_str().or(before["_uuid"].as_str()).map(Uuid::parse_str) {
+    Some(Ok(id)) if id != UUID_NIL => id,
+    _ => return Ok(()),
+  };
 
-        let take = page_size - take;
-        let result_tan: Vec<Uuid> = result_tan.iter().skip(offset).take(take).map(|s| *s).collect();
-        let result = [result_tan, result_sim].concat();
+  // balance facets are known for goods only
+  let stock = if ctx == &vec!["goods".to_string()] { stock(app, ws, &[id])? } else { Stock::new() };
 
-        return (total, result);
-      }
-    } else {
-      if full_page == full_page_tan {
-        let offset_a = full_page * half_page;
-        let offset_b = page_size - (result_tan.len() - full_page * half_page);
-        let offset_c = page_size * (page_number - full_page - 1);
-        let offset_full = offset_a + offset_b + offset_c;
+  let indexed = |data: &JsonValue| -> (Vec<String>, Vec<String>) {
+    if !data.is_object() || (data["status"] == "deleted" && !show_deleted(ctx)) {
+      return (vec![], vec![]);
+    }
+    let search = app.search.read().unwrap();
+    (search.texts(ws, ctx, data), search.facets(ws, ctx, data, &stock))
+  };
+
+  let (before, before_facets) = indexed(before);
+  let (after, facets) = indexed(data);
+
+  if before == after && before_facets == facets {
+    // IGNORE
+  } else if after.is_empty() {
+    let mut search = app.search.write().unwrap();
+    search.delete(ws, ctx, &id)?;
+  } else if before.is_empty() {
+    let mut search = app.search.write().unwrap();
+    search.create(ws, ctx, id, &after, &facets)?;
+  } else {
+    let mut search = app.search.write().unwrap();
+    search.change(ws, ctx, id, &before, &after, &facets)?;
+  }
+  Ok(())
+}
 
-        // println!("offset_a = {offset_a}, offset_b = {offset_b}, offset_c = {offset_c}");
+/// Refresh balance facets of goods touched by warehouse operations.
+pub fn handle_stock(app: &Application, ws: &Workspace, ops: &[OpMutation]) -> Result<(), Error> {
+  let goods: BTreeSet<Goods> = ops.iter().map(|op| op.goods).collect();
+  if goods.is_empty() {
+    return Ok(());
+  }
 
-        let result: Vec<Uuid> =
-          result_sim.iter().skip(offset_full).take(page_size).map(|s| *s).collect();
+  let stock = stock(app, ws, &goods.iter().cloned().collect::<Vec<_>>())?;
 
-        return (total, result);
-      } else {
-        let offset_a = full_page * half_page;
-        let offset_b = page_size - (result_sim.len() - full_page * half_page);
-        let offset_c = page_size * (page_number - full_page - 1);
-        let offset_full = offset_a + offset_b + offset_c;
+  for id in goods {
+    let doc = match ws.resolve_uuid(&id) {
+      Some(doc) => doc,
+      None => continue,
+    };
+    let ctx = doc.mem.ctx.clone();
+    let data = doc.json()?;
 
-        // println!("offset_a = {offset_a}, offset_b = {offset_b}, offset_c = {offset_c}");
+    if !data.is_object() || (data["status"] == "deleted" && !show_deleted(&ctx)) {
+      continue;
+    }
 
-        let result: Vec<Uuid> =
-          result_tan.iter().skip(offset_full).take(page_size).map(|s| *s).collect();
+    let mut search = app.search.write().unwrap();
+    let texts = search.texts(ws, &ctx, &data);
+    if !texts.is_empty() {
+      let facets = search.facets(ws, &ctx, &data, &stock);
+      search.change(ws, &ctx, id, &texts, &texts, &facets)?;
+    }
+  }
+  Ok(())
+}
 
-        return (total, result);
+// current storages of workspace with balance of given goods, one query per goods and storage
+fn stock(app: &Application, ws: &Workspace, goods: &[Goods]) -> Result<Stock, Error> {
+  let storages: Vec<Store> = ws
+    .memories(vec!["warehouse".into(), "storage".into()])
+    .list(None)?
+    .into_iter()
+    .filter_map(|doc| doc.json().ok().and_then(|data| data["_uuid"].uuid_or_none()))
+    .collect();
+
+  let database = app.warehouse().database;
+  let now = Utc::now();
+
+  let mut stock = Stock::new();
+  for goods in goods {
+    for store in &storages {
+      let balances = database
+        .get_balance_for_one_goods_and_store(now, store, goods)
+        .map_err(|e| service::error::Error::GeneralError(e.message()))?;
+
+      if balances.values().any(|balance| !balance.is_zero()) {
+        stock.entry(*goods).or_default().insert(*store);
       }
     }
   }
+  Ok(stock)
+}
 
-  pub fn commit(&mut self) -> Result<(), Error> {
-    self.tan.force_commit()?;
-    Ok(())
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::memories::MemoriesInFiles;
+  use crate::warehouse::test_util::init;
+  use service::{Context, Services};
+  use std::sync::Arc;
+
+  #[actix_web::test]
+  async fn test_search_fields() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(MemoriesInFiles::new(app.clone(), "memories"));
+
+    let oid = ID::random();
+    let params = |ctx: Vec<&str>| json::object! { oid: oid.to_base64(), ctx: ctx };
+    let memories = app.service("memories");
+
+    let aspirin = memories
+      .create(
+        Context::local(),
+        json::object! { name: "Аспирин", code: "A-100", barcode: "4601234567893" },
+        params(vec!["goods"]),
+      )
+      .unwrap();
+    memories
+      .create(
+        Context::local(),
+        json::object! { name: "Парацетамол", notes: "ёлка" },
+        params(vec!["goods"]),
+      )
+      .unwrap();
+    let pharmacy = memories
+      .create(Context::local(), json::object! { name: "Фармация" }, params(vec!["counterparty"]))
+      .unwrap();
+    memories
+      .create(
+        Context::local(),
+        json::object! { number: "7", counterparty: pharmacy["_uuid"].clone() },
+        params(vec!["documents"]),
+      )
+      .unwrap();
+    app.search.write().unwrap().commit().unwrap();
+
+    let find = |ctx: Vec<&str>, search: &str| {
+      let mut params = params(ctx);
+      params["search"] = search.into();
+      memories.find(Context::local(), params).unwrap()
+    };
+
+    let result = find(vec!["goods"], "аспирин");
+    assert_eq!(result["total"], 1);
+    assert_eq!(result["data"][0]["_uuid"], aspirin["_uuid"]);
+
+    assert_eq!(find(vec!["goods"], "4601234567893")["data"][0]["name"], "Аспирин");
+    assert_eq!(find(vec!["goods"], "елка")["data"][0]["name"], "Парацетамол");
+
+    // index is per context, counterparty is resolved by uuid
+    assert_eq!(find(vec!["counterparty"], "аспирин")["total"], 0);
+    assert_eq!(find(vec!["documents"], "фармация")["data"][0]["number"], "7");
+
+    let mut renamed = aspirin.clone();
+    renamed["name"] = "Ацетилсалициловая кислота".into();
+    renamed.remove("_id");
+    memories
+      .update(
+        Context::local(),
+        aspirin["_id"].as_str().unwrap().to_string(),
+        renamed,
+        params(vec!["goods"]),
+      )
+      .unwrap();
+    app.search.write().unwrap().commit().unwrap();
+
+    assert_eq!(find(vec!["goods"], "аспирин")["total"], 0);
+    assert_eq!(find(vec!["goods"], "кислота")["total"], 1);
+
+    // index is persistent, fuzzy search is restored from it
+    let mut engine = app.search.write().unwrap();
+    engine.workspaces.clear();
+    engine.load(app.wss.clone()).unwrap();
+    let (total, result, _) = engine
+      .search(&oid, &["goods".to_string()], "кислота", &[], 10, 0, None)
+      .unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(result[0].to_string(), aspirin["_uuid"].as_str().unwrap());
+    assert!(engine.workspaces[&oid].fuzzy.contains_key("goods"));
+    drop(engine);
+
+    tmp_dir.close().unwrap();
   }
-}
 
-fn remove_duplicates(mut result_sim: Vec<Uuid>, result_tan: &Vec<Uuid>) -> Vec<Uuid> {
-  result_sim.retain(|e| !result_tan.contains(e));
-  result_sim
-}
+  #[actix_web::test]
+  async fn test_search_facets() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(MemoriesInFiles::new(app.clone(), "memories"));
+
+    let oid = ID::random();
+    let params = |ctx: Vec<&str>| json::object! { oid: oid.to_base64(), ctx: ctx };
+    let memories = app.service("memories");
+    let create = |data: JsonValue, ctx: Vec<&str>| {
+      memories.create(Context::local(), data, params(ctx)).unwrap()
+    };
+
+    let drugs = create(json::object! { name: "drugs" }, vec!["category"]);
+    let pills =
+      create(json::object! { name: "pills", parent: drugs["_uuid"].clone() }, vec!["category"]);
+    let storage = create(json::object! { name: "main" }, vec!["warehouse", "storage"]);
+
+    let aspirin = create(
+      json::object! { name: "aspirin tablets", category: pills["_uuid"].clone() },
+      vec!["goods"],
+    );
+    create(json::object! { name: "bandage", category: drugs["_uuid"].clone() }, vec!["goods"]);
+    create(json::object! { name: "aspirin powder" }, vec!["goods"]);
+
+    let document = create(
+      json::object! { date: "2023-01-05", storage: storage["_uuid"].clone() },
+      vec!["warehouse", "receive", "document"],
+    );
+    create(
+      json::object! {
+        document: document["_uuid"].clone(),
+        goods: aspirin["_uuid"].clone(),
+        qty: { number: 2 },
+        cost: { number: 20 },
+      },
+      vec!["warehouse", "receive"],
+    );
+    app.search.write().unwrap().commit().unwrap();
+
+    let find = |search: &str, facets: Vec<&str>, drilldown: Vec<String>| {
+      let mut params = params(vec!["goods"]);
+      if !search.is_empty() {
+        params["search"] = search.into();
+      }
+      params["facets"] = facets.into();
+      params["drilldown"] = drilldown.into();
+      memories.find(Context::local(), params).unwrap()
+    };
+
+    let drugs_path = format!("/category/{}", drugs["_uuid"].string());
+    let pills_path = format!("{drugs_path}/{}", pills["_uuid"].string());
+
+    let result = find("", vec!["category", "has_balance"], vec![]);
+    assert_eq!(result["total"], 3);
+    assert_eq!(result["facets"]["category"].len(), 1);
+    assert_eq!(result["facets"]["category"][0]["facet"], drugs_path.as_str());
+    assert_eq!(result["facets"]["category"][0]["value"]["name"], "drugs");
+    assert_eq!(result["facets"]["category"][0]["count"], 2);
+
+    let has_balance: Vec<(String, u64)> = result["facets"]["has_balance"]
+      .members()
+      .map(|f| (f["facet"].string(), f["count"].as_u64().unwrap()))
+      .collect();
+    assert!(has_balance.contains(&("/has_balance/true".to_string(), 1)));
+    assert!(has_balance.contains(&("/has_balance/false".to_string(), 2)));
+
+    // children of category
+    let result = find("", vec![drugs_path.as_str()], vec![]);
+    assert_eq!(result["facets"][drugs_path.as_str()][0]["facet"], pills_path.as_str());
+
+    // narrow text search by category and by stock
+    let result = find("aspirin", vec!["storage"], vec![drugs_path.clone()]);
+    assert_eq!(result["total"], 1);
+    assert_eq!(result["data"][0]["_uuid"], aspirin["_uuid"]);
+    assert_eq!(result["facets"]["storage"][0]["value"]["name"], "main");
+
+    let result = find("aspirin", vec![], vec!["/has_balance/false".to_string()]);
+    assert_eq!(result["total"], 1);
+    assert_eq!(result["data"][0]["name"], "aspirin powder");
+
+    let result = find("", vec![], vec![format!("/storage/{}", storage["_uuid"].string())]);
+    assert_eq!(result["total"], 1);
+    assert_eq!(result["data"][0]["_uuid"], aspirin["_uuid"]);
+
+    tmp_dir.close().unwrap();
+  }
 
-pub fn handle_mutation(
-  app: &Application,
-  ctx: &Vec<String>,
-  before: &JsonValue,
-  data: &JsonValue,
-) -> Result<(), Error> {
-  if ctx == &vec!["drugs"] {
-    let id = data["_uuid"]
-      .as_str()
-      .map(|data| Uuid::parse_str(data).unwrap_or(UUID_NIL))
-      .unwrap_or(UUID_NIL);
-    let before_name = before["name"].as_str();
-    let after_name = data["name"].as_str();
-
-    if let Some(before_name) = before_name {
-      if let Some(after_name) = after_name {
-        if before_name == after_name {
-          // IGNORE
-        } else {
-          let mut search = app.search.write().unwrap();
-          search.change(id, before_name, after_name)?;
+  #[actix_web::test]
+  async fn test_search_ranking() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    app.register(MemoriesInFiles::new(app.clone(), "memories"));
+    app.register(crate::services::Synonyms::new(app.clone()));
+
+    let oid = ID::random();
+    let params = json::object! { oid: oid.to_base64(), ctx: ["goods"] };
+    let memories = app.service("memories");
+
+    let catalogue = [
+      "Аспирин Кардио таблетки 100 мг",
+      "Аспирин-С шипучие таблетки",
+      "Парацетамол таблетки 500 мг",
+      "Парацетамол детский суспензия",
+      "Нурофен Экспресс капсулы",
+      "Ибупрофен таблетки 200 мг",
+      "Цитрамон П таблетки",
+      "Кардиомагнил таблетки",
+      "Ацетилсалициловая кислота таблетки 500 мг",
+      "Но-шпа таблетки 40 мг",
+    ];
+    for name in catalogue {
+      memories
+        .create(Context::local(), json::object! { name: name }, params.clone())
+        .unwrap();
+    }
+    app.search.write().unwrap().commit().unwrap();
+
+    let names = |search: &str| -> Vec<String> {
+      let mut params = params.clone();
+      params["search"] = search.into();
+      let result = memories.find(Context::local(), params).unwrap();
+      result["data"].members().map(|item| item["name"].string()).collect()
+    };
+    let top = |search: &str, n: usize| -> Vec<String> {
+      let mut list: Vec<String> = names(search).into_iter().take(n).collect();
+      list.sort();
+      list
+    };
+
+    let paracetamol = vec![catalogue[2].to_string(), catalogue[3].to_string()];
+
+    // other word forms
+    assert_eq!(top("парацетамола", 2), paracetamol);
+    assert_eq!(names("аспирина")[0..2].iter().filter(|n| n.starts_with("Аспирин")).count(), 2);
+
+    // typed in Latin
+    assert_eq!(top("paracetamol", 2), paracetamol);
+    assert_eq!(names("ibuprofen")[0], catalogue[5]);
+    assert_eq!(names("citramon")[0], catalogue[6]);
+    assert_eq!(names("nurofen ekspress")[0], catalogue[4]);
+
+    // all words are required
+    assert_eq!(names("аспирин кардио")[0], catalogue[0]);
+    assert!(names("таблетка").len() >= 8);
+
+    // pages by cursor give same list without repeats
+    {
+      let engine = app.search.read().unwrap();
+      let goods = vec!["goods".to_string()];
+      let (total, full, _) = engine.search(&oid, &goods, "таблетка", &[], 100, 0, None).unwrap();
+      assert_eq!(total, full.len());
+
+      let mut pages = Vec::new();
+      let mut cursor = None;
+      loop {
+        let (count, page, next) =
+          engine.search(&oid, &goods, "таблетка", &[], 3, 0, cursor.as_deref()).unwrap();
+        assert_eq!(count, total);
+        pages.extend(page);
+        match next {
+          Some(next) => cursor = Some(next),
+          None => break,
         }
-      } else {
-        let mut search = app.search.write().unwrap();
-        search.delete(&id)?;
-      }
-    } else {
-      if let Some(after_name) = after_name {
-        let mut search = app.search.write().unwrap();
-        search.create(id, after_name)?;
-      } else {
-        // IGNORE
       }
+      assert_eq!(pages, full);
     }
+
+    // synonyms of workspace
+    app
+      .service("synonyms")
+      .create(
+        Context::local(),
+        json::object! { words: ["аспирин", "ацетилсалициловая кислота"] },
+        json::object! { oid: oid.to_base64() },
+      )
+      .unwrap();
+
+    let found = names("аспирин");
+    assert!(found[0..3].contains(&catalogue[8].to_string()), "{found:?}");
+
+    // synonyms are restored with index
+    let mut engine = app.search.write().unwrap();
+    engine.workspaces.clear();
+    engine.load(app.wss.clone()).unwrap();
+    drop(engine);
+    assert!(names("аспирин")[0..3].contains(&catalogue[8].to_string()));
+
+    tmp_dir.close().unwrap();
   }
-  Ok(())
 }
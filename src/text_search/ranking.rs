@@ -0,0 +1,189 @@
+use service::error::Error;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// How document matched query, better tier first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+  Phrase = 0,
+  Terms = 1,
+  Fuzzy = 2,
+}
+
+impl Tier {
+  fn from_u8(tier: u8) -> Option<Tier> {
+    match tier {
+      0 => Some(Tier::Phrase),
+      1 => Some(Tier::Terms),
+      2 => Some(Tier::Fuzzy),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+  pub tier: Tier,
+  pub score: f64,
+  pub id: Uuid,
+}
+
+impl Hit {
+  // tier, then higher score, then uuid
+  fn order(&self, other: &Hit) -> Ordering {
+    self
+      .tier
+      .cmp(&other.tier)
+      .then_with(|| other.score.total_cmp(&self.score))
+      .then_with(|| self.id.cmp(&other.id))
+  }
+
+  /// opaque position after this hit
+  pub fn cursor(&self) -> String {
+    format!("{}:{:016x}:{}", self.tier as u8, self.score.to_bits(), self.id)
+  }
+
+  fn from_cursor(cursor: &str) -> Option<Hit> {
+    let mut parts = cursor.splitn(3, ':');
+    let tier = Tier::from_u8(parts.next()?.parse().ok()?)?;
+    let score = f64::from_bits(u64::from_str_radix(parts.next()?, 16).ok()?);
+    let id = Uuid::parse_str(parts.next()?).ok()?;
+    Some(Hit { tier, score, id })
+  }
+}
+
+/// Single list of matches: every document once with its best tier, ordered by tier,
+/// score (descending) and uuid.
+pub fn rank(phrase: Vec<(Uuid, f64)>, terms: Vec<(Uuid, f64)>, fuzzy: Vec<(Uuid, f64)>) -> Vec<Hit> {
+  let mut seen = HashSet::new();
+  let mut hits = Vec::with_capacity(phrase.len() + terms.len() + fuzzy.len());
+
+  for (tier, matches) in [(Tier::Phrase, phrase), (Tier::Terms, terms), (Tier::Fuzzy, fuzzy)] {
+    let mut matches: Vec<Hit> =
+      matches.into_iter().map(|(id, score)| Hit { tier, score, id }).collect();
+    // best score of document within tier
+    matches.sort_by(|a, b| a.order(b));
+    for hit in matches {
+      if seen.insert(hit.id) {
+        hits.push(hit);
+      }
+    }
+  }
+
+  hits.sort_by(|a, b| a.order(b));
+  hits
+}
+
+/// Page of ranked hits after `cursor` if it is given, otherwise after `offset` hits,
+/// with cursor of next page if there is one. Cursor that can't be decoded is refused,
+/// otherwise client would get first page again and again.
+pub fn page(
+  hits: &[Hit],
+  size: usize,
+  offset: usize,
+  cursor: Option<&str>,
+) -> Result<(Vec<Uuid>, Option<String>), Error> {
+  let from = match cursor.map(Hit::from_cursor) {
+    Some(Some(after)) => hits.partition_point(|hit| hit.order(&after) != Ordering::Greater),
+    Some(None) => return Err(Error::BadRequest("invalid cursor".into())),
+    None => offset.min(hits.len()),
+  };
+  let till = (from + size).min(hits.len());
+
+  let items = hits[from..till].iter().map(|hit| hit.id).collect();
+  let next = if till < hits.len() && till > from { Some(hits[till - 1].cursor()) } else { None };
+
+  Ok((items, next))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::{Rng, SeedableRng};
+
+  fn random_matches(rng: &mut impl Rng, ids: &[Uuid]) -> Vec<(Uuid, f64)> {
+    let count = rng.gen_range(0..ids.len());
+    (0..count)
+      .map(|_| {
+        let id = ids[rng.gen_range(0..ids.len())];
+        // few distinct scores, so ties are frequent
+        (id, rng.gen_range(0..4) as f64 / 2.0)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_rank() {
+    let (a, b, c) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+
+    let hits = rank(vec![(b, 1.0)], vec![(a, 5.0), (b, 9.0), (c, 5.0)], vec![(c, 1.0), (a, 2.0)]);
+    let ids: Vec<Uuid> = hits.iter().map(|hit| hit.id).collect();
+
+    // phrase first, ties by uuid, duplicates removed
+    assert_eq!(ids, vec![b, a, c]);
+    assert_eq!(hits[1].tier, Tier::Terms);
+  }
+
+  #[test]
+  fn test_pages_concatenation() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(45);
+
+    for _ in 0..200 {
+      let ids: Vec<Uuid> = (0..rng.gen_range(1..40)).map(|_| Uuid::new_v4()).collect();
+
+      let hits = rank(
+        random_matches(&mut rng, &ids),
+        random_matches(&mut rng, &ids),
+        random_matches(&mut rng, &ids),
+      );
+      let full: Vec<Uuid> = hits.iter().map(|hit| hit.id).collect();
+
+      // ranking is stable for shuffled input
+      let mut shuffled = hits.clone();
+      shuffled.reverse();
+      let again = rank(
+        vec![],
+        shuffled
+          .iter()
+          .map(|hit| (hit.id, hit.score - hit.tier as u8 as f64 * 10.0))
+          .collect(),
+        vec![],
+      );
+      assert_eq!(again.iter().map(|hit| hit.id).collect::<Vec<_>>(), full);
+
+      let unique: HashSet<&Uuid> = full.iter().collect();
+      assert_eq!(unique.len(), full.len());
+
+      let size = rng.gen_range(1..10);
+
+      // by cursor
+      let mut pages = Vec::new();
+      let mut cursor: Option<String> = None;
+      loop {
+        let (items, next) = page(&hits, size, 0, cursor.as_deref()).unwrap();
+        assert!(items.len() <= size);
+        pages.extend(items);
+        match next {
+          Some(next) => cursor = Some(next),
+          None => break,
+        }
+      }
+      assert_eq!(pages, full);
+
+      // by offset
+      let mut pages = Vec::new();
+      for offset in (0..full.len() + size).step_by(size) {
+        pages.extend(page(&hits, size, offset, None).unwrap().0);
+      }
+      assert_eq!(pages, full);
+    }
+  }
+
+  #[test]
+  fn test_invalid_cursor() {
+    let hits = rank(vec![(Uuid::new_v4(), 1.0)], vec![], vec![]);
+    assert!(page(&hits, 10, 0, Some("garbage")).is_err());
+    assert!(page(&hits, 10, 0, Some("")).is_err());
+  }
+}
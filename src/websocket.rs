@@ -1,5 +1,8 @@
 use crate::commutator::Commutator;
-use crate::ws::{engine_io, error_general, socket_io, Connect, Disconnect, Event, WsMessage};
+use crate::ws::packet::{handshake, EnginePacket};
+use crate::ws::polling::{PollingConn, Upgrade};
+use crate::ws::socket::{Out, SocketState};
+use crate::ws::{Connect, Disconnect, WsMessage};
 use actix::{
   fut, Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, ContextFutureSpawner, Handler,
   Running, StreamHandler, WrapFuture,
@@ -11,47 +14,37 @@ use service::Context;
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-const CHECKS_INTERVAL: Duration = Duration::from_secs(11);
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(29);
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(41);
-
 pub(crate) const PING_INTERVAL: u16 = 25000;
 pub(crate) const PING_TIMEOUT: u16 = 20000;
 
 pub(crate) struct WsConn {
-  id: Uuid,
-  hb: Instant,
-  com: Addr<Commutator>,
-  ctx: Context,
+  state: SocketState,
+  // polling session waiting for upgrade
+  upgrade: Option<Addr<PollingConn>>,
+  max_payload: usize,
 }
 
 impl WsConn {
-  pub(crate) fn new(request: actix_web::dev::RequestHead, com: Addr<Commutator>) -> Self {
-    WsConn { id: Uuid::new_v4(), hb: Instant::now(), com, ctx: Context::websocket(request) }
+  pub(crate) fn new(
+    request: actix_web::dev::RequestHead,
+    com: Addr<Commutator>,
+    upgrade: Option<(Uuid, Addr<PollingConn>)>,
+    max_payload: usize,
+  ) -> Self {
+    let (id, upgrade) = match upgrade {
+      Some((id, conn)) => (id, Some(conn)),
+      None => (Uuid::new_v4(), None),
+    };
+    let state = SocketState::new(id, Context::websocket(request), com);
+    WsConn { state, upgrade, max_payload }
   }
 
-  fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
-    ctx.run_interval(CHECKS_INTERVAL, |act, ctx| {
-      let duration = Instant::now().duration_since(act.hb);
-      if duration > CLIENT_TIMEOUT {
-        // TODO act.app.do_send(Disconnect::new(act.id));
-        ctx.stop();
-        todo!()
-      } else if duration > HEARTBEAT_INTERVAL {
-        ctx.ping(engine_io::PING.as_bytes());
-      }
-    });
-  }
-}
-
-impl Actor for WsConn {
-  type Context = ws::WebsocketContext<Self>;
-
-  fn started(&mut self, ctx: &mut Self::Context) {
+  fn connect(&self, ctx: &mut ws::WebsocketContext<Self>) {
     let addr = ctx.address();
     self
+      .state
       .com
-      .send(Connect { socket: addr.recipient(), sid: self.id })
+      .send(Connect { socket: addr.recipient(), sid: self.state.sid })
       .into_actor(self)
       .then(|res, _, ctx| {
         match res {
@@ -63,102 +56,104 @@ impl Actor for WsConn {
       .wait(ctx);
   }
 
+  // take over polling session
+  fn upgrade(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+    let conn = match self.upgrade.take() {
+      Some(conn) => conn,
+      None => return,
+    };
+    conn
+      .send(Upgrade)
+      .into_actor(self)
+      .then(|res, act, ctx| {
+        match res {
+          Ok(Some(state)) => {
+            act.state = state;
+            act.state.last_pong = Instant::now();
+            act.connect(ctx);
+          },
+          _ => ctx.stop(),
+        }
+        fut::ready(())
+      })
+      .wait(ctx);
+  }
+
+  fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+    let interval = Duration::from_millis(PING_INTERVAL as u64);
+    let timeout = interval + Duration::from_millis(PING_TIMEOUT as u64);
+    ctx.run_interval(interval, move |act, ctx| {
+      // polling transport ping till upgrade
+      if act.upgrade.is_some() {
+        return;
+      }
+      if act.state.last_pong.elapsed() > timeout {
+        log::debug!("websocket {} timeout", act.state.sid);
+        ctx.stop();
+      } else {
+        ctx.text(EnginePacket::Ping(String::new()).encode());
+      }
+    });
+  }
+
+  fn apply(&mut self, outs: Vec<Out>, ctx: &mut ws::WebsocketContext<Self>) {
+    for out in outs {
+      match out {
+        Out::Text(data) => ctx.text(data),
+        Out::Close => {
+          ctx.close(None);
+          ctx.stop();
+        },
+      }
+    }
+  }
+}
+
+impl Actor for WsConn {
+  type Context = ws::WebsocketContext<Self>;
+
+  fn started(&mut self, ctx: &mut Self::Context) {
+    self.hb(ctx);
+
+    if self.upgrade.is_none() {
+      self.connect(ctx);
+      ctx.text(handshake(&self.state.sid, &[], self.max_payload));
+    }
+  }
+
   fn stopping(&mut self, _: &mut Self::Context) -> Running {
-    self.com.do_send(Disconnect { sid: self.id });
+    // not upgraded polling session stay alive
+    if self.upgrade.is_none() {
+      self.state.com.do_send(Disconnect { sid: self.state.sid });
+    }
     Running::Stop
   }
 }
 
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConn {
   fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
-    println!("msg {:?}", msg);
-
     match msg {
       Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
-      Ok(ws::Message::Text(text)) => {
-        let payload = text.to_string();
-        let code = &payload[..1];
-        let data = &payload[1..];
-        match code {
-          engine_io::OPEN => {},  // TODO
-          engine_io::CLOSE => {}, // TODO
-          engine_io::PING => {
-            self.hb = Instant::now();
-            ctx.text(engine_io::PONG);
-          },
-          engine_io::PONG => {
-            self.hb = Instant::now();
-          },
-          engine_io::MESSAGE => {
-            let code = &data[..1];
-            let data = &data[1..];
-            match code {
-              socket_io::CONNECT => {
-                ctx.text(WsMessage::connect(&self.id).data());
-              },
-              socket_io::DISCONNECT => {}, // TODO
-              socket_io::EVENT => {
-                println!("data: {:?}", data);
-                // "16[\"create\",\"authentication\",{\"strategy\":\"local\",\"email\":\"admin\",\"password\":\"111\"},{}]"
-                if let Some(i) = data.find("[") {
-                  if data.ends_with("]") {
-                    let event_id = data[..i].to_string();
-                    let data = &data[i..];
-
-                    match json::parse(data) {
-                      Ok(mut data) => {
-                        if !data.is_array() {
-                          ctx.text(
-                            WsMessage::ack(event_id, error_general("unsupported event")).data(),
-                          );
-                          return;
-                        }
-
-                        let command = if let Some(str) = data.array_remove(0).as_str() {
-                          str.to_string()
-                        } else {
-                          ctx.text(
-                            WsMessage::ack(event_id, error_general("command is missing")).data(),
-                          );
-                          return;
-                        };
-
-                        let path = if let Some(str) = data.array_remove(0).as_str() {
-                          str.to_string()
-                        } else {
-                          ctx.text(
-                            WsMessage::ack(event_id, error_general("service path is missing"))
-                              .data(),
-                          );
-                          return;
-                        };
-
-                        self.com.do_send(Event {
-                          ctx: self.ctx.clone(),
-                          sid: self.id,
-                          event_id,
-                          path,
-                          command,
-                          data,
-                        });
-                      },
-                      Err(msg) => {
-                        ctx.text(WsMessage::ack(event_id, error_general(msg.to_string())).data());
-                      },
-                    }
-                  }
-                }
-              },
-              engine_io::UPGRADE => {}, // TODO
-              engine_io::NOON => {},    // TODO
-              _ => {},                  // TODO handle errors
-            }
-          },
-          _ => {}, // TODO handle unknown message
-        }
+      Ok(ws::Message::Text(text)) => match EnginePacket::decode(&text) {
+        Ok(EnginePacket::Upgrade) => self.upgrade(ctx),
+        Ok(packet) => {
+          let outs = self.state.on_packet(packet);
+          self.apply(outs, ctx);
+        },
+        Err(e) => log::warn!("websocket {}: {e}", self.state.sid),
+      },
+      // attachments of binary event
+      Ok(ws::Message::Binary(data)) => {
+        let outs = self.state.on_packet(EnginePacket::Binary(data.to_vec()));
+        self.apply(outs, ctx);
       },
-      Ok(ws::Message::Binary(_)) => {
-        // TODO handle binary
+      Ok(ws::Message::Close(reason)) => {
+        ctx.close(reason);
+        ctx.stop();
+      },
+      Err(e) => {
+        log::warn!("websocket {}: {e}", self.state.sid);
+        ctx.stop();
       },
       _ => (),
     }
@@ -169,8 +164,6 @@ impl Handler<WsMessage> for WsConn {
   type Result = ();
 
   fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
-    let data = msg.data();
-    println!("sending: {}", data);
-    ctx.text(data);
+    ctx.text(msg.data());
   }
 }
@@ -0,0 +1,51 @@
+// Records engine.io packets exchanged by socket.io-client 4 with running server in format
+// replayed by `ws::polling` tests:
+//
+//   npm install socket.io-client@4
+//   node capture.js http://localhost:3080 > polling.transcript
+//
+// `>` is packet sent by client, `<` by server, session id is replaced by `{sid}`.
+const { io } = require("socket.io-client");
+
+const url = process.argv[2] || "http://localhost:3080";
+const CODES = { open: "0", close: "1", ping: "2", pong: "3", message: "4", upgrade: "5", noop: "6" };
+
+const lines = [];
+
+// text form of packet, binary is base64 encoded as polling transport does
+function encode(packet) {
+  if (packet.data !== undefined && typeof packet.data !== "string") {
+    return "b" + Buffer.from(packet.data).toString("base64");
+  }
+  return CODES[packet.type] + (packet.data || "");
+}
+
+const socket = io(url, { transports: ["polling"] });
+const engine = socket.io.engine;
+
+engine.on("packet", (packet) => lines.push("< " + encode(packet)));
+engine.on("packetCreate", (packet) => lines.push("> " + encode(packet)));
+
+function done(sid) {
+  for (const line of lines) {
+    console.log(line.split(sid).join("{sid}"));
+  }
+  process.exit(0);
+}
+
+socket.on("connect", () => {
+  // event with acknowledgement, then binary one
+  socket.emit("find", "memories", { oid: "yjmg", ctx: ["goods"] }, () => {
+    socket.emit("create", "memories", Buffer.from([1, 2, 3]), { oid: "yjmg" }, () => {
+      // engine forgets id on close
+      const sid = engine.id;
+      socket.disconnect();
+      setTimeout(() => done(sid), 100);
+    });
+  });
+});
+
+socket.on("connect_error", (e) => {
+  console.error(e.message);
+  process.exit(1);
+});
@@ -1,3 +1,4 @@
+use crate::ws::packet::SocketPacket;
 use crate::ws::{engine_io, socket_io};
 use actix::prelude::*;
 use json::JsonValue;
@@ -12,26 +13,6 @@ pub(crate) struct WsMessage {
 }
 
 impl WsMessage {
-  pub(crate) fn open(sid: &Uuid) -> Self {
-    let data = format!(
-      "{{\"sid\":\"{}\",\"upgrades\":[\"websocket\"],\"pingInterval\":{},\"pingTimeout\":{}}}",
-      sid.to_string(),
-      crate::websocket::PING_INTERVAL,
-      crate::websocket::PING_TIMEOUT
-    );
-    WsMessage { data, engine_code: engine_io::OPEN.into(), socket_code: None }
-  }
-
-  pub(crate) fn connect(sid: &Uuid) -> Self {
-    let data = format!("{{\"sid\":\"{}\"}}", sid.to_string());
-    println!("connect {data}");
-    WsMessage {
-      data,
-      engine_code: engine_io::MESSAGE.into(),
-      socket_code: Some(socket_io::CONNECT.into()),
-    }
-  }
-
   pub(crate) fn event<S: Convertable>(response: S) -> Self {
     let data = response.data();
     WsMessage {
@@ -41,18 +22,10 @@ impl WsMessage {
     }
   }
 
-  pub(crate) fn ack<S: Convertable>(event_id: String, response: S) -> Self {
-    let response = response.data();
-    let data = if response.starts_with("[") {
-      format!("{}{}", event_id, response)
-    } else {
-      format!("{}[{}]", event_id, response)
-    };
-    WsMessage {
-      data,
-      engine_code: engine_io::MESSAGE.into(),
-      socket_code: Some(socket_io::ACK.into()),
-    }
+  pub(crate) fn ack(nsp: &str, event_id: String, response: JsonValue) -> Self {
+    let response = if response.is_array() { response } else { json::array![response] };
+    let packet = SocketPacket::new(socket_io::ACK, nsp, Some(event_id), response);
+    WsMessage { data: packet.encode(), engine_code: engine_io::MESSAGE.into(), socket_code: None }
   }
 
   pub(crate) fn data(self) -> String {
@@ -65,6 +38,8 @@ impl WsMessage {
 pub(crate) struct Event {
   pub(crate) ctx: service::Context,
   pub(crate) sid: Uuid,
+  pub(crate) nsp: String,
+  // empty if acknowledgement isn't requested
   pub(crate) event_id: String,
   pub(crate) path: String,
   pub(crate) command: String,
@@ -1,10 +1,14 @@
 pub(crate) mod engine_io;
 mod messages;
+pub(crate) mod packet;
+pub(crate) mod polling;
+pub(crate) mod socket;
 pub(crate) mod socket_io;
 pub(crate) mod start;
 
 pub(crate) use messages::*;
-pub use start::start_connection;
+pub use polling::Polling;
+pub use start::{polling_post, start_connection};
 
 use json::JsonValue;
 
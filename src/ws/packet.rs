@@ -0,0 +1,300 @@
+use base64::Engine;
+use json::JsonValue;
+use uuid::Uuid;
+
+use crate::ws::{engine_io, socket_io};
+
+// packets separator of polling payload
+pub(crate) const SEPARATOR: char = '\x1e';
+
+/// engine.io v4 packet
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EnginePacket {
+  Open(String),
+  Close,
+  Ping(String),
+  Pong(String),
+  Message(String),
+  Binary(Vec<u8>),
+  Upgrade,
+  Noop,
+}
+
+impl EnginePacket {
+  pub(crate) fn decode(text: &str) -> Result<Self, String> {
+    if text.is_empty() {
+      return Err("empty packet".into());
+    }
+    // type is single ascii character
+    if !text.is_char_boundary(1) {
+      return Err("unknown packet type".into());
+    }
+    let (code, data) = text.split_at(1);
+    match code {
+      engine_io::OPEN => Ok(EnginePacket::Open(data.into())),
+      engine_io::CLOSE => Ok(EnginePacket::Close),
+      engine_io::PING => Ok(EnginePacket::Ping(data.into())),
+      engine_io::PONG => Ok(EnginePacket::Pong(data.into())),
+      engine_io::MESSAGE => Ok(EnginePacket::Message(data.into())),
+      engine_io::UPGRADE => Ok(EnginePacket::Upgrade),
+      engine_io::NOON => Ok(EnginePacket::Noop),
+      // binary packet of polling transport
+      "b" => base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map(EnginePacket::Binary)
+        .map_err(|e| format!("invalid binary packet: {e}")),
+      _ => Err(format!("unknown packet type {code:?}")),
+    }
+  }
+
+  /// text form, binary is base64 encoded as for polling transport
+  pub(crate) fn encode(&self) -> String {
+    match self {
+      EnginePacket::Open(data) => format!("{}{data}", engine_io::OPEN),
+      EnginePacket::Close => engine_io::CLOSE.into(),
+      EnginePacket::Ping(data) => format!("{}{data}", engine_io::PING),
+      EnginePacket::Pong(data) => format!("{}{data}", engine_io::PONG),
+      EnginePacket::Message(data) => format!("{}{data}", engine_io::MESSAGE),
+      EnginePacket::Binary(data) => {
+        format!("b{}", base64::engine::general_purpose::STANDARD.encode(data))
+      },
+      EnginePacket::Upgrade => engine_io::UPGRADE.into(),
+      EnginePacket::Noop => engine_io::NOON.into(),
+    }
+  }
+
+  /// packets of polling request body
+  pub(crate) fn decode_payload(payload: &str) -> Result<Vec<Self>, String> {
+    payload.split(SEPARATOR).map(EnginePacket::decode).collect()
+  }
+
+  pub(crate) fn encode_payload(packets: &[String]) -> String {
+    packets.join(&SEPARATOR.to_string())
+  }
+}
+
+/// data of open packet
+pub(crate) fn handshake(sid: &Uuid, upgrades: &[&str], max_payload: usize) -> String {
+  let data = json::object! {
+    sid: sid.to_string(),
+    upgrades: upgrades.to_vec(),
+    pingInterval: crate::websocket::PING_INTERVAL,
+    pingTimeout: crate::websocket::PING_TIMEOUT,
+    maxPayload: max_payload,
+  };
+  EnginePacket::Open(data.dump()).encode()
+}
+
+/// socket.io v5 packet: <type>[<attachments>-][<namespace>,][<ack id>][<json data>]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SocketPacket {
+  pub(crate) kind: String,
+  pub(crate) nsp: String,
+  pub(crate) attachments: usize,
+  pub(crate) id: Option<String>,
+  pub(crate) data: JsonValue,
+}
+
+impl SocketPacket {
+  pub(crate) fn new(kind: &str, nsp: &str, id: Option<String>, data: JsonValue) -> Self {
+    SocketPacket { kind: kind.into(), nsp: nsp.into(), attachments: 0, id, data }
+  }
+
+  pub(crate) fn decode(text: &str) -> Result<Self, String> {
+    if text.is_empty() {
+      return Err("empty packet".into());
+    }
+    if !text.is_char_boundary(1) {
+      return Err("unknown packet type".into());
+    }
+    let (kind, mut rest) = text.split_at(1);
+    match kind {
+      socket_io::CONNECT
+      | socket_io::DISCONNECT
+      | socket_io::EVENT
+      | socket_io::ACK
+      | socket_io::CONNECT_ERROR
+      | socket_io::BINARY_EVENT
+      | socket_io::BINARY_ACK => {},
+      _ => return Err(format!("unknown packet type {kind:?}")),
+    }
+
+    let mut attachments = 0;
+    if kind == socket_io::BINARY_EVENT || kind == socket_io::BINARY_ACK {
+      let (count, tail) = rest.split_once('-').ok_or("missing attachments count")?;
+      attachments = count.parse().map_err(|_| format!("invalid attachments count {count:?}"))?;
+      rest = tail;
+    }
+
+    let mut nsp = "/".to_string();
+    if rest.starts_with('/') {
+      let end = rest.find(',').unwrap_or(rest.len());
+      nsp = rest[..end].to_string();
+      rest = rest.get(end + 1..).unwrap_or("");
+    }
+
+    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let id = if digits > 0 { Some(rest[..digits].to_string()) } else { None };
+    rest = &rest[digits..];
+
+    let data = if rest.is_empty() {
+      JsonValue::Null
+    } else {
+      json::parse(rest).map_err(|e| format!("invalid packet data: {e}"))?
+    };
+
+    Ok(SocketPacket { kind: kind.into(), nsp, attachments, id, data })
+  }
+
+  pub(crate) fn encode(&self) -> String {
+    let mut text = self.kind.clone();
+    if self.attachments > 0 {
+      text.push_str(&format!("{}-", self.attachments));
+    }
+    if self.nsp != "/" {
+      text.push_str(&self.nsp);
+      text.push(',');
+    }
+    if let Some(id) = &self.id {
+      text.push_str(id);
+    }
+    if !self.data.is_null() {
+      text.push_str(&self.data.dump());
+    }
+    text
+  }
+
+  /// replace attachment placeholders by base64 encoded content
+  pub(crate) fn attach(&mut self, attachments: &[Vec<u8>]) -> Result<(), String> {
+    fn replace(value: &mut JsonValue, attachments: &[Vec<u8>]) -> Result<(), String> {
+      if value["_placeholder"].as_bool() == Some(true) {
+        let num = value["num"].as_usize().ok_or("placeholder without number")?;
+        let data = attachments.get(num).ok_or(format!("missing attachment {num}"))?;
+        *value = base64::engine::general_purpose::STANDARD.encode(data).into();
+        return Ok(());
+      }
+      if value.is_array() {
+        for item in value.members_mut() {
+          replace(item, attachments)?;
+        }
+      } else if value.is_object() {
+        for (_, item) in value.entries_mut() {
+          replace(item, attachments)?;
+        }
+      }
+      Ok(())
+    }
+
+    replace(&mut self.data, attachments)?;
+    self.attachments = 0;
+    self.kind = match self.kind.as_str() {
+      socket_io::BINARY_EVENT => socket_io::EVENT.into(),
+      socket_io::BINARY_ACK => socket_io::ACK.into(),
+      kind => kind.into(),
+    };
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // packets as socket.io-client 4 sends them over polling and websocket
+  const TRANSCRIPT: [&str; 9] = [
+    "40",
+    "40/admin,{\"token\":\"abc\"}",
+    "420[\"find\",\"memories\",{\"oid\":\"yjmg\",\"ctx\":[\"goods\"]}]",
+    "42[\"create\",\"rooms\",{\"oid\":\"yjmg\"}]",
+    "42/admin,13[\"get\",\"roles\",\"owner\",{\"oid\":\"yjmg\"}]",
+    "451-[\"create\",\"picture\",{\"file\":{\"_placeholder\":true,\"num\":0}},{}]",
+    "41",
+    "2probe",
+    "5",
+  ];
+
+  #[test]
+  fn test_engine_packets() {
+    let packets: Vec<EnginePacket> =
+      TRANSCRIPT.iter().map(|t| EnginePacket::decode(t).unwrap()).collect();
+
+    assert_eq!(packets[0], EnginePacket::Message("0".into()));
+    assert_eq!(packets[7], EnginePacket::Ping("probe".into()));
+    assert_eq!(packets[8], EnginePacket::Upgrade);
+
+    for (packet, text) in packets.iter().zip(TRANSCRIPT.iter()) {
+      assert_eq!(&packet.encode(), text);
+    }
+
+    let payload = "2\x1e40\x1ebAQID";
+    let packets = EnginePacket::decode_payload(payload).unwrap();
+    assert_eq!(
+      packets,
+      vec![
+        EnginePacket::Ping("".into()),
+        EnginePacket::Message("0".into()),
+        EnginePacket::Binary(vec![1, 2, 3])
+      ]
+    );
+    let encoded: Vec<String> = packets.iter().map(|p| p.encode()).collect();
+    assert_eq!(EnginePacket::encode_payload(&encoded), payload);
+
+    assert!(EnginePacket::decode("9").is_err());
+    assert!(EnginePacket::decode("").is_err());
+    assert!(EnginePacket::decode("é").is_err());
+  }
+
+  #[test]
+  fn test_socket_packets() {
+    let decode = |text: &str| match EnginePacket::decode(text).unwrap() {
+      EnginePacket::Message(data) => SocketPacket::decode(&data).unwrap(),
+      packet => panic!("unexpected {packet:?}"),
+    };
+
+    let connect = decode(TRANSCRIPT[1]);
+    assert_eq!(connect.kind, socket_io::CONNECT);
+    assert_eq!(connect.nsp, "/admin");
+    assert_eq!(connect.data["token"], "abc");
+
+    let event = decode(TRANSCRIPT[2]);
+    assert_eq!(event.nsp, "/");
+    assert_eq!(event.id, Some("0".into()));
+    assert_eq!(event.data[1], "memories");
+
+    let event = decode(TRANSCRIPT[3]);
+    assert_eq!(event.id, None);
+
+    let event = decode(TRANSCRIPT[4]);
+    assert_eq!(event.nsp, "/admin");
+    assert_eq!(event.id, Some("13".into()));
+
+    let mut binary = decode(TRANSCRIPT[5]);
+    assert_eq!(binary.kind, socket_io::BINARY_EVENT);
+    assert_eq!(binary.attachments, 1);
+    assert_eq!(binary.id, None);
+    binary.attach(&[b"image".to_vec()]).unwrap();
+    assert_eq!(binary.kind, socket_io::EVENT);
+    assert_eq!(binary.data[2]["file"], "aW1hZ2U=");
+    assert!(decode(TRANSCRIPT[5]).attach(&[]).is_err());
+
+    let disconnect = decode(TRANSCRIPT[6]);
+    assert_eq!(disconnect.kind, socket_io::DISCONNECT);
+
+    for text in &TRANSCRIPT[..7] {
+      let packet = decode(text);
+      assert_eq!(EnginePacket::Message(packet.encode()).encode(), *text);
+    }
+
+    let ack = SocketPacket::new(socket_io::ACK, "/admin", Some("13".into()), json::array![null]);
+    assert_eq!(ack.encode(), "3/admin,13[null]");
+
+    assert!(SocketPacket::decode("2[broken").is_err());
+    assert!(SocketPacket::decode("5[]").is_err());
+
+    // multi-byte type inside of message
+    match EnginePacket::decode("4é").unwrap() {
+      EnginePacket::Message(data) => assert!(SocketPacket::decode(&data).is_err()),
+      packet => panic!("unexpected {packet:?}"),
+    }
+  }
+}
@@ -0,0 +1,392 @@
+use actix::prelude::*;
+use actix_web::HttpResponse;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::websocket::{PING_INTERVAL, PING_TIMEOUT};
+use crate::ws::packet::{handshake, EnginePacket};
+use crate::ws::socket::{Out, SocketState};
+use crate::ws::{Connect, Disconnect, WsMessage};
+
+/// Long-polling connections by session id.
+#[derive(Clone, Default)]
+pub struct Polling {
+  conns: Arc<RwLock<HashMap<Uuid, Addr<PollingConn>>>>,
+}
+
+impl Polling {
+  pub(crate) fn get(&self, sid: &Uuid) -> Option<Addr<PollingConn>> {
+    self.conns.read().unwrap().get(sid).cloned()
+  }
+
+  /// new session, response is open packet
+  pub(crate) fn open(&self, state: SocketState, max_payload: usize) -> HttpResponse {
+    let sid = state.sid;
+    let conn = PollingConn {
+      state,
+      queue: Vec::new(),
+      waiting: None,
+      upgraded: false,
+      polling: self.clone(),
+    }
+    .start();
+    self.conns.write().unwrap().insert(sid, conn);
+
+    text(handshake(&sid, &["websocket"], max_payload))
+  }
+
+  /// packets for client, waits till something to send
+  pub(crate) async fn poll(&self, sid: &Uuid) -> HttpResponse {
+    let conn = match self.get(sid) {
+      Some(conn) => conn,
+      None => return error(1, "Session ID unknown"),
+    };
+
+    match conn.send(Poll).await {
+      Ok(Some(receiver)) => match receiver.await {
+        Ok(payload) => text(payload),
+        Err(_) => text(EnginePacket::Close.encode()),
+      },
+      // only one poll at a time, session is closed
+      Ok(None) => error(3, "Bad request"),
+      Err(_) => error(1, "Session ID unknown"),
+    }
+  }
+
+  /// packets from client
+  pub(crate) async fn post(&self, sid: &Uuid, payload: String) -> HttpResponse {
+    let conn = match self.get(sid) {
+      Some(conn) => conn,
+      None => return error(1, "Session ID unknown"),
+    };
+
+    match conn.send(Post(payload)).await {
+      Ok(Ok(_)) => text("ok".into()),
+      Ok(Err(_)) => error(3, "Bad request"),
+      Err(_) => error(1, "Session ID unknown"),
+    }
+  }
+
+  fn remove(&self, sid: &Uuid) {
+    self.conns.write().unwrap().remove(sid);
+  }
+}
+
+pub(crate) fn text(data: String) -> HttpResponse {
+  HttpResponse::Ok().content_type("text/plain; charset=UTF-8").body(data)
+}
+
+pub(crate) fn error(code: u8, message: &str) -> HttpResponse {
+  let data = json::object! { code: code, message: message };
+  HttpResponse::BadRequest().content_type("application/json").body(data.dump())
+}
+
+pub(crate) struct PollingConn {
+  state: SocketState,
+  // encoded packets waiting for poll
+  queue: Vec<String>,
+  waiting: Option<oneshot::Sender<String>>,
+  upgraded: bool,
+  polling: Polling,
+}
+
+impl PollingConn {
+  fn push(&mut self, packet: String) {
+    self.queue.push(packet);
+    self.flush();
+  }
+
+  fn flush(&mut self) {
+    if self.queue.is_empty() {
+      return;
+    }
+    if let Some(waiting) = self.waiting.take() {
+      let payload = EnginePacket::encode_payload(&self.queue);
+      self.queue.clear();
+      // poll request may be gone, client will get packets with next one
+      if let Err(payload) = waiting.send(payload) {
+        self.queue.push(payload);
+      }
+    }
+  }
+}
+
+impl Actor for PollingConn {
+  type Context = Context<Self>;
+
+  fn started(&mut self, ctx: &mut Self::Context) {
+    let socket = ctx.address().recipient();
+    self.state.com.do_send(Connect { socket, sid: self.state.sid });
+
+    let interval = Duration::from_millis(PING_INTERVAL as u64);
+    let timeout = interval + Duration::from_millis(PING_TIMEOUT as u64);
+    ctx.run_interval(interval, move |act, ctx| {
+      if act.state.last_pong.elapsed() > timeout {
+        log::debug!("polling {} timeout", act.state.sid);
+        ctx.stop();
+      } else {
+        act.push(EnginePacket::Ping(String::new()).encode());
+      }
+    });
+  }
+
+  fn stopping(&mut self, _: &mut Self::Context) -> Running {
+    self.polling.remove(&self.state.sid);
+
+    if self.upgraded {
+      // release pending poll, websocket continue the session
+      self.queue.push(EnginePacket::Noop.encode());
+    } else {
+      self.state.com.do_send(Disconnect { sid: self.state.sid });
+      self.queue.push(EnginePacket::Close.encode());
+    }
+    self.flush();
+
+    Running::Stop
+  }
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<oneshot::Receiver<String>>")]
+struct Poll;
+
+impl Handler<Poll> for PollingConn {
+  type Result = Option<oneshot::Receiver<String>>;
+
+  fn handle(&mut self, _: Poll, ctx: &mut Self::Context) -> Self::Result {
+    if self.waiting.is_some() {
+      ctx.stop();
+      return None;
+    }
+
+    let (sender, receiver) = oneshot::channel();
+    self.waiting = Some(sender);
+    self.flush();
+
+    Some(receiver)
+  }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(), String>")]
+struct Post(String);
+
+impl Handler<Post> for PollingConn {
+  type Result = Result<(), String>;
+
+  fn handle(&mut self, msg: Post, ctx: &mut Self::Context) -> Self::Result {
+    let packets = match EnginePacket::decode_payload(&msg.0) {
+      Ok(packets) => packets,
+      Err(e) => {
+        log::warn!("polling {}: {e}", self.state.sid);
+        ctx.stop();
+        return Err(e);
+      },
+    };
+
+    for packet in packets {
+      for out in self.state.on_packet(packet) {
+        match out {
+          Out::Text(data) => self.push(data),
+          Out::Close => ctx.stop(),
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Handler<WsMessage> for PollingConn {
+  type Result = ();
+
+  fn handle(&mut self, msg: WsMessage, _: &mut Self::Context) {
+    self.push(msg.data());
+  }
+}
+
+/// websocket take over the session
+#[derive(Message)]
+#[rtype(result = "Option<SocketState>")]
+pub(crate) struct Upgrade;
+
+impl Handler<Upgrade> for PollingConn {
+  type Result = Option<SocketState>;
+
+  fn handle(&mut self, _: Upgrade, ctx: &mut Self::Context) -> Self::Result {
+    if self.upgraded {
+      return None;
+    }
+    self.upgraded = true;
+    ctx.stop();
+
+    Some(self.state.clone())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::commutator::{Application, Commutator};
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use crate::ws::packet::SEPARATOR;
+  use crate::ws::{polling_post, start_connection};
+  use actix_web::{test, web, App};
+
+  // exchange of socket.io-client 4 with `transports: ["polling"]`
+  #[actix_web::test]
+  async fn test_polling_transcript() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    let com = Commutator::new(app.clone(), events).start();
+
+    let server = test::init_service(
+      App::new()
+        .app_data(web::Data::new(app))
+        .app_data(web::Data::new(com))
+        .app_data(web::Data::new(Polling::default()))
+        .service(web::scope("/socket.io").service(start_connection).service(polling_post)),
+    )
+    .await;
+
+    // handshake
+    let req = test::TestRequest::get()
+      .uri("/socket.io/?EIO=4&transport=polling&t=OQk3b8x")
+      .to_request();
+    let response = String::from_utf8(test::call_and_read_body(&server, req).await.to_vec()).unwrap();
+    assert!(response.starts_with("0{\"sid\":"));
+    let open = json::parse(&response[1..]).unwrap();
+    assert_eq!(open["upgrades"][0], "websocket");
+    assert_eq!(open["pingInterval"], PING_INTERVAL);
+    let sid = open["sid"].as_str().unwrap().to_string();
+
+    let uri = format!("/socket.io/?EIO=4&transport=polling&t=OQk3bB0&sid={sid}");
+
+    // connect to namespaces, second one is unknown
+    let req = test::TestRequest::post().uri(&uri).set_payload("40\x1e40/admin,").to_request();
+    let response = test::call_and_read_body(&server, req).await;
+    assert_eq!(response, "ok");
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let response = String::from_utf8(test::call_and_read_body(&server, req).await.to_vec()).unwrap();
+    let packets: Vec<&str> = response.split('\x1e').collect();
+    assert_eq!(packets[0], format!("40{{\"sid\":\"{sid}\"}}"));
+    assert_eq!(packets[1], "44/admin,{\"message\":\"Invalid namespace\"}");
+
+    // event with acknowledgement, not authenticated
+    let req = test::TestRequest::post()
+      .uri(&uri)
+      .set_payload("420[\"find\",\"memories\",{\"oid\":\"yjmg\",\"ctx\":[\"goods\"]}]")
+      .to_request();
+    test::call_service(&server, req).await;
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let response = String::from_utf8(test::call_and_read_body(&server, req).await.to_vec()).unwrap();
+    assert!(response.starts_with("430[{"));
+    let ack = json::parse(&response[3..]).unwrap();
+    assert_eq!(ack[0]["code"], 401);
+
+    // unknown session
+    let req = test::TestRequest::get()
+      .uri("/socket.io/?EIO=4&transport=polling&sid=2a8f6c7e-3c0e-4a57-8d5c-5d9b1b8d8a31")
+      .to_request();
+    let response = test::call_service(&server, req).await;
+    assert_eq!(response.status(), 400);
+
+    // unsupported version
+    let req = test::TestRequest::get().uri("/socket.io/?EIO=3&transport=polling").to_request();
+    let response = test::call_service(&server, req).await;
+    assert_eq!(response.status(), 400);
+
+    // close
+    let req = test::TestRequest::post().uri(&uri).set_payload("1").to_request();
+    test::call_service(&server, req).await;
+
+    tmp_dir.close().unwrap();
+  }
+
+  const POLLING: &str = include_str!("fixtures/polling.transcript");
+
+  // packets of `fixtures/capture.js` output, `true` if sent by client
+  fn transcript(text: &str) -> Vec<(bool, String)> {
+    text
+      .lines()
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(|line| {
+        let (direction, packet) = line.split_at(2);
+        (direction == "> ", packet.to_string())
+      })
+      .collect()
+  }
+
+  // client packets are posted in order, server ones must come with polls in the same order
+  #[actix_web::test]
+  async fn test_replay_transcripts() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    let com = Commutator::new(app.clone(), events).start();
+
+    let server = test::init_service(
+      App::new()
+        .app_data(web::Data::new(app))
+        .app_data(web::Data::new(com))
+        .app_data(web::Data::new(Polling::default()))
+        .service(web::scope("/socket.io").service(start_connection).service(polling_post)),
+    )
+    .await;
+
+    let mut lines = transcript(POLLING).into_iter().peekable();
+
+    // open packet is response to handshake, it differs by session only
+    let req = test::TestRequest::get().uri("/socket.io/?EIO=4&transport=polling").to_request();
+    let response = String::from_utf8(test::call_and_read_body(&server, req).await.to_vec()).unwrap();
+    let open = json::parse(&response[1..]).unwrap();
+    let sid = open["sid"].as_str().unwrap().to_string();
+
+    let (from_client, expected) = lines.next().unwrap();
+    assert!(!from_client);
+    let expected = json::parse(&expected.replace("{sid}", &sid)[1..]).unwrap();
+    for name in ["sid", "upgrades", "pingInterval", "pingTimeout"] {
+      assert_eq!(open[name], expected[name], "{name}");
+    }
+
+    let uri = format!("/socket.io/?EIO=4&transport=polling&sid={sid}");
+    while let Some(&(from_client, _)) = lines.peek() {
+      if from_client {
+        let mut packets = vec![];
+        while let Some((true, packet)) = lines.peek() {
+          packets.push(packet.clone());
+          lines.next();
+        }
+
+        let payload = EnginePacket::encode_payload(&packets);
+        let req = test::TestRequest::post().uri(&uri).set_payload(payload).to_request();
+        assert_eq!(test::call_and_read_body(&server, req).await, "ok");
+      } else {
+        let mut expected = vec![];
+        while let Some((false, packet)) = lines.peek() {
+          expected.push(packet.replace("{sid}", &sid));
+          lines.next();
+        }
+
+        let mut received: Vec<String> = vec![];
+        while received.len() < expected.len() {
+          let req = test::TestRequest::get().uri(&uri).to_request();
+          let body = test::call_and_read_body(&server, req).await;
+          let payload = String::from_utf8(body.to_vec()).unwrap();
+          received.extend(payload.split(SEPARATOR).map(|p| p.to_string()));
+        }
+        assert_eq!(received, expected);
+      }
+    }
+
+    tmp_dir.close().unwrap();
+  }
+}
@@ -0,0 +1,218 @@
+use actix::Addr;
+use std::collections::HashSet;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::commutator::Commutator;
+use crate::ws::packet::{EnginePacket, SocketPacket};
+use crate::ws::{error_general, socket_io, Event};
+use service::Context;
+
+// namespaces served by commutator
+const NAMESPACES: [&str; 1] = ["/"];
+
+pub(crate) enum Out {
+  // encoded engine.io packet
+  Text(String),
+  Close,
+}
+
+/// socket.io state of connection, shared by websocket and polling transports
+#[derive(Clone)]
+pub(crate) struct SocketState {
+  pub(crate) sid: Uuid,
+  pub(crate) ctx: Context,
+  pub(crate) last_pong: Instant,
+
+  pub(crate) com: Addr<Commutator>,
+  namespaces: HashSet<String>,
+  // binary event waiting for attachments
+  pending: Option<(SocketPacket, Vec<Vec<u8>>)>,
+}
+
+impl SocketState {
+  pub(crate) fn new(sid: Uuid, ctx: Context, com: Addr<Commutator>) -> Self {
+    SocketState {
+      sid,
+      ctx,
+      last_pong: Instant::now(),
+      com,
+      namespaces: HashSet::new(),
+      pending: None,
+    }
+  }
+
+  pub(crate) fn on_packet(&mut self, packet: EnginePacket) -> Vec<Out> {
+    match packet {
+      EnginePacket::Open(_) | EnginePacket::Noop | EnginePacket::Upgrade => vec![],
+      EnginePacket::Close => vec![Out::Close],
+      // "2probe" of upgrade
+      EnginePacket::Ping(data) => vec![Out::Text(EnginePacket::Pong(data).encode())],
+      EnginePacket::Pong(_) => {
+        self.last_pong = Instant::now();
+        vec![]
+      },
+      EnginePacket::Message(text) => match SocketPacket::decode(&text) {
+        Ok(packet) => self.on_socket_packet(packet),
+        Err(e) => {
+          log::warn!("socket {}: {e}", self.sid);
+          vec![]
+        },
+      },
+      EnginePacket::Binary(data) => self.on_attachment(data),
+    }
+  }
+
+  fn on_socket_packet(&mut self, packet: SocketPacket) -> Vec<Out> {
+    match packet.kind.as_str() {
+      socket_io::CONNECT => {
+        if NAMESPACES.contains(&packet.nsp.as_str()) {
+          self.namespaces.insert(packet.nsp.clone());
+          let data = json::object! { sid: self.sid.to_string() };
+          reply(SocketPacket::new(socket_io::CONNECT, &packet.nsp, None, data))
+        } else {
+          let data = json::object! { message: "Invalid namespace" };
+          reply(SocketPacket::new(socket_io::CONNECT_ERROR, &packet.nsp, None, data))
+        }
+      },
+      socket_io::DISCONNECT => {
+        self.namespaces.remove(&packet.nsp);
+        if self.namespaces.is_empty() {
+          vec![Out::Close]
+        } else {
+          vec![]
+        }
+      },
+      socket_io::EVENT => self.dispatch(packet),
+      socket_io::BINARY_EVENT => {
+        if packet.attachments == 0 {
+          self.dispatch(packet)
+        } else {
+          self.pending = Some((packet, Vec::new()));
+          vec![]
+        }
+      },
+      // server doesn't request acknowledgements
+      _ => vec![],
+    }
+  }
+
+  fn on_attachment(&mut self, data: Vec<u8>) -> Vec<Out> {
+    let (mut packet, mut attachments) = match self.pending.take() {
+      Some(pending) => pending,
+      None => {
+        log::warn!("socket {}: unexpected attachment", self.sid);
+        return vec![];
+      },
+    };
+
+    attachments.push(data);
+    if attachments.len() < packet.attachments {
+      self.pending = Some((packet, attachments));
+      return vec![];
+    }
+
+    match packet.attach(&attachments) {
+      Ok(_) => self.dispatch(packet),
+      Err(e) => self.fail(&packet, e),
+    }
+  }
+
+  // ["command", "service", ...arguments]
+  fn dispatch(&mut self, packet: SocketPacket) -> Vec<Out> {
+    if !self.namespaces.contains(&packet.nsp) {
+      log::warn!("socket {}: event for not connected namespace {}", self.sid, packet.nsp);
+      return vec![];
+    }
+
+    let mut data = packet.data.clone();
+    if !data.is_array() {
+      return self.fail(&packet, "unsupported event".into());
+    }
+
+    let command = match data.array_remove(0).as_str() {
+      Some(str) => str.to_string(),
+      None => return self.fail(&packet, "command is missing".into()),
+    };
+
+    let path = match data.array_remove(0).as_str() {
+      Some(str) => str.to_string(),
+      None => return self.fail(&packet, "service path is missing".into()),
+    };
+
+    self.com.do_send(Event {
+      ctx: self.ctx.clone(),
+      sid: self.sid,
+      nsp: packet.nsp,
+      event_id: packet.id.unwrap_or_default(),
+      path,
+      command,
+      data,
+    });
+
+    vec![]
+  }
+
+  fn fail(&self, packet: &SocketPacket, error: String) -> Vec<Out> {
+    match &packet.id {
+      Some(id) => {
+        let data = json::array![error_general(error)];
+        reply(SocketPacket::new(socket_io::ACK, &packet.nsp, Some(id.clone()), data))
+      },
+      None => {
+        log::warn!("socket {}: {error}", self.sid);
+        vec![]
+      },
+    }
+  }
+}
+
+fn reply(packet: SocketPacket) -> Vec<Out> {
+  vec![Out::Text(EnginePacket::Message(packet.encode()).encode())]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::commutator::Application;
+  use crate::storage::Workspaces;
+  use crate::warehouse::test_util::init;
+  use actix::Actor;
+  use std::sync::Arc;
+
+  fn texts(outs: Vec<Out>) -> Vec<String> {
+    outs
+      .into_iter()
+      .map(|out| match out {
+        Out::Text(text) => text,
+        Out::Close => "close".into(),
+      })
+      .collect()
+  }
+
+  #[actix_web::test]
+  async fn test_connect_namespaces() {
+    let (tmp_dir, settings, db) = init();
+
+    let wss = Workspaces::new(tmp_dir.path().join("companies"));
+    let (app, events) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+    let com = Commutator::new(app, events).start();
+
+    let sid = Uuid::new_v4();
+    let mut state = SocketState::new(sid, Context::local(), com);
+
+    // websocket frames are passed as is
+    let mut send = |text: &str| texts(state.on_packet(EnginePacket::decode(text).unwrap()));
+
+    assert_eq!(send("40"), vec![format!("40{{\"sid\":\"{sid}\"}}")]);
+    assert_eq!(
+      send("40/admin,{\"token\":\"abc\"}"),
+      vec!["44/admin,{\"message\":\"Invalid namespace\"}".to_string()]
+    );
+
+    // events of rejected namespace are ignored
+    assert!(send("42/admin,13[\"get\",\"roles\",\"owner\",{\"oid\":\"yjmg\"}]").is_empty());
+
+    assert_eq!(send("41"), vec!["close".to_string()]);
+  }
+}
@@ -1,18 +1,87 @@
-use crate::commutator::Commutator;
+use crate::commutator::{Application, Commutator};
 use crate::websocket::WsConn;
+use crate::ws::polling::{error, Polling};
+use crate::ws::socket::SocketState;
 use actix::Addr;
-use actix_web::{get, web, Error, HttpRequest, HttpResponse};
+use actix_web::{get, post, web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
+use service::Context;
+use uuid::Uuid;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct EngineQuery {
+  #[serde(rename = "EIO")]
+  eio: Option<String>,
+  transport: Option<String>,
+  sid: Option<String>,
+}
+
+impl EngineQuery {
+  fn check(&self) -> Result<Option<Uuid>, HttpResponse> {
+    if self.eio.as_deref() != Some("4") {
+      return Err(error(5, "Unsupported protocol version"));
+    }
+    match &self.sid {
+      Some(sid) => Uuid::parse_str(sid).map(Some).map_err(|_| error(1, "Session ID unknown")),
+      None => Ok(None),
+    }
+  }
+}
 
 #[get("/")]
 pub async fn start_connection(
   req: HttpRequest,
   stream: web::Payload,
+  query: web::Query<EngineQuery>,
   srv: web::Data<Addr<Commutator>>,
+  polling: web::Data<Polling>,
+  app: web::Data<Application>,
+) -> Result<HttpResponse, Error> {
+  let max_payload = app.limits.message_size();
+
+  let sid = match query.check() {
+    Ok(sid) => sid,
+    Err(response) => return Ok(response),
+  };
+
+  match query.transport.as_deref() {
+    Some("websocket") => {
+      // upgrade of polling session
+      let upgrade = match sid {
+        Some(sid) => match polling.get(&sid) {
+          Some(conn) => Some((sid, conn)),
+          None => return Ok(error(1, "Session ID unknown")),
+        },
+        None => None,
+      };
+      let connection = WsConn::new(req.head().clone(), srv.get_ref().clone(), upgrade, max_payload);
+      ws::WsResponseBuilder::new(connection, &req, stream)
+        .frame_size(max_payload)
+        .start()
+    },
+    Some("polling") => match sid {
+      Some(sid) => Ok(polling.poll(&sid).await),
+      None => {
+        let ctx = Context::websocket(req.head().clone());
+        let state = SocketState::new(Uuid::new_v4(), ctx, srv.get_ref().clone());
+        Ok(polling.open(state, max_payload))
+      },
+    },
+    _ => Ok(error(0, "Transport unknown")),
+  }
+}
+
+#[post("/")]
+pub async fn polling_post(
+  query: web::Query<EngineQuery>,
+  body: String,
+  polling: web::Data<Polling>,
 ) -> Result<HttpResponse, Error> {
-  // println!("account {account:?}");
-  let connection = WsConn::new(req.head().clone(), srv.get_ref().clone());
-  let resp = ws::start(connection, &req, stream);
-  println!("resp {:?}", resp);
-  resp
+  match query.check() {
+    Ok(Some(sid)) if query.transport.as_deref() == Some("polling") => {
+      Ok(polling.post(&sid, body).await)
+    },
+    Ok(_) => Ok(error(3, "Bad request")),
+    Err(response) => Ok(response),
+  }
 }
@@ -0,0 +1,42 @@
+/// Check digit of GTIN (EAN-8, UPC-A, EAN-13, GTIN-14) for code without it.
+pub fn check_digit(body: &str) -> Option<char> {
+  if body.is_empty() || !body.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+
+  // weights 3 and 1 from the right
+  let sum: u32 = body
+    .chars()
+    .rev()
+    .enumerate()
+    .map(|(i, c)| c.to_digit(10).unwrap_or_default() * if i % 2 == 0 { 3 } else { 1 })
+    .sum();
+
+  char::from_digit((10 - sum % 10) % 10, 10)
+}
+
+/// Code of GTIN length with correct check digit.
+pub fn is_valid_gtin(code: &str) -> bool {
+  if ![8, 12, 13, 14].contains(&code.len()) {
+    return false;
+  }
+  let (body, digit) = code.split_at(code.len() - 1);
+  check_digit(body).map(|c| digit.starts_with(c)).unwrap_or(false)
+}
+
+/// Numeric codes must be valid GTIN, others are internal codes.
+pub fn is_valid(code: &str) -> bool {
+  if code.is_empty() {
+    false
+  } else if code.chars().all(|c| c.is_ascii_digit()) {
+    is_valid_gtin(code)
+  } else {
+    true
+  }
+}
+
+/// Form of batch barcode issued before check digit was added: "2" + yymmdd + 5 digits. It has
+/// no check digit, so it's only good for lookup of registered batch, not for validation.
+pub fn is_legacy_batch(code: &str) -> bool {
+  code.len() == 12 && code.starts_with('2') && code.chars().all(|c| c.is_ascii_digit())
+}
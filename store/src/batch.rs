@@ -41,13 +41,16 @@ impl Batch {
     }
   }
 
-  pub(crate) fn to_barcode(&self) -> String {
+  /// EAN-13 with restricted circulation prefix: "2" + yymmdd + 5 digits of id + check digit
+  pub fn to_barcode(&self) -> String {
     let date = self.date.to_string();
     let mut id: String = self.id.to_string().chars().filter(|c| *c >= '0' && *c <= '9').collect();
     while id.len() < 5 {
       id.push('0');
     }
-    format!("2{}{}{}{}", &date[2..4], &date[5..7], &date[8..10], &id[0..5])
+    let body = format!("2{}{}{}{}", &date[2..4], &date[5..7], &date[8..10], &id[0..5]);
+    let digit = crate::barcode::check_digit(&body).unwrap_or('0');
+    format!("{body}{digit}")
   }
 
   pub(crate) fn to_bytes(&self, goods: &Goods) -> Vec<u8> {
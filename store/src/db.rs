@@ -8,11 +8,11 @@ use super::{
   elements::{Report, Store},
   error::WHError,
 };
-use crate::balance::Balance;
+use crate::balance::{Balance, Cost};
 use crate::batch::Batch;
 use crate::checkpoints::CheckpointTopology;
-use crate::elements::Goods;
-use crate::operations::{Op, OpMutation};
+use crate::elements::{Goods, Mode, Qty, UUID_MAX};
+use crate::operations::{InternalOperation, Op, OpMutation};
 use crate::ordered_topology::OrderedTopology;
 use json::JsonValue;
 use log::debug;
@@ -89,6 +89,29 @@ impl Db {
     Ok(balances)
   }
 
+  /// balances of goods at store by batches at `date`, zero balances are skipped
+  pub fn get_balance_for_store_goods(
+    &self,
+    date: DateTime<Utc>,
+    store: Store,
+    goods: Goods,
+  ) -> Result<HashMap<Batch, BalanceForGoods>, WHError> {
+    // operations of store and goods till end of date
+    let till = Op {
+      id: UUID_MAX,
+      date,
+      store,
+      goods,
+      batch: Batch::MAX(),
+      store_into: None,
+      op: InternalOperation::Issue(Qty::ZERO, Cost::ZERO, Mode::Manual),
+      is_dependent: true,
+      dependant: vec![],
+    };
+
+    self.balances_for_store_goods_before_operation(&till)
+  }
+
   fn closest_checkpoint_balances_for_store_goods(
     &self,
     op: &Op,
@@ -218,6 +241,22 @@ impl Db {
     Err(WHError::new("fn get_report not implemented"))
   }
 
+  pub fn get_ops_for_storage(
+    &self,
+    storage: Store,
+    from_date: DateTime<Utc>,
+    till_date: DateTime<Utc>,
+  ) -> Result<Vec<Op>, WHError> {
+    for ordered_topology in self.ordered_topologies.iter() {
+      match ordered_topology.get_ops_for_storage(storage, from_date, till_date) {
+        Ok(ops) => return Ok(ops),
+        Err(_) => {}, // ignore
+      }
+    }
+
+    Err(WHError::new("fn get_ops_for_storage not implemented"))
+  }
+
   pub fn get_report_for_storage(
     &self,
     storage: Store,
@@ -150,31 +150,44 @@ pub fn receive_data(
   ctx: &Vec<String>,
   before: JsonValue,
 ) -> Result<JsonValue, WHError> {
+  let ops = data_to_ops(app, wid, &data, ctx, &before)?;
+
+  if !ops.is_empty() {
+    app.warehouse().mutate(&ops)?;
+  }
+
+  Ok(data)
+}
+
+/// warehouse mutations caused by change of document, without applying them
+pub fn data_to_ops(
+  app: &(impl GetWarehouse + Services),
+  wid: &str,
+  data: &JsonValue,
+  ctx: &Vec<String>,
+  before: &JsonValue,
+) -> Result<Vec<OpMutation>, WHError> {
   // TODO if structure of input Json is invalid, should return it without changes and save it to memories anyway
   // If my data was corrupted, should rewrite it and do the operations
   // TODO tests with invalid structure of incoming JsonValue
   log::debug!("BEFOR: {:?}", before.dump());
   log::debug!("AFTER: {:?}", data.dump());
 
-  let old_data = data.clone();
-  let mut new_data = data.clone();
-  let mut new_before = before.clone();
-
-  let before = match json_to_ops(app, wid, &new_before, ctx) {
+  let before = match json_to_ops(app, wid, before, ctx) {
     Ok(res) => res,
     Err(e) => {
       println!("_WHERROR_ BEFORE: {}", e.message());
       println!("{}", data.dump());
-      return Ok(old_data);
+      return Ok(vec![]);
     },
   };
 
-  let mut after = match json_to_ops(app, wid, &new_data, ctx) {
+  let mut after = match json_to_ops(app, wid, data, ctx) {
     Ok(res) => res,
     Err(e) => {
       println!("_WHERROR_ AFTER: {}", e.message());
       println!("{}", data.dump());
-      return Ok(old_data);
+      return Ok(vec![]);
     },
   };
 
@@ -211,12 +224,7 @@ pub fn receive_data(
 
   log::debug!("OPS: {:#?}", ops);
 
-  if ops.is_empty() {
-    Ok(old_data)
-  } else {
-    app.warehouse().mutate(&ops)?;
-    Ok(new_data)
-  }
+  Ok(ops)
 }
 
 #[derive(PartialEq, Clone)]
@@ -2,6 +2,7 @@ use wh_storage::WHStorage;
 
 pub mod aggregations;
 pub mod balance;
+pub mod barcode;
 pub mod batch;
 pub mod checkpoints;
 mod db;
@@ -93,7 +93,7 @@ fn json(
     match items.len() {
       0 => memories_create(app, item(), ctx),
       1 => Ok(items[0].clone()),
-      _ => unreachable!("two or more same items in db"),
+      n => Err(Error::Conflict(format!("{n} items at {ctx:?} match {}", item().dump()))),
     }
   } else {
     memories_create(app, item(), ctx)
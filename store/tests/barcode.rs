@@ -0,0 +1,37 @@
+use store::barcode::{check_digit, is_legacy_batch, is_valid, is_valid_gtin};
+use store::batch::Batch;
+use store::elements::dt;
+use uuid::Uuid;
+
+#[test]
+fn store_test_barcode_check_digit() {
+    assert_eq!(check_digit("460123456789"), Some('3'));
+    assert_eq!(check_digit("9638507"), Some('4'));
+    assert_eq!(check_digit("03600029145"), Some('2'));
+    assert_eq!(check_digit(""), None);
+    assert_eq!(check_digit("12a"), None);
+
+    assert!(is_valid_gtin("4601234567893"));
+    assert!(is_valid_gtin("96385074"));
+    assert!(is_valid_gtin("036000291452"));
+    assert!(!is_valid_gtin("4601234567890"));
+    assert!(!is_valid_gtin("123"));
+
+    assert!(is_valid("INT-0001"));
+    assert!(!is_valid("4601234567890"));
+    assert!(!is_valid(""));
+}
+
+#[test]
+fn store_test_batch_barcode() {
+    let batch = Batch { id: Uuid::from_u128(12345), date: dt("2023-02-17").unwrap() };
+    let barcode = batch.to_barcode();
+
+    assert_eq!(barcode.len(), 13);
+    assert!(barcode.starts_with("2230217"));
+    assert!(is_valid_gtin(&barcode));
+
+    assert!(is_legacy_batch(&barcode[..12]));
+    assert!(!is_valid(&barcode[..12]));
+    assert!(!is_valid("200000000000"));
+}
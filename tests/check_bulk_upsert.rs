@@ -0,0 +1,115 @@
+mod test_init;
+
+use chrono::Utc;
+use json::object;
+use std::sync::Arc;
+use test_init::init;
+
+use crate::test_init::{goods, store};
+use nae_backend::commutator::Application;
+use nae_backend::memories::MemoriesInFiles;
+use nae_backend::storage::Workspaces;
+use service::{Context, Services};
+use store::GetWarehouse;
+
+const WID: &str = "yjmgJUmDo_kn9uxVi8s9Mj9mgGRJISxRt63wT46NyTQ";
+
+#[actix_web::test]
+async fn check_bulk_upsert() {
+  let (tmp_dir, settings, db) = init();
+
+  let wss = Workspaces::new(tmp_dir.path().join("companies"));
+
+  let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+
+  app.register(MemoriesInFiles::new(app.clone(), "memories"));
+
+  let params = object! { oid: WID, ctx: vec!["goods"], upsert: "code" };
+
+  let result = app
+    .service("memories")
+    .create(
+      Context::local(),
+      json::array![
+        object! { code: "1", name: "g1" },
+        object! { code: "2", name: "g2" },
+        "not an object",
+        object! { name: "without code" },
+      ],
+      params.clone(),
+    )
+    .unwrap();
+
+  assert_eq!(result["created"], 2);
+  assert_eq!(result["updated"], 0);
+  assert_eq!(result["failed"], 2);
+  assert_eq!(result["data"][0]["status"], "created");
+  assert_eq!(result["data"][2]["status"], "failed");
+  assert_eq!(result["data"][3]["row"], 3);
+
+  let g1 = result["data"][0]["_uuid"].clone();
+
+  // same rows again: update by natural key
+  let result = app
+    .service("memories")
+    .create(
+      Context::local(),
+      json::array![
+        object! { code: "1", name: "g1 renamed" },
+        object! { code: "2", name: "g2" },
+        object! { code: "3", name: "g3" },
+      ],
+      params.clone(),
+    )
+    .unwrap();
+
+  assert_eq!(result["created"], 1);
+  assert_eq!(result["updated"], 1);
+  assert_eq!(result["failed"], 0);
+  assert_eq!(result["data"][0]["_uuid"], g1);
+  assert_eq!(result["data"][1]["status"], "unchanged");
+
+  let doc = app
+    .service("memories")
+    .get(Context::local(), g1.to_string(), params.clone())
+    .unwrap();
+  assert_eq!(doc["name"], "g1 renamed");
+
+  // warehouse effects of all rows applied in one batch
+  let s1 = store(&app, "s1");
+  let g4 = goods(&app, "g4");
+
+  let result = app
+    .service("memories")
+    .create(
+      Context::local(),
+      json::array![
+        object! {
+          date: "2023-01-20",
+          storage: s1.to_string(),
+          goods: g4.to_string(),
+          qty: object! {number: "3.0"},
+          cost: object! {number: "0.3"},
+        },
+        object! {
+          date: "2023-01-21",
+          storage: s1.to_string(),
+          goods: g4.to_string(),
+          qty: object! {number: "2.0"},
+          cost: object! {number: "0.2"},
+        },
+      ],
+      object! { oid: WID, ctx: vec!["warehouse", "receive"] },
+    )
+    .unwrap();
+
+  assert_eq!(result["created"], 2);
+  assert!(result["warehouse"]["error"].is_null());
+
+  let balances = app.warehouse().database.get_balance_for_all(Utc::now()).unwrap();
+  let batches = balances.get(&s1).unwrap().get(&g4).unwrap();
+  let qty: rust_decimal::Decimal = batches.values().map(|b| b.qty).sum();
+  assert_eq!(qty, 5.into());
+
+  tmp_dir.close().unwrap();
+}
@@ -0,0 +1,121 @@
+mod test_init;
+
+use json::object;
+use std::sync::Arc;
+use test_init::init;
+use uuid::Uuid;
+
+use crate::test_init::{document_create, goods, store};
+use nae_backend::commutator::Application;
+use nae_backend::memories::{MemoriesInFiles, References};
+use nae_backend::storage::Workspaces;
+use service::error::Error;
+use service::{Context, Services};
+
+const WID: &str = "yjmgJUmDo_kn9uxVi8s9Mj9mgGRJISxRt63wT46NyTQ";
+
+#[actix_web::test]
+async fn check_references() {
+  let (tmp_dir, settings, db) = init();
+
+  let wss = Workspaces::new(tmp_dir.path().join("companies"));
+
+  let (mut app, _) = Application::new(Arc::new(settings), Arc::new(db), wss).await.unwrap();
+
+  app.register(MemoriesInFiles::new(app.clone(), "memories"));
+  app.register(References::new(app.clone()));
+
+  let s1 = store(&app, "s1");
+  let g1 = goods(&app, "g1");
+  let g2 = goods(&app, "g2");
+
+  let receive = document_create(
+    &app,
+    object! {
+      date: "2023-01-20",
+      storage: s1.to_string(),
+      goods: g1.to_string(),
+      qty: object! {number: "3.0"},
+      cost: object! {number: "0.3"},
+    },
+    vec!["warehouse", "receive"],
+  );
+
+  // where used
+  let used = app
+    .service("references")
+    .find(Context::local(), object! { oid: WID, target: g1.to_string() })
+    .unwrap();
+  assert_eq!(used["total"], 1);
+  assert_eq!(used["data"][0]["source"], receive["_uuid"]);
+  assert_eq!(used["data"][0]["field"], "goods");
+
+  let unused = app
+    .service("references")
+    .find(Context::local(), object! { oid: WID, target: g2.to_string() })
+    .unwrap();
+  assert_eq!(unused["total"], 0);
+
+  // goods that is used can't be deleted
+  let result = app.service("memories").patch(
+    Context::local(),
+    g1.to_string(),
+    object! { status: "deleted" },
+    object! { oid: WID, ctx: vec!["warehouse", "goods"] },
+  );
+  match result {
+    Err(Error::Conflict(_)) => {},
+    r => panic!("expected conflict, got {r:?}"),
+  }
+
+  // but not used one can
+  app
+    .service("memories")
+    .patch(
+      Context::local(),
+      g2.to_string(),
+      object! { status: "deleted" },
+      object! { oid: WID, ctx: vec!["warehouse", "goods"] },
+    )
+    .unwrap();
+
+  // switching to other goods release reference
+  app
+    .service("memories")
+    .patch(
+      Context::local(),
+      receive["_uuid"].to_string(),
+      object! { goods: g2.to_string() },
+      object! { oid: WID, ctx: vec!["warehouse", "receive"] },
+    )
+    .unwrap();
+
+  let used = app
+    .service("references")
+    .find(Context::local(), object! { oid: WID, target: g1.to_string() })
+    .unwrap();
+  assert_eq!(used["total"], 0);
+
+  // reference to unknown goods
+  let broken = document_create(
+    &app,
+    object! {
+      date: "2023-01-21",
+      storage: s1.to_string(),
+      goods: Uuid::new_v4().to_string(),
+      qty: object! {number: "1.0"},
+      cost: object! {number: "0.1"},
+    },
+    vec!["warehouse", "receive"],
+  );
+
+  let dangling = app
+    .service("references")
+    .find(Context::local(), object! { oid: WID, dangling: true })
+    .unwrap();
+  assert_eq!(dangling["total"], 1);
+  assert_eq!(dangling["data"][0]["source"], broken["_uuid"]);
+  assert_eq!(dangling["data"][0]["field"], "goods");
+
+  tmp_dir.close().unwrap();
+}